@@ -0,0 +1,439 @@
+//! `chip8 asm`: turns source in the syntax `chip8 disasm` prints back into a ROM.
+//!
+//! Each line holds at most one instruction, in Cowgod's mnemonics as `Instruction` displays
+//! them, e.g. `LD V1, #0A` or `DRW V0, V1, 5`, optionally after a `label:`, and anything after
+//! a `;` is a comment. Numbers are decimal, or hex after `#` or `0x`, and a label stands for
+//! the address of what follows it. `DB` and `DW` put bytes and 16-bit words in as they are.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use chip8_core::{encode, Instruction, PROGRAM_START};
+
+/// Assemble `source` into a ROM to be loaded at `PROGRAM_START`.
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let lines: Vec<Line> = source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| Line::parse(i + 1, text))
+        .collect::<Result<_, _>>()?;
+
+    // The first pass only needs sizes, so that labels used before they're defined resolve.
+    let mut labels = HashMap::new();
+    let mut addr = PROGRAM_START;
+    for line in &lines {
+        if let Some(label) = line.label {
+            if labels.insert(label, addr).is_some() {
+                return Err(line.error(AsmErrorKind::DuplicateLabel(label.to_string())));
+            }
+        }
+        addr += line.size();
+    }
+
+    let mut rom = Vec::new();
+    for line in &lines {
+        line.emit(&labels, &mut rom)
+            .map_err(|kind| line.error(kind))?;
+    }
+
+    Ok(rom)
+}
+
+/// A line of source, split into its parts but not yet understood.
+struct Line<'a> {
+    number: usize,
+    label: Option<&'a str>,
+    mnemonic: Option<String>,
+    operands: Vec<&'a str>,
+}
+
+impl<'a> Line<'a> {
+    fn parse(number: usize, text: &'a str) -> Result<Self, AsmError> {
+        let mut text = text.split(';').next().unwrap_or_default().trim();
+        let mut label = None;
+        if let Some((name, rest)) = text.split_once(':') {
+            let name = name.trim();
+            if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                return Err(AsmError {
+                    line: number,
+                    kind: AsmErrorKind::BadLabel(name.to_string()),
+                });
+            }
+            label = Some(name);
+            text = rest.trim();
+        }
+
+        let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+            Some((mnemonic, operands)) => (mnemonic, operands.split(',').map(str::trim).collect()),
+            None => (text, Vec::new()),
+        };
+
+        Ok(Line {
+            number,
+            label,
+            mnemonic: (!mnemonic.is_empty()).then(|| mnemonic.to_ascii_uppercase()),
+            operands,
+        })
+    }
+
+    fn error(&self, kind: AsmErrorKind) -> AsmError {
+        AsmError {
+            line: self.number,
+            kind,
+        }
+    }
+
+    /// How many bytes the line assembles to.
+    fn size(&self) -> usize {
+        match self.mnemonic.as_deref() {
+            None => 0,
+            Some("DB") => self.operands.len(),
+            Some("DW") => self.operands.len() * 2,
+            Some(_) => 2,
+        }
+    }
+
+    fn emit(&self, labels: &HashMap<&str, usize>, rom: &mut Vec<u8>) -> Result<(), AsmErrorKind> {
+        let Some(mnemonic) = self.mnemonic.as_deref() else {
+            return Ok(());
+        };
+        let operands = Operands {
+            operands: &self.operands,
+            labels,
+        };
+
+        match mnemonic {
+            "DB" => {
+                for i in 0..self.operands.len() {
+                    rom.push(operands.number(i, 0xFF)? as u8);
+                }
+            }
+            "DW" => {
+                for i in 0..self.operands.len() {
+                    rom.extend_from_slice(&(operands.number(i, 0xFFFF)? as u16).to_be_bytes());
+                }
+            }
+            _ => {
+                let instruction = instruction(mnemonic, &operands)?;
+                rom.extend_from_slice(&encode(instruction).to_be_bytes());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A line's operands, and the labels they can refer to.
+struct Operands<'a> {
+    operands: &'a [&'a str],
+    labels: &'a HashMap<&'a str, usize>,
+}
+
+impl Operands<'_> {
+    fn shape(&self) -> Vec<Operand> {
+        self.operands
+            .iter()
+            .map(|operand| Operand::of(operand))
+            .collect()
+    }
+
+    /// Operand `i` as a number or label no bigger than `max`.
+    fn number(&self, i: usize, max: usize) -> Result<usize, AsmErrorKind> {
+        let text = self.operands.get(i).ok_or(AsmErrorKind::BadOperands)?;
+        let value = match parse_number(text) {
+            Some(value) => value,
+            None if text.starts_with(|c: char| c.is_alphabetic() || c == '_') => *self
+                .labels
+                .get(text)
+                .ok_or_else(|| AsmErrorKind::UnknownLabel(text.to_string()))?,
+            None => return Err(AsmErrorKind::BadNumber(text.to_string())),
+        };
+        if value > max {
+            return Err(AsmErrorKind::OutOfRange(text.to_string(), max));
+        }
+
+        Ok(value)
+    }
+
+    fn addr(&self, i: usize) -> Result<u16, AsmErrorKind> {
+        self.number(i, 0xFFF).map(|addr| addr as u16)
+    }
+
+    fn byte(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        self.number(i, 0xFF).map(|kk| kk as u8)
+    }
+
+    fn nibble(&self, i: usize) -> Result<u8, AsmErrorKind> {
+        self.number(i, 0xF).map(|n| n as u8)
+    }
+}
+
+/// What kind of thing an operand is, for telling an instruction's forms apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand {
+    V(u8),
+    I,
+    /// `[I]`, the memory `I` points at.
+    AtI,
+    Dt,
+    St,
+    K,
+    F,
+    B,
+    /// A number or a label.
+    Value,
+}
+
+impl Operand {
+    fn of(text: &str) -> Self {
+        match text.to_ascii_uppercase().as_str() {
+            "I" => Operand::I,
+            "[I]" => Operand::AtI,
+            "DT" => Operand::Dt,
+            "ST" => Operand::St,
+            "K" => Operand::K,
+            "F" => Operand::F,
+            "B" => Operand::B,
+            register => match register.strip_prefix('V') {
+                Some(digit) if digit.len() == 1 => match u8::from_str_radix(digit, 16) {
+                    Ok(x) => Operand::V(x),
+                    Err(_) => Operand::Value,
+                },
+                _ => Operand::Value,
+            },
+        }
+    }
+}
+
+fn instruction(mnemonic: &str, operands: &Operands) -> Result<Instruction, AsmErrorKind> {
+    use Instruction::*;
+    use Operand::{AtI, Dt, St, Value, V};
+
+    let instruction = match (mnemonic, operands.shape().as_slice()) {
+        ("HALT", []) => Halt,
+        ("SYS", [Value]) => Sys {
+            addr: operands.addr(0)?,
+        },
+        ("SCD", [Value]) => Scd {
+            n: operands.nibble(0)?,
+        },
+        ("CLS", []) => Cls,
+        ("RET", []) => Ret,
+        ("SCR", []) => Scr,
+        ("SCL", []) => Scl,
+        ("LOW", []) => Low,
+        ("HIGH", []) => High,
+        ("JP", [Value]) => Jp {
+            addr: operands.addr(0)?,
+        },
+        ("JP", [V(0), Value]) => {
+            let addr = operands.addr(1)?;
+            JpV0 {
+                x: (addr >> 8) as u8,
+                addr,
+            }
+        }
+        ("CALL", [Value]) => Call {
+            addr: operands.addr(0)?,
+        },
+        ("SE", [V(x), Value]) => SeByte {
+            x: *x,
+            kk: operands.byte(1)?,
+        },
+        ("SE", [V(x), V(y)]) => SeReg { x: *x, y: *y },
+        ("SNE", [V(x), Value]) => SneByte {
+            x: *x,
+            kk: operands.byte(1)?,
+        },
+        ("SNE", [V(x), V(y)]) => SneReg { x: *x, y: *y },
+        ("LD", [V(x), Value]) => LdByte {
+            x: *x,
+            kk: operands.byte(1)?,
+        },
+        ("LD", [V(x), V(y)]) => LdReg { x: *x, y: *y },
+        ("LD", [Operand::I, Value]) => LdI {
+            addr: operands.addr(1)?,
+        },
+        ("LD", [V(x), Dt]) => LdVxDt { x: *x },
+        ("LD", [V(x), Operand::K]) => LdVxK { x: *x },
+        ("LD", [Dt, V(x)]) => LdDtVx { x: *x },
+        ("LD", [St, V(x)]) => LdStVx { x: *x },
+        ("LD", [Operand::F, V(x)]) => LdF { x: *x },
+        ("LD", [Operand::B, V(x)]) => LdB { x: *x },
+        ("LD", [AtI, V(x)]) => LdIVx { x: *x },
+        ("LD", [V(x), AtI]) => LdVxI { x: *x },
+        ("ADD", [V(x), Value]) => AddByte {
+            x: *x,
+            kk: operands.byte(1)?,
+        },
+        ("ADD", [V(x), V(y)]) => AddReg { x: *x, y: *y },
+        ("ADD", [Operand::I, V(x)]) => AddI { x: *x },
+        ("OR", [V(x), V(y)]) => Or { x: *x, y: *y },
+        ("AND", [V(x), V(y)]) => And { x: *x, y: *y },
+        ("XOR", [V(x), V(y)]) => Xor { x: *x, y: *y },
+        ("SUB", [V(x), V(y)]) => Sub { x: *x, y: *y },
+        ("SUBN", [V(x), V(y)]) => Subn { x: *x, y: *y },
+        ("SHR", [V(x)]) => Shr { x: *x, y: *x },
+        ("SHR", [V(x), V(y)]) => Shr { x: *x, y: *y },
+        ("SHL", [V(x)]) => Shl { x: *x, y: *x },
+        ("SHL", [V(x), V(y)]) => Shl { x: *x, y: *y },
+        ("RND", [V(x), Value]) => Rnd {
+            x: *x,
+            kk: operands.byte(1)?,
+        },
+        ("DRW", [V(x), V(y), Value]) => Drw {
+            x: *x,
+            y: *y,
+            n: operands.nibble(2)?,
+        },
+        ("SKP", [V(x)]) => Skp { x: *x },
+        ("SKNP", [V(x)]) => Sknp { x: *x },
+        ("PLANE", [Value]) => Plane {
+            n: operands.nibble(0)?,
+        },
+        ("AUDIO", []) => Audio,
+        ("PITCH", [V(x)]) => Pitch { x: *x },
+        (
+            "HALT" | "SYS" | "SCD" | "CLS" | "RET" | "SCR" | "SCL" | "LOW" | "HIGH" | "JP" | "CALL"
+            | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND" | "XOR" | "SUB" | "SUBN" | "SHR" | "SHL"
+            | "RND" | "DRW" | "SKP" | "SKNP" | "PLANE" | "AUDIO" | "PITCH",
+            _,
+        ) => return Err(AsmErrorKind::BadOperands),
+        _ => return Err(AsmErrorKind::UnknownMnemonic(mnemonic.to_string())),
+    };
+
+    Ok(instruction)
+}
+
+/// A number written in decimal, or in hex after `#` or `0x`.
+fn parse_number(text: &str) -> Option<usize> {
+    let hex = text
+        .strip_prefix('#')
+        .or_else(|| text.strip_prefix("0x"))
+        .or_else(|| text.strip_prefix("0X"));
+    match hex {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+/// Why a line of source couldn't be assembled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    /// Counting from 1.
+    pub line: usize,
+    kind: AsmErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum AsmErrorKind {
+    UnknownMnemonic(String),
+    BadOperands,
+    BadLabel(String),
+    DuplicateLabel(String),
+    UnknownLabel(String),
+    BadNumber(String),
+    /// The operand, and the most it can be.
+    OutOfRange(String, usize),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(mnemonic) => {
+                write!(f, "{} isn't an instruction", mnemonic)
+            }
+            AsmErrorKind::BadOperands => write!(f, "the instruction doesn't take those operands"),
+            AsmErrorKind::BadLabel(label) => write!(f, "{:?} isn't a label name", label),
+            AsmErrorKind::DuplicateLabel(label) => write!(f, "{} is already defined", label),
+            AsmErrorKind::UnknownLabel(label) => write!(f, "{} isn't defined", label),
+            AsmErrorKind::BadNumber(text) => write!(f, "{:?} isn't a number", text),
+            AsmErrorKind::OutOfRange(text, max) => {
+                write!(f, "{} is bigger than the #{:X} that fits", text, max)
+            }
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::decode;
+
+    use super::*;
+
+    fn error(source: &str) -> (usize, AsmErrorKind) {
+        let error = assemble(source).unwrap_err();
+        (error.line, error.kind)
+    }
+
+    #[test]
+    fn assembles_whatever_disasm_prints_back_into_the_same_opcode() {
+        for opcode in 0..=u16::MAX {
+            let text = decode(opcode).to_string();
+            let rom = assemble(&text).unwrap_or_else(|e| panic!("{:04X} {}: {}", opcode, text, e));
+            assert_eq!(rom, opcode.to_be_bytes(), "{:04X} {}", opcode, text);
+        }
+    }
+
+    #[test]
+    fn labels_can_be_used_before_they_are_defined() {
+        let source = "start: call sub\n\
+                      jp start\n\
+                      sub: ld v0, #0A\n\
+                      ret\n";
+        assert_eq!(
+            assemble(source).unwrap(),
+            [0x22, 0x04, 0x12, 0x00, 0x60, 0x0A, 0x00, 0xEE]
+        );
+    }
+
+    #[test]
+    fn skips_comments_and_blank_lines() {
+        let source = "; a comment on its own\n\
+                      \n\
+                      CLS ; clear the screen\n\
+                      data: DB 1, #02, 0x3 ; bytes\n\
+                      DW #1234\n";
+        assert_eq!(
+            assemble(source).unwrap(),
+            [0x00, 0xE0, 0x01, 0x02, 0x03, 0x12, 0x34]
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_each_error() {
+        assert_eq!(
+            error("a: cls\na: ret"),
+            (2, AsmErrorKind::DuplicateLabel("a".to_string()))
+        );
+        assert_eq!(
+            error("cls\nld v0, 256"),
+            (2, AsmErrorKind::OutOfRange("256".to_string(), 0xFF))
+        );
+        assert_eq!(
+            error("jp #1000"),
+            (1, AsmErrorKind::OutOfRange("#1000".to_string(), 0xFFF))
+        );
+        assert_eq!(
+            error("jp nowhere"),
+            (1, AsmErrorKind::UnknownLabel("nowhere".to_string()))
+        );
+        assert_eq!(
+            error("ld v0, #zz"),
+            (1, AsmErrorKind::BadNumber("#zz".to_string()))
+        );
+        assert_eq!(error("cls v0"), (1, AsmErrorKind::BadOperands));
+        assert_eq!(
+            error("nop"),
+            (1, AsmErrorKind::UnknownMnemonic("NOP".to_string()))
+        );
+        assert_eq!(
+            error("two words: cls"),
+            (1, AsmErrorKind::BadLabel("two words".to_string()))
+        );
+    }
+}
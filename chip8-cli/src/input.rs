@@ -0,0 +1,94 @@
+//! Keypad input and hotkeys from terminal key events, shared by the plain and full-screen
+//! terminal frontends.
+
+use chip8_frontend::{Hotkey, HotkeyAction, HotkeyKey, Hotkeys, Keymap};
+use crossterm::event::{KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+
+/// Most terminals only report presses, so without release events a key counts as held for this
+/// many frames after the last press or auto-repeat.
+const HOLD_FRAMES: u32 = 10;
+
+/// Which hex keys are held, from press and (where the terminal reports them) release events.
+pub struct KeyState {
+    /// Frames left until each key counts as released.
+    held: [u32; 16],
+    releases_reported: bool,
+    keymap: Keymap,
+    hotkeys: Hotkeys,
+}
+
+impl KeyState {
+    /// `releases_reported` is whether the terminal sends release events, see
+    /// `crossterm::terminal::supports_keyboard_enhancement`. `keymap` says which typed characters
+    /// are which keys, and `hotkeys` which keys do something else instead.
+    pub fn new(releases_reported: bool, keymap: Keymap, hotkeys: Hotkeys) -> Self {
+        KeyState {
+            held: [0; 16],
+            releases_reported,
+            keymap,
+            hotkeys,
+        }
+    }
+
+    /// Count down the hold time of keys from terminals that don't report releases. Call once per
+    /// frame.
+    pub fn tick(&mut self) {
+        if !self.releases_reported {
+            for held in &mut self.held {
+                *held = held.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Update the keypad from `key`, unless it's a hotkey, in which case its action is returned
+    /// instead. Ctrl-C always quits.
+    pub fn handle(&mut self, key: KeyEvent) -> Option<HotkeyAction> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if key.code == KeyCode::Char('c') && ctrl {
+            return Some(HotkeyAction::Quit);
+        }
+
+        if let Some(hotkey) = hotkey(key) {
+            if let Some(action) = self.hotkeys.action(hotkey) {
+                // Releases and, where they're reported, repeats don't do it again.
+                return (key.kind == KeyEventKind::Press).then_some(action);
+            }
+        }
+
+        let KeyCode::Char(c) = key.code else {
+            return None;
+        };
+        let hex = self.keymap.hex_key(c)?;
+
+        self.held[hex as usize] = match key.kind {
+            KeyEventKind::Release => 0,
+            _ if self.releases_reported => u32::MAX,
+            _ => HOLD_FRAMES,
+        };
+
+        None
+    }
+
+    pub fn is_down(&self, key: u8) -> bool {
+        self.held[key as usize] > 0
+    }
+}
+
+fn hotkey(key: KeyEvent) -> Option<Hotkey> {
+    let code = match key.code {
+        KeyCode::F(n) => HotkeyKey::F(n),
+        KeyCode::Char(' ') => HotkeyKey::Space,
+        KeyCode::Char(c) => HotkeyKey::Char(c),
+        KeyCode::Esc => HotkeyKey::Escape,
+        KeyCode::Tab => HotkeyKey::Tab,
+        KeyCode::Enter => HotkeyKey::Enter,
+        KeyCode::Backspace => HotkeyKey::Backspace,
+        _ => return None,
+    };
+
+    Some(
+        Hotkey::new(code)
+            .with_ctrl(key.modifiers.contains(KeyModifiers::CONTROL))
+            .with_shift(key.modifiers.contains(KeyModifiers::SHIFT)),
+    )
+}
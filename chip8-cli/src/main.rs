@@ -0,0 +1,409 @@
+use std::error::Error;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, process};
+
+use chip8_core::{
+    decode, rom_hash, validate_rom, Chip8, Chip8Error, InputLog, Quirks, RomSettings, RunEnd,
+    RunExit, RunSetup, DEFAULT_MEMORY_SIZE, PROGRAM_START,
+};
+
+use chip8_frontend::{
+    check_rom, random_seed, record_input, serve_input, Controls, HotkeyAction, HotkeyError,
+    Hotkeys, Keymap, Palette, RomWatcher,
+};
+use clap::{Args, Parser, Subcommand};
+
+use crate::terminal::Look;
+
+mod asm;
+mod input;
+mod terminal;
+#[cfg(feature = "tui")]
+mod tui;
+
+/// Play, debug, disassemble and assemble CHIP-8 programs in the terminal.
+///
+/// `chip8 ROM` is short for `chip8 run ROM`. With no ROM at all, a built-in demo runs.
+#[derive(Parser)]
+#[command(name = "chip8", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// The ROM to play: a file, - for standard input or, with the download feature, an
+    /// http:// or https:// URL.
+    rom: Option<String>,
+    /// Play with the debugging panes, as `chip8 debug` does.
+    #[arg(long)]
+    tui: bool,
+    #[command(flatten)]
+    play: PlayArgs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Play a ROM in the terminal.
+    Run {
+        /// The ROM to play: a file, - for standard input or, with the download feature, an
+        /// http:// or https:// URL.
+        rom: String,
+        #[command(flatten)]
+        play: PlayArgs,
+    },
+    /// Play a ROM full-screen, next to its registers and a log of what it's doing. Needs the
+    /// tui feature.
+    Debug {
+        /// The ROM to play: a file, - for standard input or, with the download feature, an
+        /// http:// or https:// URL. With --library it can be left out, to pick one from there.
+        #[arg(required_unless_present = "library")]
+        rom: Option<String>,
+        /// List the ROMs in DIR, with their sizes and the variant they're for, to start any of
+        /// them from: with F1, or from the start if ROM is left out.
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["record_input", "watch"])]
+        library: Option<PathBuf>,
+        #[command(flatten)]
+        play: PlayArgs,
+    },
+    /// Print a ROM's instructions in the syntax `chip8 asm` reads back.
+    Disasm {
+        /// The ROM to disassemble, from anywhere `chip8 run` can play one.
+        rom: String,
+    },
+    /// Assemble a source file into a ROM.
+    Asm {
+        /// The source to assemble.
+        source: String,
+        /// Where to write the ROM: SOURCE with a .ch8 extension by default, or - for standard
+        /// output.
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Play an input log back on ROM, and check that it ends where the recording did.
+    Replay {
+        /// The ROM the log was recorded on.
+        rom: String,
+        /// The input log, from `--record-input`.
+        log: String,
+    },
+}
+
+/// How to play a ROM.
+#[derive(Args)]
+struct PlayArgs {
+    /// Instructions per second. By default, what the ROM database has for the ROM, or 600.
+    #[arg(long, value_name = "N")]
+    ips: Option<u32>,
+    /// How many character cells across, and half-cells down, each pixel takes.
+    #[arg(long, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..=8))]
+    scale: u16,
+    /// The interpreter to behave like: modern, vip, schip or xo-chip. By default, what the ROM
+    /// database has for the ROM, or modern.
+    #[arg(long, value_name = "PROFILE", value_parser = quirks)]
+    quirks: Option<Quirks>,
+    /// Look ROMs up in FILE before the built-in database of well-known ROMs, for the quirks and
+    /// instructions per second they need. Each line is SHA1 PROFILE IPS TITLE.
+    #[arg(long, value_name = "FILE")]
+    rom_db: Option<PathBuf>,
+    /// The display's colours: mono, green, amber, lcd or high-contrast. By default, the
+    /// terminal's own.
+    #[arg(long, value_name = "NAME", value_parser = palette)]
+    palette: Option<Palette>,
+    /// The random number generator's seed, for `CXKK`, to play the same way every time. By
+    /// default, a different one each run.
+    #[arg(long, value_name = "N")]
+    seed: Option<u32>,
+    /// A preset (qwerty, azerty, qwertz or colemak) or the 16 keys for 123C 456D 789E A0BF,
+    /// e.g. 1234qwerasdfzxcv.
+    #[arg(long, value_name = "LAYOUT")]
+    keymap: Option<Keymap>,
+    /// Rebind a hotkey, e.g. pause=F3 or reset=none. Can be given more than once.
+    #[arg(long = "hotkey", value_name = "ACTION=KEY", value_parser = hotkey)]
+    hotkeys: Vec<String>,
+    /// Log every keypad press and release, with its frame, to FILE, for `chip8 replay`. Turns
+    /// reset and load-state off, since a replay couldn't follow them.
+    #[arg(long, value_name = "FILE")]
+    record_input: Option<String>,
+    /// Take keypad commands from scripts at ADDRESS, HOST:PORT or unix:PATH, one per line:
+    /// press KEY, release KEY or tap KEY, each optionally with a frame.
+    #[arg(long, value_name = "ADDRESS")]
+    remote: Option<String>,
+    /// Start the ROM again from the top whenever its file is rewritten, e.g. by an assembler.
+    /// Needs a ROM file, not standard input or a URL.
+    #[arg(long, conflicts_with = "record_input")]
+    watch: bool,
+}
+
+fn quirks(name: &str) -> Result<Quirks, String> {
+    Quirks::named(name).ok_or_else(|| format!("the profiles are {}", Quirks::NAMES.join(", ")))
+}
+
+fn palette(name: &str) -> Result<Palette, String> {
+    Palette::named(name).ok_or_else(|| format!("the palettes are {}", Palette::NAMES.join(", ")))
+}
+
+/// Check a `--hotkey` binding, which `play` makes once it has the `Hotkeys` to make it in.
+fn hotkey(binding: &str) -> Result<String, HotkeyError> {
+    Hotkeys::default().bind(binding)?;
+
+    Ok(binding.to_string())
+}
+
+/// Computes `5 + (10 * 2) + (10 * 2) - 7`, calling a subroutine that adds `V1` to `V0` twice.
+#[rustfmt::skip]
+const DEMO: [u8; 20] = [
+    0x60, 0x05, // 0x200: LD V0, 5
+    0x61, 0x0A, // 0x202: LD V1, 10
+    0x62, 0x07, // 0x204: LD V2, 7
+    0x22, 0x0E, // 0x206: CALL 0x20E
+    0x22, 0x0E, // 0x208: CALL 0x20E
+    0x80, 0x25, // 0x20A: SUB V0, V2
+    0x00, 0x00, // 0x20C: HALT
+    0x80, 0x14, // 0x20E: ADD V0, V1
+    0x80, 0x14, // 0x210: ADD V0, V1
+    0x00, 0xEE, // 0x212: RET
+];
+
+fn run_demo() -> Result<(), Chip8Error> {
+    let mut cpu = Chip8::builder().rom(&DEMO).build()?;
+    while cpu.run()? != RunExit::Halted {}
+
+    assert_eq!(cpu.registers()[0], 38);
+
+    println!("5 + (10 * 2) + (10 * 2) - 7 = {}", cpu.registers()[0]);
+
+    Ok(())
+}
+
+/// The most a ROM download reads: far more than fits in memory, so that `validate_rom` says a
+/// ROM's too large rather than the download cutting it off.
+#[cfg(feature = "download")]
+const DOWNLOAD_LIMIT: u64 = 1 << 20;
+
+/// Read the ROM at `path`, checking it will fit and warning about anything off about it.
+/// `path` can also be `-` for standard input or, with the `download` feature, an `http://` or
+/// `https://` URL.
+fn read_rom(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let rom = fetch(path).map_err(|err| format!("{}: {}", path, err))?;
+
+    check_rom(path, rom)
+}
+
+/// Start the ROM at `path` on `cpu` in place of the one running, e.g. once it's been rewritten
+/// or picked from the library. Unlike `read_rom`, this keeps quiet about warnings, which would
+/// land in the middle of the picture.
+fn load(path: &Path, cpu: &mut Chip8, controls: &mut Controls) -> Result<(), Box<dyn Error>> {
+    let rom = fs::read(path)?;
+    let _warnings = validate_rom(&rom, DEFAULT_MEMORY_SIZE - PROGRAM_START)?;
+    controls.load_rom(cpu, &rom)?;
+
+    Ok(())
+}
+
+/// The quirks and clock speed to play ROMs with: the ones `args` gives, and otherwise what the
+/// `--rom-db` file or the built-in `KnownRoms` has for each.
+fn rom_settings(args: &PlayArgs) -> Result<RomSettings, Box<dyn Error>> {
+    let settings = RomSettings::new(args.quirks, args.ips);
+    let Some(path) = &args.rom_db else {
+        return Ok(settings);
+    };
+
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(settings
+        .with_known_roms(text)
+        .map_err(|err| format!("{}: {}", path.display(), err))?)
+}
+
+fn fetch(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if path == "-" {
+        let mut rom = Vec::new();
+        io::stdin().read_to_end(&mut rom)?;
+        return Ok(rom);
+    }
+
+    if path.starts_with("http://") || path.starts_with("https://") {
+        #[cfg(feature = "download")]
+        return Ok(ureq::get(path)
+            .call()?
+            .body_mut()
+            .with_config()
+            .limit(DOWNLOAD_LIMIT)
+            .read_to_vec()?);
+        #[cfg(not(feature = "download"))]
+        return Err("chip8 was built without the download feature".into());
+    }
+
+    Ok(fs::read(path)?)
+}
+
+/// Play the ROM at `path` in the terminal as `args` say, full-screen with debugging panes if `tui`
+/// is set, and a ROM library to pick others from if `library` is also set, in which case `path` can
+/// be left out. With `--record-input`, every press and release is written to that file as it
+/// happens, and how the run ended once it's over, for `chip8 replay`. With `--remote`, scripts can
+/// press keys too, by connecting to that address. With `--watch`, the ROM starts again whenever its
+/// file is rewritten.
+fn play(
+    path: Option<&str>,
+    tui: bool,
+    library: Option<PathBuf>,
+    args: PlayArgs,
+) -> Result<(), Box<dyn Error>> {
+    let watcher = match path {
+        Some(path) if args.watch && path != "-" && !path.contains("://") => {
+            Some(RomWatcher::new(path))
+        }
+        _ if args.watch => return Err("--watch needs a ROM file".into()),
+        _ => None,
+    };
+
+    // Without a ROM, the machine waits with an empty memory until one is picked.
+    let rom = path.map(read_rom).transpose()?.unwrap_or_default();
+    let rom_settings = rom_settings(&args)?;
+    let (quirks, clock_hz) = rom_settings.for_rom(&rom);
+    let setup = RunSetup {
+        seed: args.seed.unwrap_or_else(random_seed),
+        clock_hz,
+        quirks,
+        ..RunSetup::for_rom(&rom)
+    };
+    let mut cpu = setup.builder(&rom).build()?;
+    if let Some(address) = &args.remote {
+        serve_input(address, cpu.input_sender())?;
+    }
+
+    let keymap = args.keymap.unwrap_or_default();
+    let mut hotkeys = Hotkeys::default();
+    for binding in &args.hotkeys {
+        hotkeys.bind(binding)?;
+    }
+    let look = Look {
+        scale: args.scale as usize,
+        palette: args.palette,
+    };
+
+    let log = match &args.record_input {
+        Some(input_log) => {
+            let log = record_input(&mut cpu, Path::new(input_log), &setup)?;
+            // A replay only has the input to go on, so it couldn't follow a jump back.
+            hotkeys.set(HotkeyAction::Reset, None);
+            hotkeys.set(HotkeyAction::LoadState, None);
+            Some(log)
+        }
+        None => None,
+    };
+
+    #[cfg(feature = "tui")]
+    let cpu = if tui {
+        let library = library.map(|dir| (dir, path.is_none()));
+        tui::run(cpu, keymap, hotkeys, look, watcher, library, rom_settings)?
+    } else {
+        terminal::run(cpu, keymap, hotkeys, look, watcher, rom_settings)?
+    };
+    #[cfg(not(feature = "tui"))]
+    let cpu = if tui || library.is_some() {
+        return Err("chip8 was built without the tui feature".into());
+    } else {
+        terminal::run(cpu, keymap, hotkeys, look, watcher, rom_settings)?
+    };
+
+    if let Some(log) = log {
+        log.finish(RunEnd::of(&cpu))?;
+    }
+
+    Ok(())
+}
+
+/// Play the input log at `log_path` back on `rom_path`, and check that it ends in the same state
+/// the recording did.
+fn replay(rom_path: &str, log_path: &str) -> Result<(), Box<dyn Error>> {
+    let rom = read_rom(rom_path)?;
+    let log = InputLog::decode(&fs::read(log_path)?)?;
+    if log.setup().rom_hash != rom_hash(&rom) {
+        return Err(format!("{} was recorded with a different ROM", log_path).into());
+    }
+
+    let cpu = log.replay(&rom)?;
+    let state_hash = cpu.state_hash();
+    println!(
+        "replayed {} frames to state {:016x}",
+        cpu.frame(),
+        state_hash
+    );
+
+    match log.end() {
+        Some(end) if end.state_hash == state_hash => println!("matches the recording"),
+        Some(end) => {
+            return Err(format!(
+                "replay diverged: the recording ended in state {:016x}",
+                end.state_hash
+            )
+            .into())
+        }
+        None => {
+            println!("the log doesn't say how the recording ended, so there's nothing to check")
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the ROM at `path` one instruction per line, each with its address and opcode in a
+/// comment, so that `chip8 asm` turns the listing back into the same ROM.
+fn disasm(path: &str) -> Result<(), Box<dyn Error>> {
+    let rom = fetch(path)?;
+    for (i, chunk) in rom.chunks(2).enumerate() {
+        let addr = PROGRAM_START + i * 2;
+        match *chunk {
+            [high, low] => {
+                let opcode = u16::from_be_bytes([high, low]);
+                let text = decode(opcode).to_string();
+                println!("{:<20}; {:03X}: {:04X}", text, addr, opcode);
+            }
+            [byte] => println!(
+                "{:<20}; {:03X}: {:02X}",
+                format!("DB #{:02X}", byte),
+                addr,
+                byte
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(())
+}
+
+/// Assemble the source at `path` into a ROM, written to `output`.
+fn asm(path: &str, output: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let rom = asm::assemble(&fs::read_to_string(path)?)?;
+    match output {
+        Some("-") => io::stdout().write_all(&rom)?,
+        Some(output) => fs::write(output, rom)?,
+        None => fs::write(Path::new(path).with_extension("ch8"), rom)?,
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let result = match (cli.command, cli.rom) {
+        (Some(Command::Run { rom, play: args }), _) => play(Some(&rom), false, None, args),
+        (
+            Some(Command::Debug {
+                rom,
+                library,
+                play: args,
+            }),
+            _,
+        ) => play(rom.as_deref(), true, library, args),
+        (Some(Command::Disasm { rom }), _) => disasm(&rom),
+        (Some(Command::Asm { source, output }), _) => asm(&source, output.as_deref()),
+        (Some(Command::Replay { rom, log }), _) => replay(&rom, &log),
+        (None, Some(rom)) => play(Some(&rom), cli.tui, None, cli.play),
+        (None, None) => run_demo().map_err(Into::into),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,199 @@
+//! Plays a ROM in the terminal, drawing two pixel rows per character cell with `▀`/`▄`.
+
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use chip8_core::{AudioSink, Chip8, DirtyRows, DisplaySink, Framebuffer, KeyInput, RomSettings};
+
+use chip8_frontend::{Controls, FramePacer, HotkeyAction, Hotkeys, Keymap, Palette, RomWatcher};
+use crossterm::event::{
+    self, Event, KeyboardEnhancementFlags, PopKeyboardEnhancementFlags,
+    PushKeyboardEnhancementFlags,
+};
+use crossterm::{cursor, execute, queue, style, terminal};
+
+use crate::input::KeyState;
+
+/// How the display is drawn: each pixel `scale` character cells across and as many half-cells
+/// down, in `palette`'s foreground and background, or without one in the terminal's own
+/// colours. Half-blocks only have two colours, so XO-CHIP's second plane shows as foreground.
+#[derive(Debug, Clone, Copy)]
+pub struct Look {
+    pub scale: usize,
+    pub palette: Option<Palette>,
+}
+
+struct Terminal {
+    stdout: Stdout,
+    keys: KeyState,
+    releases_reported: bool,
+    quit: bool,
+    /// The width of the display last drawn, which changes with SUPER-CHIP's hires mode.
+    width: usize,
+    look: Look,
+}
+
+impl Terminal {
+    fn enter(keymap: Keymap, hotkeys: Hotkeys, look: Look) -> io::Result<Self> {
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        let releases_reported = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases_reported {
+            execute!(
+                stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+
+        Ok(Terminal {
+            stdout,
+            keys: KeyState::new(releases_reported, keymap, hotkeys),
+            releases_reported,
+            quit: false,
+            width: 0,
+            look,
+        })
+    }
+
+    /// Drain pending terminal events without blocking, carrying out any hotkeys on `cpu`.
+    fn poll(&mut self, cpu: &mut Chip8, controls: &mut Controls) -> io::Result<()> {
+        self.keys.tick();
+
+        while event::poll(Duration::ZERO)? {
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+
+            // There's nowhere to say what a hotkey did without spoiling the picture, but a reset
+            // or a loaded state needs redrawing, even while paused.
+            match self.keys.handle(key) {
+                Some(HotkeyAction::Quit) => self.quit = true,
+                Some(action) if controls.apply(action, cpu).is_some() => {
+                    self.draw(cpu.display(), DirtyRows::ALL)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Redraw the lines showing any of the `dirty` rows. Terminals are slow enough that
+    /// rewriting the whole screen every frame can make them lag.
+    fn draw(&mut self, framebuffer: &Framebuffer, mut dirty: DirtyRows) -> io::Result<()> {
+        if let Some(palette) = self.look.palette {
+            let rgb = |[r, g, b]: [u8; 3]| style::Color::Rgb { r, g, b };
+            let colors = style::Colors::new(rgb(palette.foreground), rgb(palette.background));
+            queue!(self.stdout, style::SetColors(colors))?;
+        }
+
+        // Switching resolution would leave the edges of a bigger picture behind.
+        if framebuffer.width() != self.width {
+            queue!(self.stdout, terminal::Clear(terminal::ClearType::All))?;
+            self.width = framebuffer.width();
+            dirty = DirtyRows::ALL;
+        }
+
+        let scale = self.look.scale;
+        for (row, line) in half_block_lines(framebuffer, scale).enumerate() {
+            if !dirty.contains(row * 2 / scale) && !dirty.contains((row * 2 + 1) / scale) {
+                continue;
+            }
+
+            queue!(
+                self.stdout,
+                cursor::MoveTo(0, row as u16),
+                style::Print(line)
+            )?;
+        }
+
+        queue!(self.stdout, style::ResetColor)?;
+        self.stdout.flush()
+    }
+}
+
+/// The display as lines of text, each character cell showing two pixels stacked vertically: 16
+/// lines of 64 characters, or 32 of 128 in hires, times `scale` each way.
+pub fn half_block_lines(
+    framebuffer: &Framebuffer,
+    scale: usize,
+) -> impl Iterator<Item = String> + '_ {
+    let pixel = move |x: usize, y: usize| framebuffer.pixel(x / scale, y / scale);
+    (0..framebuffer.height() * scale).step_by(2).map(move |y| {
+        (0..framebuffer.width() * scale)
+            .map(|x| match (pixel(x, y), pixel(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            })
+            .collect()
+    })
+}
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.releases_reported {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+impl DisplaySink for Terminal {
+    fn present_rows(&mut self, framebuffer: &Framebuffer, dirty: DirtyRows) {
+        // A failed write just means a missed frame. Its rows stay stale until drawn over again.
+        let _ = self.draw(framebuffer, dirty);
+    }
+}
+
+impl KeyInput for Terminal {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys.is_down(key)
+    }
+}
+
+impl AudioSink for Terminal {
+    fn start_beep(&mut self) {
+        let _ = write!(self.stdout, "\x07");
+    }
+}
+
+/// Run `cpu` at 60 frames per second until the quit hotkey or Ctrl-C is pressed, then hand it
+/// back. With a `watcher`, the ROM starts again whenever it's rewritten, with the quirks and
+/// clock speed `rom_settings` pick for it.
+pub fn run(
+    mut cpu: Chip8,
+    keymap: Keymap,
+    hotkeys: Hotkeys,
+    look: Look,
+    mut watcher: Option<RomWatcher>,
+    rom_settings: RomSettings,
+) -> Result<Chip8, Box<dyn std::error::Error>> {
+    let mut term = Terminal::enter(keymap, hotkeys, look)?;
+    term.draw(cpu.display(), DirtyRows::ALL)?;
+
+    let mut controls = Controls::with_rom_settings(rom_settings);
+    let mut pacer = FramePacer::new();
+
+    while !term.quit {
+        term.poll(&mut cpu, &mut controls)?;
+        if let Some(watcher) = &mut watcher {
+            // There's nowhere to say what's wrong with a rewritten ROM that won't load either,
+            // so the old one keeps going until it's fixed.
+            if watcher.changed() && crate::load(watcher.path(), &mut cpu, &mut controls).is_ok() {
+                term.draw(cpu.display(), DirtyRows::ALL)?;
+            }
+        }
+        for _ in 0..controls.frames() {
+            cpu.run_frame_with(&mut term)?;
+        }
+
+        pacer.wait();
+    }
+
+    Ok(cpu)
+}
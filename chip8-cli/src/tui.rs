@@ -0,0 +1,429 @@
+//! A full-screen terminal UI: the game display, the machine's registers, and a log of what the
+//! emulator has been doing.
+
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chip8_core::{
+    AudioSink, Chip8, DisplaySink, EmulatorEvent, Framebuffer, KeyInput, RomSettings,
+};
+
+use chip8_frontend::{
+    scan_library, Controls, FramePacer, HotkeyAction, Hotkeys, Keymap, RomEntry, RomWatcher,
+};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::{execute, terminal};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Clear, Paragraph, Row, Table, TableState};
+use ratatui::Frame;
+
+use crate::input::KeyState;
+use crate::terminal::{half_block_lines, Look};
+
+/// How many lines the log pane keeps.
+const LOG_LINES: usize = 200;
+
+struct Io<'a> {
+    keys: &'a KeyState,
+}
+
+impl DisplaySink for Io<'_> {}
+
+impl KeyInput for Io<'_> {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys.is_down(key)
+    }
+}
+
+impl AudioSink for Io<'_> {}
+
+/// The ROMs in the `--library` directory, listed over everything else to pick one from.
+struct Library {
+    dir: PathBuf,
+    entries: Vec<RomEntry>,
+    /// Why the directory couldn't be listed, if it couldn't.
+    error: Option<String>,
+    selected: usize,
+    open: bool,
+}
+
+impl Library {
+    fn new(dir: PathBuf) -> Self {
+        Library {
+            dir,
+            entries: Vec::new(),
+            error: None,
+            selected: 0,
+            open: false,
+        }
+    }
+
+    /// List the directory again, so that ROMs added since show up, and show the list.
+    fn open(&mut self) {
+        match scan_library(&self.dir) {
+            Ok(entries) => {
+                self.entries = entries;
+                self.error = None;
+            }
+            Err(err) => {
+                self.entries.clear();
+                self.error = Some(err.to_string());
+            }
+        }
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        self.open = true;
+    }
+
+    /// Move the selection for an arrow key or the like, or return the ROM Enter picks. Returns
+    /// `None` for keys the list doesn't use, which are left for the keypad and hotkeys.
+    fn handle(&mut self, key: KeyEvent) -> Option<Option<&RomEntry>> {
+        let last = self.entries.len().saturating_sub(1);
+        let page = 10;
+        let selected = match key.code {
+            KeyCode::Up => self.selected.saturating_sub(1),
+            KeyCode::Down => (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected.saturating_sub(page),
+            KeyCode::PageDown => (self.selected + page).min(last),
+            KeyCode::Home => 0,
+            KeyCode::End => last,
+            KeyCode::Enter if key.kind == KeyEventKind::Press => {
+                return Some(self.entries.get(self.selected));
+            }
+            KeyCode::Enter => self.selected,
+            _ => return None,
+        };
+        if key.kind != KeyEventKind::Release {
+            self.selected = selected;
+        }
+
+        Some(None)
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let area = frame
+            .area()
+            .centered(Constraint::Percentage(80), Constraint::Percentage(80));
+        let title = format!("Library: {}", self.dir.display());
+        let block = Block::bordered()
+            .title(title)
+            .title_bottom("Up/Down to choose, Enter to play");
+        frame.render_widget(Clear, area);
+
+        if let Some(error) = &self.error {
+            frame.render_widget(Paragraph::new(error.as_str()).block(block), area);
+            return;
+        }
+        if self.entries.is_empty() {
+            frame.render_widget(Paragraph::new("No ROMs here").block(block), area);
+            return;
+        }
+
+        let rows = self.entries.iter().map(|entry| {
+            Row::new([
+                entry.title.clone(),
+                format!("{:>5} bytes", entry.size),
+                entry.variant.name().to_string(),
+            ])
+        });
+        let widths = [
+            Constraint::Min(20),
+            Constraint::Length(11),
+            Constraint::Length(10),
+        ];
+        let table = Table::new(rows, widths)
+            .header(Row::new(["Title", "Size", "Variant"]).bold())
+            .row_highlight_style(Style::new().reversed())
+            .block(block);
+        let mut state = TableState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(table, area, &mut state);
+    }
+}
+
+struct Tui {
+    cpu: Chip8,
+    keys: KeyState,
+    controls: Controls,
+    log: VecDeque<String>,
+    frame: u64,
+    /// Set once execution fails. The UI stays up so the state can be inspected.
+    stopped: bool,
+    look: Look,
+    watcher: Option<RomWatcher>,
+    library: Option<Library>,
+}
+
+impl Tui {
+    fn log(&mut self, message: String) {
+        if self.log.len() == LOG_LINES {
+            self.log.pop_front();
+        }
+        self.log
+            .push_back(format!("{:>6}  {}", self.frame, message));
+    }
+
+    /// Whether the library is showing, which pauses the game.
+    fn picking(&self) -> bool {
+        self.library.as_ref().is_some_and(|library| library.open)
+    }
+
+    /// Pass `key` to the library if it's showing and has a use for it. Returns whether it did.
+    fn library_key(&mut self, key: KeyEvent) -> bool {
+        let Some(library) = self.library.as_mut().filter(|library| library.open) else {
+            return false;
+        };
+        let Some(picked) = library.handle(key) else {
+            return false;
+        };
+
+        if let Some(entry) = picked {
+            let (path, title) = (entry.path.clone(), entry.title.clone());
+            match crate::load(&path, &mut self.cpu, &mut self.controls) {
+                Ok(()) => {
+                    library.open = false;
+                    self.stopped = false;
+                    self.log(format!("started {}", title));
+                }
+                Err(err) => self.log(format!("couldn't start {}: {}", title, err)),
+            }
+        }
+
+        true
+    }
+
+    /// Carry out a hotkey's `action`, logging what it did.
+    fn hotkey(&mut self, action: HotkeyAction) {
+        if action == HotkeyAction::Library {
+            if let Some(library) = &mut self.library {
+                if library.open {
+                    library.open = false;
+                } else {
+                    library.open();
+                }
+            }
+            return;
+        }
+
+        let Some(message) = self.controls.apply(action, &mut self.cpu) else {
+            return;
+        };
+
+        // Going back to the start or a saved state gets past whatever stopped execution.
+        if matches!(action, HotkeyAction::Reset | HotkeyAction::LoadState) {
+            self.stopped = false;
+        }
+        self.log(message);
+    }
+
+    /// Start the ROM again if it's watched and has been rewritten, logging how that went.
+    fn reload(&mut self) {
+        let Some(watcher) = &mut self.watcher else {
+            return;
+        };
+        if !watcher.changed() {
+            return;
+        }
+
+        match crate::load(watcher.path(), &mut self.cpu, &mut self.controls) {
+            Ok(()) => {
+                self.stopped = false;
+                self.log("reloaded the ROM".to_string());
+            }
+            Err(err) => self.log(format!("couldn't reload the ROM: {}", err)),
+        }
+    }
+
+    fn run_frame(&mut self) {
+        let mut io = Io { keys: &self.keys };
+
+        if let Err(err) = self.cpu.run_frame_with(&mut io) {
+            self.log(format!("error: {}", err));
+            self.stopped = true;
+        }
+
+        while let Some(event) = self.cpu.poll_event() {
+            match event {
+                // Most frames draw; logging them would drown out everything else.
+                EmulatorEvent::DisplayUpdated => {}
+                EmulatorEvent::SoundStarted => self.log("beep".to_string()),
+                EmulatorEvent::SoundStopped => self.log("beep stopped".to_string()),
+                // Games run the delay timer down all the time.
+                EmulatorEvent::DelayExpired => {}
+                EmulatorEvent::WaitingForKey { x } => {
+                    self.log(format!("waiting for a key for V{:X}", x))
+                }
+                EmulatorEvent::Halted => self.log("halted".to_string()),
+            }
+        }
+
+        self.frame += 1;
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        // The screen's pane grows in SUPER-CHIP's hires mode. Two more cells each way for the
+        // border.
+        let display = self.cpu.display();
+        let scale = self.look.scale;
+        let (width, height) = (
+            (display.width() * scale) as u16 + 2,
+            (display.height() * scale / 2) as u16 + 2,
+        );
+        let [left, log] = Layout::horizontal([Constraint::Length(width), Constraint::Min(20)])
+            .areas(frame.area());
+        let [screen, registers] =
+            Layout::vertical([Constraint::Length(height), Constraint::Min(8)]).areas(left);
+
+        frame.render_widget(self.screen(self.cpu.display()), screen);
+        frame.render_widget(self.registers(), registers);
+
+        let visible = log.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self
+            .log
+            .iter()
+            .skip(self.log.len().saturating_sub(visible))
+            .map(|line| Line::raw(line.as_str()))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines).block(Block::bordered().title("Log")),
+            log,
+        );
+
+        if let Some(library) = self.library.as_ref().filter(|library| library.open) {
+            library.draw(frame);
+        }
+    }
+
+    fn screen(&self, framebuffer: &Framebuffer) -> Paragraph<'static> {
+        let style = match self.look.palette {
+            Some(palette) => {
+                let rgb = |[r, g, b]: [u8; 3]| Color::Rgb(r, g, b);
+                Style::new()
+                    .fg(rgb(palette.foreground))
+                    .bg(rgb(palette.background))
+            }
+            None => Style::new(),
+        };
+        let lines: Vec<Line> = half_block_lines(framebuffer, self.look.scale)
+            .map(|line| Line::styled(line, style))
+            .collect();
+
+        Paragraph::new(lines).block(Block::bordered().title("CHIP-8"))
+    }
+
+    fn registers(&self) -> Paragraph<'static> {
+        let cpu = &self.cpu;
+        let v = cpu.registers();
+        let mut lines: Vec<Line> = (0..4)
+            .map(|row| {
+                let cells: Vec<String> = (row * 4..row * 4 + 4)
+                    .map(|i| format!("V{:X} {:02X}", i, v[i]))
+                    .collect();
+                Line::raw(cells.join("   "))
+            })
+            .collect();
+
+        lines.push(Line::raw(format!(
+            "I {:03X}   PC {:03X}   DT {:02X}   ST {:02X}",
+            cpu.i(),
+            cpu.pc(),
+            cpu.timers().delay,
+            cpu.timers().sound
+        )));
+
+        let stack: Vec<String> = cpu.stack_frames().map(|f| format!("{:03X}", f)).collect();
+        lines.push(Line::raw(format!("Stack: {}", stack.join(" "))));
+
+        Paragraph::new(lines).block(Block::bordered().title("Registers"))
+    }
+}
+
+/// Run `cpu` full-screen until the quit hotkey or Ctrl-C is pressed, then hand it back. With a
+/// `watcher`, the ROM starts again whenever it's rewritten. With a `library` directory, the
+/// library hotkey lists the ROMs in it to pick another from, and without a ROM to start with
+/// the list is up from the start. ROMs started either way get the quirks and clock speed
+/// `rom_settings` pick for them.
+pub fn run(
+    cpu: Chip8,
+    keymap: Keymap,
+    hotkeys: Hotkeys,
+    look: Look,
+    watcher: Option<RomWatcher>,
+    library: Option<(PathBuf, bool)>,
+    rom_settings: RomSettings,
+) -> Result<Chip8, Box<dyn Error>> {
+    let mut terminal = ratatui::try_init()?;
+
+    let releases_reported = terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if releases_reported {
+        execute!(
+            io::stdout(),
+            PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+        )?;
+    }
+
+    let mut tui = Tui {
+        cpu,
+        keys: KeyState::new(releases_reported, keymap, hotkeys),
+        controls: Controls::with_rom_settings(rom_settings),
+        log: VecDeque::new(),
+        frame: 0,
+        stopped: false,
+        look,
+        watcher,
+        library: None,
+    };
+    if let Some((dir, open)) = library {
+        let mut library = Library::new(dir);
+        if open {
+            library.open();
+            // There's nothing to run until a game is picked.
+            tui.controls.set_paused(true);
+        }
+        tui.library = Some(library);
+    }
+    tui.log("started".to_string());
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        let mut pacer = FramePacer::new();
+
+        loop {
+            tui.keys.tick();
+            while event::poll(Duration::ZERO)? {
+                if let Event::Key(key) = event::read()? {
+                    if tui.library_key(key) {
+                        continue;
+                    }
+                    match tui.keys.handle(key) {
+                        Some(HotkeyAction::Quit) => return Ok(()),
+                        Some(action) => tui.hotkey(action),
+                        None => {}
+                    }
+                }
+            }
+
+            tui.reload();
+            for _ in 0..tui.controls.frames() {
+                if !tui.stopped && !tui.picking() {
+                    tui.run_frame();
+                }
+            }
+            terminal.draw(|frame| tui.draw(frame))?;
+
+            pacer.wait();
+        }
+    })();
+
+    if releases_reported {
+        let _ = execute!(io::stdout(), PopKeyboardEnhancementFlags);
+    }
+    ratatui::restore();
+
+    result.map(|()| tui.cpu)
+}
@@ -0,0 +1,113 @@
+/// The pitch programs start with, before any `FX3A`, which plays a pattern at 4000 bits a second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// How much one step of pitch raises the bit rate: 48 steps to the octave.
+const PITCH_STEP: f32 = 1.014_545_3;
+
+/// XO-CHIP's buzzer sound: 128 1-bit samples, loaded by `F002` from the 16 bytes at `I`, played
+/// most significant bit first and over and over for as long as the sound timer runs, at a rate
+/// set by `FX3A`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioPattern {
+    pub bits: [u8; 16],
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    /// What the buzzer plays before a program loads a pattern of its own: a 500Hz square wave.
+    pub const BUZZER: AudioPattern = AudioPattern {
+        bits: [0xF0; 16],
+        pitch: DEFAULT_PITCH,
+    };
+
+    /// How many of the pattern's bits play a second: 4000 at `DEFAULT_PITCH`, and an octave
+    /// higher or lower for every 48 the pitch is above or below it.
+    pub fn bit_rate(&self) -> f32 {
+        let steps = self.pitch as i32 - DEFAULT_PITCH as i32;
+        let mut rate = 4000.0;
+        for _ in 0..steps.unsigned_abs() {
+            if steps > 0 {
+                rate *= PITCH_STEP;
+            } else {
+                rate /= PITCH_STEP;
+            }
+        }
+
+        rate
+    }
+
+    /// Bit `i` of the 128, counting from the most significant bit of the first byte.
+    pub fn bit(&self, i: usize) -> bool {
+        self.bits[i / 8 % 16] & 0x80 >> (i % 8) != 0
+    }
+}
+
+/// Plays an `AudioPattern` as a stream of samples at a host's sample rate, from 1.0 for a set
+/// bit to -1.0 for a clear one, for frontends to feed their audio device.
+///
+/// Each sample is the average of the bits it covers, weighted by how much of each, rather than
+/// whichever bit it lands on, so a pattern resampled to a rate that isn't a multiple of its own
+/// keeps its shape instead of jittering and aliasing.
+#[derive(Debug, Clone)]
+pub struct PatternPlayer {
+    pattern: AudioPattern,
+    sample_rate: u32,
+    /// How far through the pattern, in bits, from 0 up to 128.
+    position: f32,
+    /// How many bits each sample moves `position` on.
+    step: f32,
+}
+
+impl PatternPlayer {
+    pub fn new(pattern: AudioPattern, sample_rate: u32) -> Self {
+        PatternPlayer {
+            pattern,
+            sample_rate,
+            position: 0.0,
+            step: pattern.bit_rate() / sample_rate as f32,
+        }
+    }
+
+    pub fn pattern(&self) -> &AudioPattern {
+        &self.pattern
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Switch to `pattern`, carrying on from the same point in it so the sound doesn't click.
+    pub fn set_pattern(&mut self, pattern: AudioPattern) {
+        self.pattern = pattern;
+        self.step = pattern.bit_rate() / self.sample_rate as f32;
+    }
+
+    pub fn next_sample(&mut self) -> f32 {
+        let end = self.position + self.step;
+        let mut total = 0.0;
+        let mut at = self.position;
+        while at < end {
+            let bit_end = ((at as u32 + 1) as f32).min(end);
+            let value = if self.pattern.bit(at as usize % 128) {
+                1.0
+            } else {
+                -1.0
+            };
+            total += value * (bit_end - at);
+            at = bit_end;
+        }
+        self.position = end % 128.0;
+
+        // Rounding can take the average a hair past either end.
+        (total / self.step).clamp(-1.0, 1.0)
+    }
+}
+
+impl Iterator for PatternPlayer {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        Some(self.next_sample())
+    }
+}
@@ -0,0 +1,150 @@
+use crate::cpu::{Cpu, Quirks, DEFAULT_CLOCK_HZ};
+use crate::error::Chip8Error;
+use crate::rng::{Rng, XorShiftRng};
+use crate::stack::StackPolicy;
+
+/// Where ROMs are loaded and execution starts.
+pub const PROGRAM_START: usize = 0x200;
+
+/// A CHIP-8 machine with the default random number generator.
+pub type Chip8 = Cpu<XorShiftRng>;
+
+impl Cpu {
+    /// Start configuring a new machine.
+    pub fn builder<'a>() -> Chip8Builder<'a> {
+        Chip8Builder::new()
+    }
+}
+
+/// Configures and constructs a `Cpu`, e.g.
+/// `Chip8::builder().rom(bytes).quirks(quirks).clock_hz(700).seed(42).build()`.
+pub struct Chip8Builder<'a, R: Rng = XorShiftRng> {
+    rom: &'a [u8],
+    load_address: usize,
+    quirks: Quirks,
+    clock_hz: u32,
+    ignore_unknown_opcodes: bool,
+    trap_overflow: bool,
+    write_protect: bool,
+    stack_policy: StackPolicy,
+    rng: R,
+}
+
+impl Chip8Builder<'_> {
+    pub fn new() -> Self {
+        Chip8Builder {
+            rom: &[],
+            load_address: PROGRAM_START,
+            quirks: Quirks::default(),
+            clock_hz: DEFAULT_CLOCK_HZ,
+            ignore_unknown_opcodes: false,
+            trap_overflow: false,
+            write_protect: false,
+            stack_policy: StackPolicy::Error,
+            rng: XorShiftRng::new(0),
+        }
+    }
+
+    /// Seed the default random number generator, so `CXKK` produces the same numbers every run.
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.rng = XorShiftRng::new(seed);
+        self
+    }
+}
+
+impl Default for Chip8Builder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, R: Rng> Chip8Builder<'a, R> {
+    /// The program to load, at `PROGRAM_START` unless `load_address` says otherwise.
+    pub fn rom(mut self, rom: &'a [u8]) -> Self {
+        self.rom = rom;
+        self
+    }
+
+    /// Load the ROM and start execution at `addr` instead of `PROGRAM_START`.
+    pub fn load_address(mut self, addr: usize) -> Self {
+        self.load_address = addr;
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// How many instructions to execute per second. `run_frame` runs a 60th of this. See
+    /// `Cpu::set_clock_hz`.
+    pub fn clock_hz(mut self, hz: u32) -> Self {
+        self.clock_hz = hz;
+        self
+    }
+
+    /// See `Cpu::set_ignore_unknown_opcodes`.
+    pub fn ignore_unknown_opcodes(mut self, ignore: bool) -> Self {
+        self.ignore_unknown_opcodes = ignore;
+        self
+    }
+
+    /// See `Cpu::set_trap_overflow`.
+    pub fn trap_overflow(mut self, trap: bool) -> Self {
+        self.trap_overflow = trap;
+        self
+    }
+
+    /// See `Memory::set_write_protected`.
+    pub fn write_protect(mut self, protect: bool) -> Self {
+        self.write_protect = protect;
+        self
+    }
+
+    /// See `Cpu::set_stack_policy`.
+    pub fn stack_policy(mut self, policy: StackPolicy) -> Self {
+        self.stack_policy = policy;
+        self
+    }
+
+    /// Use a custom random number generator instead of the seeded default.
+    pub fn rng<R2: Rng>(self, rng: R2) -> Chip8Builder<'a, R2> {
+        Chip8Builder {
+            rom: self.rom,
+            load_address: self.load_address,
+            quirks: self.quirks,
+            clock_hz: self.clock_hz,
+            ignore_unknown_opcodes: self.ignore_unknown_opcodes,
+            trap_overflow: self.trap_overflow,
+            write_protect: self.write_protect,
+            stack_policy: self.stack_policy,
+            rng,
+        }
+    }
+
+    /// Construct the machine, with the ROM loaded and the program counter at its load address.
+    ///
+    /// Fails with `Chip8Error::RomTooLarge` if the ROM doesn't fit in memory, or
+    /// `Chip8Error::LoadAddressOutOfBounds` if the load address is past the end of it.
+    pub fn build(self) -> Result<Cpu<R>, Chip8Error> {
+        self.build_sized()
+    }
+
+    /// Like `build`, but with `MEM` bytes of memory and a `STACK`-deep call stack, e.g. 64K for
+    /// XO-CHIP ROMs.
+    pub fn build_sized<const MEM: usize, const STACK: usize>(
+        self,
+    ) -> Result<Cpu<R, MEM, STACK>, Chip8Error> {
+        let mut cpu = Cpu::new(self.rng);
+        cpu.load_rom_at(self.load_address, self.rom)?;
+
+        *cpu.quirks_mut() = self.quirks;
+        cpu.set_clock_hz(self.clock_hz);
+        cpu.set_ignore_unknown_opcodes(self.ignore_unknown_opcodes);
+        cpu.set_trap_overflow(self.trap_overflow);
+        cpu.memory_mut().set_write_protected(self.write_protect);
+        cpu.set_stack_policy(self.stack_policy);
+
+        Ok(cpu)
+    }
+}
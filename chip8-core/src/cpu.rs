@@ -0,0 +1,2483 @@
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::collections::BTreeMap;
+use core::hash::Hasher;
+#[cfg(feature = "std")]
+use std::sync::mpsc::{self, Receiver};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use crate::audio::{AudioPattern, PatternPlayer, DEFAULT_PITCH};
+use crate::builder::PROGRAM_START;
+use crate::display::{DirtyRows, Framebuffer};
+use crate::error::Chip8Error;
+use crate::events::{EmulatorEvent, EventQueue};
+use crate::hash::Fnv1a;
+use crate::hooks::Hooks;
+#[cfg(feature = "std")]
+use crate::input::InputSender;
+use crate::input::{InputEvent, InputQueue};
+use crate::instruction::{decode, Instruction};
+use crate::keypad::Keypad;
+use crate::memory::{Memory, MemoryError, DEFAULT_MEMORY_SIZE, FONT_ADDR, FONT_GLYPH_SIZE};
+use crate::peripherals::{AudioSink, Peripherals};
+use crate::rng::{Rng, XorShiftRng};
+use crate::save_state::SaveState;
+use crate::stack::{CallStack, StackPolicy};
+use crate::timers::Timers;
+
+/// Behaviors that differ between CHIP-8 interpreters, which some ROMs depend on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quirks {
+    /// `FX1E` sets `VF` to 1 when `I` goes past the end of memory (0xFFF by default), and to 0
+    /// otherwise (Amiga/SCHIP).
+    pub add_i_sets_vf: bool,
+    /// `FX55`/`FX65` leave `I` pointing just past the last register stored or loaded (COSMAC VIP).
+    pub load_store_increments_i: bool,
+    /// `8XY6`/`8XYE` shift `Vy` and store the result in `Vx` (COSMAC VIP), rather than shifting
+    /// `Vx` in place (CHIP-48/SCHIP).
+    pub shift_uses_vy: bool,
+    /// `BNNN` is read as `BXNN` and jumps to `xnn + Vx` instead of `nnn + V0` (CHIP-48/SCHIP).
+    pub jump_with_vx: bool,
+    /// `DXYN` cuts sprites off at the edges of the screen (COSMAC VIP/SCHIP), rather than wrapping
+    /// the parts that fall off around to the opposite edge. Either way, the starting coordinates
+    /// wrap.
+    pub clip_sprites: bool,
+    /// `FX0A` finishes when the key is released rather than when it's pressed (COSMAC VIP), so a
+    /// key held down from one `FX0A` isn't read again straight away by the next.
+    pub wait_for_release: bool,
+}
+
+impl Quirks {
+    /// The COSMAC VIP's original interpreter, for the oldest ROMs.
+    pub const VIP: Quirks = Quirks {
+        add_i_sets_vf: false,
+        load_store_increments_i: true,
+        shift_uses_vy: true,
+        jump_with_vx: false,
+        clip_sprites: true,
+        wait_for_release: true,
+    };
+    /// SUPER-CHIP 1.1 on the HP 48, which most games from the 90s were written for.
+    pub const SCHIP: Quirks = Quirks {
+        add_i_sets_vf: true,
+        load_store_increments_i: false,
+        shift_uses_vy: false,
+        jump_with_vx: true,
+        clip_sprites: true,
+        wait_for_release: false,
+    };
+    /// XO-CHIP, as Octo runs it.
+    pub const XO_CHIP: Quirks = Quirks {
+        add_i_sets_vf: false,
+        load_store_increments_i: true,
+        shift_uses_vy: true,
+        jump_with_vx: false,
+        clip_sprites: false,
+        wait_for_release: false,
+    };
+
+    /// The profiles `named` accepts. `modern` is the default, with every quirk off.
+    pub const NAMES: [&'static str; 4] = ["modern", "vip", "schip", "xo-chip"];
+
+    /// A profile by name, as given to a frontend's `--quirks`.
+    pub fn named(name: &str) -> Option<Quirks> {
+        let quirks = match name {
+            "modern" => Quirks::default(),
+            "vip" => Quirks::VIP,
+            "schip" => Quirks::SCHIP,
+            "xo-chip" => Quirks::XO_CHIP,
+            _ => return None,
+        };
+
+        Some(quirks)
+    }
+}
+
+/// How many instructions are executed per second by default.
+pub const DEFAULT_CLOCK_HZ: u32 = 600;
+/// How many instructions `run_frame` executes by default: a 60th of `DEFAULT_CLOCK_HZ`.
+pub const DEFAULT_CYCLES_PER_FRAME: usize = DEFAULT_CLOCK_HZ as usize / FRAMES_PER_SECOND;
+/// How often the timers tick, and so how often `run_frame` should be called.
+const FRAMES_PER_SECOND: usize = 60;
+
+/// What a call to `Cpu::step` did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    /// An instruction ran and didn't touch the display.
+    Executed,
+    /// An instruction ran and changed the display.
+    Drew,
+    /// The program is blocked on `FX0A` until `Cpu::press_key` is called.
+    WaitingForKey,
+    /// The next instruction is `0000`, which stops the program.
+    Halted,
+}
+
+/// Why `Cpu::run` returned control to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunExit {
+    /// A frame's worth of instructions ran. `drew` is whether any of them changed the display.
+    FrameComplete { drew: bool },
+    /// The program blocked on `FX0A`, and will store the next key press in register `x`. The
+    /// frame still ended.
+    WaitingForKey { x: u8 },
+    /// The next instruction is `0000`.
+    Halted,
+}
+
+/// A native routine attached to a `0NNN` address with `Cpu::register_routine`.
+#[cfg(feature = "alloc")]
+pub type MachineRoutine<R, const MEM: usize, const STACK: usize> =
+    Box<dyn FnMut(&mut Cpu<R, MEM, STACK>) + Send + Sync>;
+
+/// How many nested subroutine calls a classic CHIP-8 supports.
+pub const DEFAULT_STACK_DEPTH: usize = 16;
+
+/// The complete state of a CHIP-8 machine, with `MEM` bytes of memory and room for `STACK`
+/// nested subroutine calls.
+///
+/// With the `serde` feature the whole machine state can be serialized, except for hooks and
+/// machine routines, which are left unset when deserializing.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(bound(
+        serialize = "R: serde::Serialize",
+        deserialize = "R: serde::Deserialize<'de>"
+    ))
+)]
+pub struct Cpu<
+    R: Rng = XorShiftRng,
+    const MEM: usize = DEFAULT_MEMORY_SIZE,
+    const STACK: usize = DEFAULT_STACK_DEPTH,
+> {
+    registers: [u8; 16],
+    index_register: u16,
+    position_in_memory: usize,
+    /// Where the last ROM was loaded, and so where `reset` restarts execution.
+    entry_point: usize,
+    memory: Memory<MEM>,
+    stack: CallStack<STACK>,
+    display: Framebuffer,
+    /// The rows changed since the display was last presented by `run_frame_with`.
+    dirty_rows: DirtyRows,
+    keypad: Keypad,
+    /// The keys the `Peripherals` passed to `run_frame_with` reported held last frame.
+    io_keys: u16,
+    /// Keys pressed by queued input this frame.
+    tapped: u16,
+    /// Keys released by queued input in the same frame they were pressed, which stay down until
+    /// the frame ends so that the program gets a chance to see them.
+    deferred_releases: u16,
+    /// The keys `EX9E` and `EXA1` have tested this frame.
+    polling: u16,
+    /// The keys they tested last frame.
+    polled_keys: u16,
+    waiting_for_key: Option<u8>,
+    /// With the `wait_for_release` quirk, the key pressed during `FX0A` that it's waiting to be
+    /// released.
+    waiting_key_down: Option<u8>,
+    timers: Timers,
+    /// The 1-bit samples `F002` last loaded, if it has run since power-on or the last `reset`.
+    audio_pattern: Option<[u8; 16]>,
+    /// The pattern's pitch, from `FX3A`.
+    pitch: u8,
+    /// Frames completed since power-on or the last `reset`.
+    frame: u64,
+    rng: R,
+    quirks: Quirks,
+    /// Treat unknown opcodes as no-ops instead of stopping with an error.
+    ignore_unknown_opcodes: bool,
+    /// Stop with an error when `7XKK` or `FX1E` overflow, which is usually a ROM bug.
+    trap_overflow: bool,
+    #[cfg(feature = "alloc")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    routines: BTreeMap<u16, MachineRoutine<R, MEM, STACK>>,
+    /// Instructions per second, spread over 60 frames a second.
+    clock_hz: u32,
+    /// Sixtieths of an instruction owed from earlier frames when `clock_hz` isn't a multiple of 60.
+    cycle_remainder: u32,
+    /// How far `step_clocked` has got towards the end of the frame, in 60ths of an instruction.
+    frame_progress: u32,
+    /// Whether the `AudioSink` passed to `run_frame_with` is currently beeping.
+    beeping: bool,
+    /// Whether the audio pattern or pitch has changed since `run_frame_with` last passed them
+    /// on to the `AudioSink`.
+    pattern_changed: bool,
+    /// Makes the samples for an `AudioSink` with a `sample_rate`, once there's been one.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    sample_player: Option<PatternPlayer>,
+    /// 60ths of a sample owed from earlier frames when the sink's rate isn't a multiple of 60.
+    sample_remainder: u32,
+    #[cfg_attr(feature = "serde", serde(skip, default = "Hooks::new"))]
+    hooks: Hooks,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    events: EventQueue,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    input: InputQueue,
+    /// The sending half is kept to hand out clones from `input_sender`.
+    #[cfg(feature = "std")]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    input_channel: Option<(InputSender, Mutex<Receiver<InputEvent>>)>,
+}
+
+impl<R: Rng, const MEM: usize, const STACK: usize> Cpu<R, MEM, STACK> {
+    /// Create a machine with cleared registers and memory, except for the built-in font.
+    pub fn new(rng: R) -> Self {
+        Cpu {
+            registers: [0; 16],
+            index_register: 0,
+            memory: Memory::new(),
+            position_in_memory: 0,
+            entry_point: 0,
+            stack: CallStack::new(),
+            display: Framebuffer::new(),
+            dirty_rows: DirtyRows::NONE,
+            keypad: Keypad::new(),
+            io_keys: 0,
+            tapped: 0,
+            deferred_releases: 0,
+            polling: 0,
+            polled_keys: 0,
+            waiting_for_key: None,
+            waiting_key_down: None,
+            timers: Timers::new(),
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            frame: 0,
+            rng,
+            quirks: Quirks::default(),
+            ignore_unknown_opcodes: false,
+            trap_overflow: false,
+            #[cfg(feature = "alloc")]
+            routines: BTreeMap::new(),
+            clock_hz: DEFAULT_CLOCK_HZ,
+            cycle_remainder: 0,
+            frame_progress: 0,
+            beeping: false,
+            pattern_changed: false,
+            sample_player: None,
+            sample_remainder: 0,
+            hooks: Hooks::new(),
+            events: EventQueue::new(),
+            input: InputQueue::new(),
+            #[cfg(feature = "std")]
+            input_channel: None,
+        }
+    }
+
+    /// Read the big-endian opcode at `addr`.
+    fn read_opcode(&self, addr: usize) -> Result<u16, MemoryError> {
+        let op_byte1 = self.memory.read(addr)? as u16;
+        let op_byte2 = self.memory.read(addr + 1)? as u16;
+
+        Ok(op_byte1 << 8 | op_byte2)
+    }
+
+    /// Decode the instruction at the program counter without executing it.
+    pub(crate) fn fetch(&self) -> Result<(usize, Instruction), Chip8Error> {
+        let pc = self.position_in_memory;
+        let opcode = self
+            .read_opcode(pc)
+            .map_err(|err| Chip8Error::from_memory(err, pc))?;
+
+        Ok((pc, decode(opcode)))
+    }
+
+    /// Copy `rom` into memory at `PROGRAM_START` and point the program counter at it.
+    ///
+    /// Fails with `Chip8Error::RomTooLarge`, without touching memory, if the ROM doesn't fit.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.load_rom_at(PROGRAM_START, rom)
+    }
+
+    /// Like `load_rom`, but at `addr`, e.g. 0x600 for ETI 660 programs.
+    ///
+    /// Also fails, with `Chip8Error::LoadAddressOutOfBounds`, if `addr` is past the end of
+    /// memory, even for an empty ROM.
+    pub fn load_rom_at(&mut self, addr: usize, program: &[u8]) -> Result<(), Chip8Error> {
+        let len = self.memory.len();
+        let Some(max) = len.checked_sub(addr) else {
+            return Err(Chip8Error::LoadAddressOutOfBounds { addr, len });
+        };
+        if program.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: program.len(),
+                max,
+            });
+        }
+
+        self.memory.as_mut_slice()[addr..addr + program.len()].copy_from_slice(program);
+        self.position_in_memory = addr;
+        self.entry_point = addr;
+
+        Ok(())
+    }
+
+    /// Restore the power-on state and restart the loaded program from the beginning.
+    ///
+    /// Registers, the stack, the framebuffer, the keypad, queued input, the timers, the frame
+    /// count and the progress through a fractional `clock_hz` are cleared, the random number
+    /// generator goes back to its seed with `Rng::restart` and the font is rewritten, so the
+    /// program runs as it would on a fresh machine. The rest of memory is left alone, along with
+    /// configuration such as quirks, hooks and machine routines.
+    pub fn reset(&mut self) {
+        if self.timers.sound > 0 {
+            self.sound_stopped();
+        }
+
+        self.events.clear();
+        self.registers = [0; 16];
+        self.index_register = 0;
+        self.position_in_memory = self.entry_point;
+        self.stack.clear();
+        self.display = Framebuffer::new();
+        self.dirty_rows = DirtyRows::ALL;
+        self.keypad = Keypad::new();
+        self.io_keys = 0;
+        self.tapped = 0;
+        self.deferred_releases = 0;
+        self.polling = 0;
+        self.polled_keys = 0;
+        self.input.clear();
+        self.waiting_for_key = None;
+        self.waiting_key_down = None;
+        self.timers = Timers::new();
+        self.pattern_changed |= self.audio_pattern.is_some();
+        self.audio_pattern = None;
+        self.pitch = DEFAULT_PITCH;
+        self.frame = 0;
+        self.cycle_remainder = 0;
+        self.frame_progress = 0;
+        self.sample_remainder = 0;
+        self.rng.restart();
+        self.memory.restore_font();
+    }
+
+    /// Like `reset`, but also clear memory and load `rom` where the previous ROM was loaded.
+    ///
+    /// Fails with `Chip8Error::RomTooLarge`, leaving the machine reset but empty, if `rom`
+    /// doesn't fit.
+    pub fn reset_with_rom(&mut self, rom: &[u8]) -> Result<(), Chip8Error> {
+        self.reset();
+        self.memory.clear();
+
+        self.load_rom_at(self.entry_point, rom)
+    }
+
+    /// Run a frame's worth of instructions, a 60th of `clock_hz`, or until the program halts,
+    /// then hand control back to the caller.
+    ///
+    /// Timers are ticked at the end of the frame, so a frontend can simply call `run` once per
+    /// frame and render, read input and play audio in between. Blocking on `FX0A` spends the
+    /// rest of the frame waiting, which still ends it, so the timers keep counting down and
+    /// input queued for later frames still arrives. When blocked, call `press_key` to deliver
+    /// a key and then `run` again to resume.
+    ///
+    /// Queued input that's due is applied first, whether or not the last call completed a frame.
+    pub fn run(&mut self) -> Result<RunExit, Chip8Error> {
+        let mut exit = None;
+        let mut drew = false;
+        self.apply_queued_input();
+
+        for _ in 0..self.frame_cycles() {
+            match self.step()? {
+                StepOutcome::Executed => {}
+                StepOutcome::Drew => drew = true,
+                StepOutcome::WaitingForKey => {
+                    // Only reached while `waiting_for_key` is set.
+                    let x = self.waiting_for_key.unwrap_or_default();
+                    exit = Some(RunExit::WaitingForKey { x });
+                    break;
+                }
+                StepOutcome::Halted => return Ok(RunExit::Halted),
+            }
+        }
+
+        self.end_frame();
+
+        Ok(exit.unwrap_or(RunExit::FrameComplete { drew }))
+    }
+
+    /// Fetch, decode and execute exactly one instruction.
+    ///
+    /// While blocked on `FX0A`, or when the next instruction is `0000`, nothing is executed and
+    /// the program counter stays put, so calling `step` again reports the same outcome.
+    pub fn step(&mut self) -> Result<StepOutcome, Chip8Error> {
+        if self.waiting_for_key.is_some() {
+            return Ok(StepOutcome::WaitingForKey);
+        }
+
+        let (pc, instruction) = self.fetch()?;
+        if instruction == Instruction::Halt {
+            self.events.push_once(EmulatorEvent::Halted);
+            return Ok(StepOutcome::Halted);
+        }
+
+        self.position_in_memory += 2;
+        self.hooks.instruction(pc, instruction);
+        self.execute(instruction, pc)?;
+
+        if self.waiting_for_key.is_some() {
+            Ok(StepOutcome::WaitingForKey)
+        } else if matches!(
+            instruction,
+            Instruction::Drw { .. }
+                | Instruction::Cls
+                | Instruction::Scd { .. }
+                | Instruction::Scr
+                | Instruction::Scl
+                | Instruction::Low
+                | Instruction::High
+        ) {
+            Ok(StepOutcome::Drew)
+        } else {
+            Ok(StepOutcome::Executed)
+        }
+    }
+
+    /// Like `step`, for hosts that call it `clock_hz` times a second themselves, e.g. from a
+    /// hardware timer, instead of running whole frames: once enough calls have been made for a
+    /// 60th of a second, the frame ends and the timers tick, as at the end of `run_frame`.
+    ///
+    /// The calls are counted in 60ths, so with a clock that isn't a multiple of 60 some frames
+    /// take one more call than others and the timers keep to 60Hz over any length of time.
+    /// Calls that only wait for a key or stay halted count too, since the timers run on
+    /// regardless.
+    pub fn step_clocked(&mut self) -> Result<StepOutcome, Chip8Error> {
+        let frame = FRAMES_PER_SECOND as u32;
+        // Only the first call of a frame can start with less than one call's worth of progress.
+        if self.frame_progress < frame {
+            self.apply_queued_input();
+        }
+
+        let outcome = self.step()?;
+
+        self.frame_progress += frame;
+        while self.clock_hz > 0 && self.frame_progress >= self.clock_hz {
+            self.frame_progress -= self.clock_hz;
+            self.end_frame();
+        }
+
+        Ok(outcome)
+    }
+
+    /// Execute up to `n` instructions, stopping early if the program halts or waits for a key.
+    ///
+    /// Returns `Halted` or `WaitingForKey` if execution stopped early, otherwise `Drew` if any of
+    /// the instructions changed the display and `Executed` if none did. Timers are not ticked.
+    pub fn run_cycles(&mut self, n: usize) -> Result<StepOutcome, Chip8Error> {
+        let mut outcome = StepOutcome::Executed;
+
+        for _ in 0..n {
+            match self.step()? {
+                StepOutcome::Executed => {}
+                StepOutcome::Drew => outcome = StepOutcome::Drew,
+                stopped => return Ok(stopped),
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    /// Emulate one 60Hz frame: apply the queued input that's due, run a 60th of `clock_hz`
+    /// instructions, then tick the timers once.
+    ///
+    /// Frontends call this once per displayed frame to pace emulation against wall-clock time.
+    /// Drawing and the timers then stay at 60Hz however fast the CPU is clocked.
+    pub fn run_frame(&mut self) -> Result<StepOutcome, Chip8Error> {
+        self.apply_queued_input();
+        let cycles = self.frame_cycles();
+        let outcome = self.run_cycles(cycles);
+        self.end_frame();
+
+        outcome
+    }
+
+    /// Like `run_frame`, but also exchange input and output with a frontend's `Peripherals`.
+    ///
+    /// Before running, the keys `io` reports pressed or released since the last frame are
+    /// pressed or released on the keypad, which may complete a pending `FX0A`, and then the
+    /// queued input is applied. Keys `io` hasn't changed keep whatever state the queue or
+    /// `press_key` left them in. Afterwards the framebuffer is presented if it changed, the
+    /// `AudioSink` is given the audio pattern if `F002` or `FX3A` changed it, the beep is
+    /// started or stopped to follow the sound timer, and the frame's samples are pushed if the
+    /// sink has a `sample_rate`.
+    pub fn run_frame_with(&mut self, io: &mut impl Peripherals) -> Result<StepOutcome, Chip8Error> {
+        let mask = (0..16).fold(0, |mask, key| mask | (io.is_down(key) as u16) << key);
+        let changed = self.io_keys ^ mask;
+        self.io_keys = mask;
+        self.set_keys(self.keypad.mask() & !changed | mask & changed);
+        self.apply_queued_input();
+
+        let cycles = self.frame_cycles();
+        let outcome = self.run_cycles(cycles)?;
+
+        if !self.dirty_rows.is_empty() {
+            io.present_rows(&self.display, self.dirty_rows);
+            self.dirty_rows = DirtyRows::NONE;
+        }
+
+        if self.pattern_changed {
+            io.set_pattern(self.audio_pattern());
+            self.pattern_changed = false;
+        }
+
+        let sounding = self.timers.sound > 0;
+        if sounding && !self.beeping {
+            io.start_beep();
+        } else if !sounding && self.beeping {
+            io.stop_beep();
+        }
+        self.beeping = sounding;
+
+        if let Some(rate) = io.sample_rate() {
+            self.push_samples(io, rate);
+        }
+
+        self.end_frame();
+
+        Ok(outcome)
+    }
+
+    /// Give `audio` a frame's worth of samples at `rate`, a few at a time so that nothing
+    /// has to be allocated.
+    fn push_samples(&mut self, audio: &mut impl AudioSink, rate: u32) {
+        let pattern = self.audio_pattern().unwrap_or(AudioPattern::BUZZER);
+        let player = match &mut self.sample_player {
+            Some(player) if player.sample_rate() == rate => player,
+            slot => slot.insert(PatternPlayer::new(pattern, rate)),
+        };
+        if *player.pattern() != pattern {
+            player.set_pattern(pattern);
+        }
+
+        let owed = rate as u64 + self.sample_remainder as u64;
+        let frames = FRAMES_PER_SECOND as u64;
+        self.sample_remainder = (owed % frames) as u32;
+
+        let mut buffer = [0.0; 128];
+        let mut left = (owed / frames) as usize;
+        while left > 0 {
+            let chunk = &mut buffer[..left.min(128)];
+            for sample in chunk.iter_mut() {
+                *sample = if self.beeping {
+                    player.next_sample()
+                } else {
+                    0.0
+                };
+            }
+            audio.push_samples(chunk);
+            left -= chunk.len();
+        }
+    }
+
+    /// Execute a single decoded instruction that was read from `pc`.
+    fn execute(&mut self, instruction: Instruction, pc: usize) -> Result<(), Chip8Error> {
+        use Instruction::*;
+
+        match instruction {
+            Halt => { /* `step` stops before executing a halt. */ }
+            Sys { addr } => self.sys(addr, pc)?,
+            Scd { n } => self.scd(n),
+            Cls => self.cls(),
+            Ret => self.ret(pc)?,
+            Scr => self.scr(),
+            Scl => self.scl(),
+            Low => self.set_hires(false),
+            High => self.set_hires(true),
+            Jp { addr } => self.jmp(addr),
+            Call { addr } => self.call(addr, pc)?,
+            SeByte { x, kk } => self.se(self.registers[x as usize], kk),
+            SneByte { x, kk } => self.sne(self.registers[x as usize], kk),
+            SeReg { x, y } => self.se(self.registers[x as usize], self.registers[y as usize]),
+            LdByte { x, kk } => self.ld(x, kk),
+            AddByte { x, kk } => {
+                let overflowed = self.add(x, kk);
+                self.check_overflow(overflowed, instruction, pc)?;
+            }
+            LdReg { x, y } => self.ld(x, self.registers[y as usize]),
+            Or { x, y } => self.or_xy(x, y),
+            And { x, y } => self.and_xy(x, y),
+            Xor { x, y } => self.xor_xy(x, y),
+            AddReg { x, y } => self.add_xy(x, y),
+            Sub { x, y } => self.sub_xy(x, y),
+            Shr { x, y } => self.shr_xy(x, y),
+            Subn { x, y } => self.subn_xy(x, y),
+            Shl { x, y } => self.shl_xy(x, y),
+            SneReg { x, y } => self.sne_xy(x, y),
+            LdI { addr } => self.ld_i(addr),
+            JpV0 { x, addr } => self.jp_v0(x, addr),
+            Rnd { x, kk } => self.rnd(x, kk),
+            Drw { x, y, n } => self.drw(x, y, n, pc)?,
+            Skp { x } => self.skp(x),
+            Sknp { x } => self.sknp(x),
+            Plane { n } => self.display.select_planes(n),
+            Audio => self.ld_audio(pc)?,
+            LdVxDt { x } => self.ld_vx_dt(x),
+            LdVxK { x } => self.ld_vx_k(x),
+            LdDtVx { x } => self.ld_dt_vx(x),
+            LdStVx { x } => self.ld_st_vx(x),
+            AddI { x } => {
+                let overflowed = self.add_i(x);
+                self.check_overflow(overflowed, instruction, pc)?;
+            }
+            LdF { x } => self.ld_f(x),
+            LdB { x } => self.ld_b(x, pc)?,
+            Pitch { x } => self.ld_pitch(x),
+            LdIVx { x } => self.ld_i_vx(x, pc)?,
+            LdVxI { x } => self.ld_vx_i(x, pc)?,
+            Unknown(opcode) => self.unknown_opcode(opcode, pc)?,
+        }
+
+        Ok(())
+    }
+
+    /// The general purpose registers `V0` through `VF`.
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.registers
+    }
+
+    /// The address of the next instruction to execute.
+    pub fn pc(&self) -> usize {
+        self.position_in_memory
+    }
+
+    /// Jump to `addr`. An address outside memory is reported by the next `step`.
+    pub fn set_pc(&mut self, addr: usize) {
+        self.position_in_memory = addr;
+    }
+
+    /// The index register `I`.
+    pub fn i(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn set_i(&mut self, value: u16) {
+        self.index_register = value;
+    }
+
+    /// The return addresses of the active subroutine calls, outermost first.
+    pub fn stack_frames(&self) -> impl Iterator<Item = u16> + '_ {
+        self.stack.frames()
+    }
+
+    /// All `MEM` bytes of addressable memory. Programs conventionally start at 0x200.
+    pub fn memory(&self) -> &Memory<MEM> {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut Memory<MEM> {
+        &mut self.memory
+    }
+
+    pub fn display(&self) -> &Framebuffer {
+        &self.display
+    }
+
+    /// Whether the display changed since it was last marked clean, so frontends can skip
+    /// redrawing identical frames. `run_frame_with` does this bookkeeping itself.
+    pub fn is_display_dirty(&self) -> bool {
+        !self.dirty_rows.is_empty()
+    }
+
+    /// Which rows changed since the display was last marked clean, for frontends that redraw
+    /// only those.
+    pub fn dirty_rows(&self) -> DirtyRows {
+        self.dirty_rows
+    }
+
+    /// Record that the current display has been drawn.
+    pub fn mark_display_clean(&mut self) {
+        self.dirty_rows = DirtyRows::NONE;
+    }
+
+    pub fn keypad(&self) -> &Keypad {
+        &self.keypad
+    }
+
+    pub fn timers(&self) -> &Timers {
+        &self.timers
+    }
+
+    pub fn timers_mut(&mut self) -> &mut Timers {
+        &mut self.timers
+    }
+
+    /// The register `FX0A` will store the next key press in, if the program is waiting for one.
+    pub fn waiting_for_key(&self) -> Option<u8> {
+        self.waiting_for_key
+    }
+
+    /// The keys `EX9E` and `EXA1` tested during the last frame run, one bit per key with key 0
+    /// in the low bit, e.g. to show players which keys a game reads.
+    pub fn polled_keys(&self) -> u16 {
+        self.polled_keys
+    }
+
+    /// What the buzzer plays while the sound timer runs: the pattern `F002` loaded at the pitch
+    /// `FX3A` set, or `None`, for a plain tone, until an XO-CHIP program runs `F002`.
+    pub fn audio_pattern(&self) -> Option<AudioPattern> {
+        self.audio_pattern.map(|bits| AudioPattern {
+            bits,
+            pitch: self.pitch,
+        })
+    }
+
+    /// Set how many instructions `run_frame` executes per frame.
+    pub fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.set_clock_hz((cycles * FRAMES_PER_SECOND) as u32);
+    }
+
+    /// How many instructions are executed per second of frames.
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    /// Set how many instructions to execute per second. It needn't be a multiple of 60: frames
+    /// run one more instruction now and then to make up the difference.
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.clock_hz = hz;
+        self.cycle_remainder = 0;
+        self.frame_progress = 0;
+    }
+
+    /// How many instructions to run this frame, carrying the fraction left over to the next.
+    fn frame_cycles(&mut self) -> usize {
+        let owed = self.clock_hz as u64 + self.cycle_remainder as u64;
+        let frames = FRAMES_PER_SECOND as u64;
+        self.cycle_remainder = (owed % frames) as u32;
+
+        (owed / frames) as usize
+    }
+
+    pub fn quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn quirks_mut(&mut self) -> &mut Quirks {
+        &mut self.quirks
+    }
+
+    /// Treat unknown opcodes as no-ops instead of stopping with `Chip8Error::UnknownOpcode`.
+    pub fn set_ignore_unknown_opcodes(&mut self, ignore: bool) {
+        self.ignore_unknown_opcodes = ignore;
+    }
+
+    /// Choose what `2NNN` does once all `STACK` call frames are in use.
+    pub fn set_stack_policy(&mut self, policy: StackPolicy) {
+        self.stack.set_policy(policy);
+    }
+
+    /// Stop with `Chip8Error::Overflow` when `7XKK` or `FX1E` overflow.
+    pub fn set_trap_overflow(&mut self, trap: bool) {
+        self.trap_overflow = trap;
+    }
+
+    fn unknown_opcode(&self, opcode: u16, pc: usize) -> Result<(), Chip8Error> {
+        if self.ignore_unknown_opcodes {
+            Ok(())
+        } else {
+            Err(Chip8Error::UnknownOpcode { opcode, pc })
+        }
+    }
+
+    fn check_overflow(
+        &self,
+        overflowed: bool,
+        instruction: Instruction,
+        pc: usize,
+    ) -> Result<(), Chip8Error> {
+        if overflowed && self.trap_overflow {
+            Err(Chip8Error::Overflow { instruction, pc })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Attach a native routine to the `0NNN` machine-code address `addr`.
+    ///
+    /// When the ROM executes `0NNN`, the routine is called with the CPU instead of jumping into
+    /// (nonexistent) RCA 1802 machine code. It can inspect and modify any state, e.g. to log
+    /// registers, make test assertions or emulate a custom peripheral.
+    #[cfg(feature = "alloc")]
+    pub fn register_routine(
+        &mut self,
+        addr: u16,
+        routine: impl FnMut(&mut Cpu<R, MEM, STACK>) + Send + Sync + 'static,
+    ) {
+        self.routines.insert(addr & 0x0FFF, Box::new(routine));
+    }
+
+    /// Mark `key` as pressed, completing a pending `FX0A` if there is one, unless the
+    /// `wait_for_release` quirk has it wait for the key to come back up.
+    pub fn press_key(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.keypad.press(key);
+        self.hooks.input(InputEvent::press(key, self.frame));
+
+        if self.quirks.wait_for_release {
+            if self.waiting_for_key.is_some() && self.waiting_key_down.is_none() {
+                self.waiting_key_down = Some(key);
+            }
+        } else if let Some(x) = self.waiting_for_key.take() {
+            self.registers[x as usize] = key;
+        }
+    }
+
+    /// Mark `key` as released, completing a pending `FX0A` that was waiting for it with the
+    /// `wait_for_release` quirk.
+    pub fn release_key(&mut self, key: u8) {
+        let key = key & 0xF;
+        self.keypad.release(key);
+        self.hooks.input(InputEvent::release(key, self.frame));
+
+        if self.waiting_key_down == Some(key) {
+            self.waiting_key_down = None;
+            if let Some(x) = self.waiting_for_key.take() {
+                self.registers[x as usize] = key;
+            }
+        }
+    }
+
+    /// Hold down exactly the keys in `mask`, one bit per key with key 0 in the low bit, as
+    /// `press_key` and `release_key` would for each key that changed. For input that's polled
+    /// rather than delivered as events.
+    pub fn set_keys(&mut self, mask: u16) {
+        let changed = self.keypad.mask() ^ mask;
+        for key in 0..16 {
+            if changed & 1 << key == 0 {
+                continue;
+            }
+
+            if mask & 1 << key != 0 {
+                self.press_key(key);
+            } else {
+                self.release_key(key);
+            }
+        }
+    }
+
+    /// Queue a key press or release for the start of frame `event.frame`, or the next frame if
+    /// that one's already started.
+    ///
+    /// Queued input is applied by `run`, `run_frame` and `run_frame_with` before they execute
+    /// anything, in frame order, and events for the same frame in the order they were queued. A
+    /// key released in the frame it was pressed in stays down until the end of that frame, so
+    /// that `EX9E` and `EXA1` see even the quickest tap.
+    pub fn queue_input(&mut self, event: InputEvent) {
+        if let Some(oldest) = self.input.push(event) {
+            self.apply_input(oldest);
+        }
+    }
+
+    /// A handle for queueing input from other threads, which doesn't need the machine to be
+    /// locked or borrowed. Its events are picked up at the next frame boundary.
+    #[cfg(feature = "std")]
+    pub fn input_sender(&mut self) -> InputSender {
+        let (sender, _) = self.input_channel.get_or_insert_with(|| {
+            let (sender, receiver) = mpsc::channel();
+            (InputSender(sender), Mutex::new(receiver))
+        });
+
+        sender.clone()
+    }
+
+    /// The number of frames completed since power-on or the last `reset`, the clock that
+    /// `InputEvent`s are stamped with.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// A fingerprint of the machine's state: registers, memory, the stack, the display, the keypad,
+    /// the timers, the audio pattern and the frame count. Two runs that hash the same almost
+    /// certainly ended up in the same state, e.g. a replay and the recording it was made from.
+    ///
+    /// The random number generator and configuration such as quirks aren't included, and
+    /// neither is anything that only matters to peripherals, like which rows need redrawing.
+    pub fn state_hash(&self) -> u64 {
+        let mut hasher = Fnv1a::new();
+
+        hasher.write(&self.registers);
+        hasher.write(&self.index_register.to_le_bytes());
+        hasher.write(&(self.position_in_memory as u64).to_le_bytes());
+        hasher.write(self.memory.as_slice());
+        hasher.write(&(self.stack.frames().count() as u64).to_le_bytes());
+        for frame in self.stack.frames() {
+            hasher.write(&frame.to_le_bytes());
+        }
+        for row in self.display.rows() {
+            for plane in row {
+                hasher.write(&plane.to_le_bytes());
+            }
+        }
+        hasher.write(&[
+            self.display.is_hires() as u8,
+            self.display.planes(),
+            self.waiting_for_key.map_or(0xFF, |x| x),
+            self.waiting_key_down.map_or(0xFF, |key| key),
+            self.timers.delay,
+            self.timers.sound,
+        ]);
+        hasher.write(&self.keypad.mask().to_le_bytes());
+        // Only once a program has used them, so that recordings of programs that don't still
+        // match.
+        if self.audio_pattern.is_some() || self.pitch != DEFAULT_PITCH {
+            hasher.write(&self.audio_pattern.unwrap_or_default());
+            hasher.write(&[self.pitch]);
+        }
+        hasher.write(&self.frame.to_le_bytes());
+
+        hasher.finish()
+    }
+
+    /// Copy the machine's state, to go back to later with `load_state`.
+    pub fn save_state(&self) -> SaveState<R, MEM, STACK>
+    where
+        R: Clone,
+    {
+        SaveState {
+            registers: self.registers,
+            index_register: self.index_register,
+            memory: self.memory.clone(),
+            position_in_memory: self.position_in_memory,
+            stack: self.stack.clone(),
+            display: self.display.clone(),
+            keypad: self.keypad,
+            waiting_for_key: self.waiting_for_key,
+            waiting_key_down: self.waiting_key_down,
+            timers: self.timers,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            frame: self.frame,
+            rng: self.rng.clone(),
+            cycle_remainder: self.cycle_remainder,
+            frame_progress: self.frame_progress,
+        }
+    }
+
+    /// Go back to `state`, from `save_state`.
+    ///
+    /// Queued input is dropped, since it was stamped with frames from before the jump, and the
+    /// whole display is redrawn by the next `run_frame_with`. Keys held on the peripherals that
+    /// weren't held in `state` are released then, and pressed again if they were.
+    pub fn load_state(&mut self, state: &SaveState<R, MEM, STACK>)
+    where
+        R: Clone,
+    {
+        let sounding = self.timers.sound > 0;
+
+        self.registers = state.registers;
+        self.index_register = state.index_register;
+        self.memory = state.memory.clone();
+        self.position_in_memory = state.position_in_memory;
+        self.stack.load(&state.stack);
+        self.display = state.display.clone();
+        self.keypad = state.keypad;
+        self.io_keys = state.keypad.mask();
+        self.tapped = 0;
+        self.deferred_releases = 0;
+        self.input.clear();
+        self.waiting_for_key = state.waiting_for_key;
+        self.waiting_key_down = state.waiting_key_down;
+        self.timers = state.timers;
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.pattern_changed = true;
+        self.frame = state.frame;
+        self.rng = state.rng.clone();
+        self.cycle_remainder = state.cycle_remainder;
+        self.frame_progress = state.frame_progress;
+
+        match (sounding, self.timers.sound > 0) {
+            (false, true) => self.sound_started(),
+            (true, false) => self.sound_stopped(),
+            _ => {}
+        }
+        self.display_changed(DirtyRows::ALL);
+    }
+
+    fn apply_input(&mut self, event: InputEvent) {
+        let bit = 1 << (event.key & 0xF);
+
+        if event.pressed {
+            self.tapped |= bit;
+            self.deferred_releases &= !bit;
+            self.press_key(event.key);
+        } else if self.tapped & bit != 0 {
+            self.deferred_releases |= bit;
+        } else {
+            self.release_key(event.key);
+        }
+    }
+
+    /// Press and release keys for everything queued up to and including the current frame.
+    fn apply_queued_input(&mut self) {
+        #[cfg(feature = "std")]
+        while let Some(event) = self.input_channel.as_ref().and_then(|(_, receiver)| {
+            let receiver = receiver.lock().ok()?;
+            receiver.try_recv().ok()
+        }) {
+            self.queue_input(event);
+        }
+
+        while let Some(event) = self.input.pop_due(self.frame) {
+            self.apply_input(event);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        for key in 0..16 {
+            if self.deferred_releases & 1 << key != 0 {
+                self.release_key(key);
+            }
+        }
+        self.tapped = 0;
+        self.deferred_releases = 0;
+        self.polled_keys = self.polling;
+        self.polling = 0;
+
+        self.tick_timers();
+        self.frame += 1;
+    }
+
+    /// Count the delay and sound timers down by one. The host should call this at 60Hz.
+    pub fn tick_timers(&mut self) {
+        let sounding = self.timers.sound > 0;
+        let delaying = self.timers.delay > 0;
+        self.timers.tick();
+
+        if sounding && self.timers.sound == 0 {
+            self.sound_stopped();
+        }
+        if delaying && self.timers.delay == 0 {
+            self.hooks.delay_expired();
+            self.events.push(EmulatorEvent::DelayExpired);
+        }
+    }
+
+    /// Take up to `N` pending events at once, in a fixed-capacity vector that lives on the stack.
+    #[cfg(feature = "heapless")]
+    pub fn take_events<const N: usize>(&mut self) -> heapless::Vec<EmulatorEvent, N> {
+        let mut events = heapless::Vec::new();
+
+        while !events.is_full() {
+            match self.events.pop() {
+                Some(event) => {
+                    let _ = events.push(event);
+                }
+                None => break,
+            }
+        }
+
+        events
+    }
+
+    /// Take the oldest event that hasn't been drained yet.
+    ///
+    /// Frontends typically drain the queue after each `run_frame`, e.g.
+    /// `while let Some(event) = cpu.poll_event() { ... }`.
+    pub fn poll_event(&mut self) -> Option<EmulatorEvent> {
+        self.events.pop()
+    }
+
+    fn display_changed(&mut self, rows: DirtyRows) {
+        self.dirty_rows.union(rows);
+        self.hooks.draw(&self.display);
+        self.events.push(EmulatorEvent::DisplayUpdated);
+    }
+
+    fn sound_started(&mut self) {
+        self.hooks.sound_start();
+        self.events.push(EmulatorEvent::SoundStarted);
+    }
+
+    fn sound_stopped(&mut self) {
+        self.hooks.sound_stop();
+        self.events.push(EmulatorEvent::SoundStopped);
+    }
+
+    /// Call `hook` with the address and decoded form of every instruction, just before it runs.
+    #[cfg(feature = "alloc")]
+    pub fn on_instruction(&mut self, hook: impl FnMut(usize, Instruction) + Send + Sync + 'static) {
+        self.hooks.instruction = Some(Box::new(hook));
+    }
+
+    /// Call `hook` with the framebuffer every time `00E0` or `DXYN` changes it.
+    #[cfg(feature = "alloc")]
+    pub fn on_draw(&mut self, hook: impl FnMut(&Framebuffer) + Send + Sync + 'static) {
+        self.hooks.draw = Some(Box::new(hook));
+    }
+
+    /// Call `hook` when the sound timer becomes nonzero and the buzzer should start.
+    #[cfg(feature = "alloc")]
+    pub fn on_sound_start(&mut self, hook: impl FnMut() + Send + Sync + 'static) {
+        self.hooks.sound_start = Some(Box::new(hook));
+    }
+
+    /// Call `hook` when the sound timer reaches zero and the buzzer should stop.
+    #[cfg(feature = "alloc")]
+    pub fn on_sound_stop(&mut self, hook: impl FnMut() + Send + Sync + 'static) {
+        self.hooks.sound_stop = Some(Box::new(hook));
+    }
+
+    /// Call `hook` when the delay timer counts down to zero, e.g. to schedule haptics for the
+    /// moment a program's wait ends. Setting it to zero with `FX15` doesn't count.
+    #[cfg(feature = "alloc")]
+    pub fn on_delay_expired(&mut self, hook: impl FnMut() + Send + Sync + 'static) {
+        self.hooks.delay_expired = Some(Box::new(hook));
+    }
+
+    /// Call `hook` with the target register when `FX0A` starts waiting for a key.
+    #[cfg(feature = "alloc")]
+    pub fn on_key_wait(&mut self, hook: impl FnMut(u8) + Send + Sync + 'static) {
+        self.hooks.key_wait = Some(Box::new(hook));
+    }
+
+    /// Call `hook` with every key press and release, however it was delivered, stamped with the
+    /// frame it happened in, e.g. to record an `InputLog`. Held keys are only reported when
+    /// they change, except that `press_key` is always reported.
+    #[cfg(feature = "alloc")]
+    pub fn on_input(&mut self, hook: impl FnMut(InputEvent) + Send + Sync + 'static) {
+        self.hooks.input = Some(Box::new(hook));
+    }
+
+    /// Advance the program counter past the next instruction.
+    ///
+    /// XO-CHIP's `F000 NNNN` is four bytes long, so skipping it must skip its operand too.
+    fn skip_next_instruction(&mut self) {
+        if self.read_opcode(self.position_in_memory) == Ok(0xF000) {
+            self.position_in_memory += 4;
+        } else {
+            self.position_in_memory += 2;
+        }
+    }
+
+    /// Jump to a machine code routine at `nnn`.
+    ///
+    /// Calls the native routine registered for `nnn`. Addresses without a routine are treated
+    /// like any other unknown opcode.
+    #[cfg(feature = "alloc")]
+    fn sys(&mut self, addr: u16, pc: usize) -> Result<(), Chip8Error> {
+        // Swap the routine out while it runs so it can borrow the CPU mutably. The placeholder is
+        // zero-sized, so boxing it doesn't allocate, and neither does putting the routine back.
+        let Some(slot) = self.routines.get_mut(&addr) else {
+            return self.unknown_opcode(addr, pc);
+        };
+        let mut routine = core::mem::replace(slot, Box::new(|_: &mut Self| {}));
+
+        routine(self);
+
+        if let Some(slot) = self.routines.get_mut(&addr) {
+            *slot = routine;
+        }
+
+        Ok(())
+    }
+
+    /// Without an allocator no routines can be registered, so `0NNN` is always unknown.
+    #[cfg(not(feature = "alloc"))]
+    fn sys(&mut self, addr: u16, pc: usize) -> Result<(), Chip8Error> {
+        self.unknown_opcode(addr, pc)
+    }
+
+    /// Return from a subroutine.
+    ///
+    /// The interpreter sets the program counter to the address at the top of the stack, then
+    /// subtracts 1 from the stack pointer.
+    fn ret(&mut self, pc: usize) -> Result<(), Chip8Error> {
+        let addr = self.stack.pop().ok_or(Chip8Error::StackUnderflow { pc })?;
+        self.position_in_memory = addr as usize;
+
+        Ok(())
+    }
+
+    /// Jump to location `nnn`.
+    ///
+    /// The interpreter sets the program counter to `nnn`.
+    fn jmp(&mut self, addr: u16) {
+        self.position_in_memory = addr as usize;
+    }
+
+    /// Call subroutine at `nnn`.
+    ///
+    /// The interpreter increments the stack pointer, then puts the current PC on the top of the
+    /// stack. The PC is then set to `nnn`.
+    fn call(&mut self, addr: u16, pc: usize) -> Result<(), Chip8Error> {
+        if !self.stack.push(self.position_in_memory as u16) {
+            return Err(Chip8Error::StackOverflow { pc });
+        }
+
+        self.position_in_memory = addr as usize;
+
+        Ok(())
+    }
+
+    /// Skip next instruction if `Vx = kk`.
+    ///
+    /// The interpreter compares register `Vx` to `kk`, and if they are equal, increments the
+    /// program counter by 2.
+    fn se(&mut self, vx: u8, kk: u8) {
+        if vx == kk {
+            self.skip_next_instruction();
+        }
+    }
+
+    /// Skip next instruction if `Vx != kk`.
+    ///
+    /// The interpreter compares register `Vx` to `kk`, and if they are not equal, increments the
+    /// program counter by 2.
+    fn sne(&mut self, vx: u8, kk: u8) {
+        if vx != kk {
+            self.skip_next_instruction();
+        }
+    }
+
+    /// Skip next instruction if `Vx != Vy`.
+    ///
+    /// The values of `Vx` and `Vy` are compared, and if they are not equal, the program counter is
+    /// increased by 2.
+    fn sne_xy(&mut self, x: u8, y: u8) {
+        if self.registers[x as usize] != self.registers[y as usize] {
+            self.skip_next_instruction();
+        }
+    }
+
+    /// Set `Vx = kk`.
+    ///
+    /// The interpreter puts the value `kk` into register `Vx`.
+    fn ld(&mut self, vx: u8, kk: u8) {
+        self.registers[vx as usize] = kk;
+    }
+
+    /// Set `Vx = Vx + kk`.
+    ///
+    /// Adds the value `kk` to the value of register `Vx`, then stores the result in `Vx`. The
+    /// addition wraps on overflow and `VF` is left untouched. Returns whether it overflowed.
+    fn add(&mut self, vx: u8, kk: u8) -> bool {
+        let (val, overflow) = self.registers[vx as usize].overflowing_add(kk);
+        self.registers[vx as usize] = val;
+
+        overflow
+    }
+
+    /// Set `Vx = Vx OR Vy`.
+    ///
+    /// Performs a bitwise OR on the values of `Vx` and `Vy`, then stores the result in `Vx`.
+    fn or_xy(&mut self, x: u8, y: u8) {
+        let x_ = self.registers[x as usize];
+        let y_ = self.registers[y as usize];
+
+        self.registers[x as usize] = x_ | y_;
+    }
+
+    /// Set `Vx = Vx AND Vy`.
+    ///
+    /// Performs a bitwise AND on the values of `Vx` and `Vy`, then stores the result in `Vx`.
+    fn and_xy(&mut self, x: u8, y: u8) {
+        let x_ = self.registers[x as usize];
+        let y_ = self.registers[y as usize];
+
+        self.registers[x as usize] = x_ & y_;
+    }
+
+    /// Set `Vx = Vx XOR Vy`.
+    ///
+    /// Performs a bitwise exclusive OR on the values of `Vx` and `Vy`, then stores the result in
+    /// `Vx`.
+    fn xor_xy(&mut self, x: u8, y: u8) {
+        let x_ = self.registers[x as usize];
+        let y_ = self.registers[y as usize];
+
+        self.registers[x as usize] = x_ ^ y_;
+    }
+
+    /// Set `Vx = Vx + Vy`, set `VF = carry`.
+    ///
+    /// The values of `Vx` and `Vy` are added together. If the result is greater than 8 bits
+    /// (i.e., > 255,) `VF` is set to 1, otherwise 0. Only the lowest 8 bits of the result are
+    /// kept, and stored in `Vx`.
+    fn add_xy(&mut self, x: u8, y: u8) {
+        let arg1 = self.registers[x as usize];
+        let arg2 = self.registers[y as usize];
+
+        let (val, overflow) = arg1.overflowing_add(arg2);
+        self.registers[x as usize] = val;
+
+        if overflow {
+            self.registers[0xF] = 1;
+        } else {
+            self.registers[0xF] = 0;
+        }
+    }
+
+    /// Set `Vx = Vx - Vy`, set `VF = NOT borrow`.
+    ///
+    /// If `Vx >= Vy`, so nothing is borrowed, then `VF` is set to 1, otherwise 0. Then `Vy` is
+    /// subtracted from `Vx`, and the results stored in `Vx`.
+    fn sub_xy(&mut self, x: u8, y: u8) {
+        let x_ = self.registers[x as usize];
+        let y_ = self.registers[y as usize];
+
+        if x_ >= y_ {
+            self.registers[0xF] = 1;
+        } else {
+            self.registers[0xF] = 0;
+        }
+
+        self.registers[x as usize] = x_.wrapping_sub(y_);
+    }
+
+    /// Set `Vx = Vx SHR 1`.
+    ///
+    /// If the least-significant bit of `Vx` is 1, then `VF` is set to 1, otherwise 0. Then `Vx` is
+    /// divided by 2. With the `shift_uses_vy` quirk, `Vy` is shifted instead and the result is
+    /// stored in `Vx`.
+    fn shr_xy(&mut self, x: u8, y: u8) {
+        let src = if self.quirks.shift_uses_vy { y } else { x };
+        let val = self.registers[src as usize];
+
+        self.registers[x as usize] = val >> 1;
+        self.registers[0xF] = val & 0x1;
+    }
+
+    /// Set `Vx = Vy - Vx`, set `VF = NOT borrow`.
+    ///
+    /// If `Vy >= Vx`, so nothing is borrowed, then `VF` is set to 1, otherwise 0. Then `Vx` is
+    /// subtracted from `Vy`, and the results stored in `Vx`.
+    fn subn_xy(&mut self, x: u8, y: u8) {
+        let x_ = self.registers[x as usize];
+        let y_ = self.registers[y as usize];
+
+        if y_ >= x_ {
+            self.registers[0xF] = 1;
+        } else {
+            self.registers[0xF] = 0;
+        }
+
+        self.registers[x as usize] = y_.wrapping_sub(x_);
+    }
+
+    /// Set `Vx = Vx SHL 1`.
+    ///
+    /// If the most-significant bit of `Vx` is 1, then `VF` is set to 1, otherwise to 0. Then `Vx`
+    /// is multiplied by 2. With the `shift_uses_vy` quirk, `Vy` is shifted instead and the result
+    /// is stored in `Vx`.
+    fn shl_xy(&mut self, x: u8, y: u8) {
+        let src = if self.quirks.shift_uses_vy { y } else { x };
+        let val = self.registers[src as usize];
+
+        self.registers[x as usize] = val << 1;
+        self.registers[0xF] = val >> 7;
+    }
+
+    /// Set `I = nnn`.
+    ///
+    /// The value of register `I` is set to `nnn`.
+    fn ld_i(&mut self, addr: u16) {
+        self.index_register = addr;
+    }
+
+    /// Jump to location `nnn + V0`.
+    ///
+    /// The program counter is set to `nnn` plus the value of `V0`. With the `jump_with_vx` quirk,
+    /// the instruction is read as `BXNN` and `Vx` is used instead of `V0`.
+    fn jp_v0(&mut self, x: u8, addr: u16) {
+        let offset = if self.quirks.jump_with_vx {
+            self.registers[x as usize]
+        } else {
+            self.registers[0]
+        };
+
+        self.position_in_memory = addr as usize + offset as usize;
+    }
+
+    /// Set `Vx = random byte AND kk`.
+    ///
+    /// The interpreter generates a random number from 0 to 255, which is then ANDed with the value
+    /// `kk`. The results are stored in `Vx`.
+    fn rnd(&mut self, x: u8, kk: u8) {
+        self.registers[x as usize] = self.rng.next_u8() & kk;
+    }
+
+    /// Clear the display.
+    fn cls(&mut self) {
+        self.display.clear();
+        self.display_changed(DirtyRows::ALL);
+    }
+
+    /// Scroll the display down `n` pixels (SUPER-CHIP).
+    fn scd(&mut self, n: u8) {
+        self.display.scroll_down(n as usize);
+        self.display_changed(DirtyRows::ALL);
+    }
+
+    /// Scroll the display right 4 pixels (SUPER-CHIP).
+    fn scr(&mut self) {
+        self.display.scroll_right();
+        self.display_changed(DirtyRows::ALL);
+    }
+
+    /// Scroll the display left 4 pixels (SUPER-CHIP).
+    fn scl(&mut self) {
+        self.display.scroll_left();
+        self.display_changed(DirtyRows::ALL);
+    }
+
+    /// Switch between the lores and hires screens (SUPER-CHIP). What's already drawn stays put,
+    /// each lores pixel covering four hires ones.
+    fn set_hires(&mut self, hires: bool) {
+        if self.display.is_hires() != hires {
+            self.display.set_hires(hires);
+            self.display_changed(DirtyRows::ALL);
+        }
+    }
+
+    /// Display `n`-byte sprite starting at memory location `I` at `(Vx, Vy)`, set `VF = collision`.
+    ///
+    /// The interpreter reads `n` bytes from memory, starting at the address stored in `I`. These
+    /// bytes are then displayed as sprites on screen at coordinates `(Vx, Vy)`. Sprites are XORed
+    /// onto the existing screen. If this causes any pixels to be erased, `VF` is set to 1,
+    /// otherwise it is set to 0. If the sprite is positioned so part of it is outside the
+    /// coordinates of the display, it wraps around to the opposite side of the screen, or with the
+    /// `clip_sprites` quirk is cut off.
+    ///
+    /// SUPER-CHIP draws a 16x16 sprite from 32 bytes, two per row, when `n` is 0. In hires it
+    /// also sets `VF` to the number of sprite rows that erased a pixel, plus with `clip_sprites`
+    /// the number cut off the bottom, rather than just 1.
+    ///
+    /// With both of XO-CHIP's planes selected, the sprite for the second follows the first's, so
+    /// twice as many bytes are read.
+    fn drw(&mut self, x: u8, y: u8, n: u8, pc: usize) -> Result<(), Chip8Error> {
+        let x_ = self.registers[x as usize] as usize;
+        let y_ = self.registers[y as usize] as usize;
+        let (len, width) = if n == 0 { (32, 16) } else { (n as usize, 8) };
+        let planes = self.display.planes().count_ones() as usize;
+        let sprite = self
+            .memory
+            .read_slice(self.index_register as usize, len * planes)
+            .map_err(|err| Chip8Error::from_memory(err, pc))?;
+
+        let clip = self.quirks.clip_sprites;
+        let collisions = self.display.draw(x_, y_, sprite, width, clip);
+
+        // Blank sprite rows XOR nothing, so only the others change, and clipped rows aren't drawn.
+        let height = self.display.height();
+        let top = y_ % height;
+        let sprite_rows = len / (width / 8);
+        let drawn = if clip {
+            sprite_rows.min(height - top)
+        } else {
+            sprite_rows
+        };
+        let mut rows = DirtyRows::NONE;
+        for plane in sprite.chunks(len) {
+            for (dy, _) in plane
+                .chunks(width / 8)
+                .take(drawn)
+                .enumerate()
+                .filter(|(_, row)| row.iter().any(|&byte| byte != 0))
+            {
+                rows.insert((top + dy) % height);
+            }
+        }
+
+        self.display_changed(rows);
+
+        self.registers[0xF] = if self.display.is_hires() {
+            (collisions + sprite_rows - drawn) as u8
+        } else {
+            (collisions > 0) as u8
+        };
+
+        Ok(())
+    }
+
+    /// Skip next instruction if key with the value of `Vx` is pressed.
+    ///
+    /// Checks the keyboard, and if the key corresponding to the value of `Vx` is currently in the
+    /// down position, PC is increased by 2.
+    fn skp(&mut self, x: u8) {
+        self.polling |= 1 << (self.registers[x as usize] & 0xF);
+        if self.keypad.is_down(self.registers[x as usize]) {
+            self.skip_next_instruction();
+        }
+    }
+
+    /// Skip next instruction if key with the value of `Vx` is not pressed.
+    ///
+    /// Checks the keyboard, and if the key corresponding to the value of `Vx` is currently in the
+    /// up position, PC is increased by 2.
+    fn sknp(&mut self, x: u8) {
+        self.polling |= 1 << (self.registers[x as usize] & 0xF);
+        if !self.keypad.is_down(self.registers[x as usize]) {
+            self.skip_next_instruction();
+        }
+    }
+
+    /// Set `Vx = delay timer value`.
+    ///
+    /// The value of `DT` is placed into `Vx`.
+    fn ld_vx_dt(&mut self, x: u8) {
+        self.registers[x as usize] = self.timers.delay;
+    }
+
+    /// Wait for a key press, store the value of the key in `Vx`.
+    ///
+    /// All execution stops until a key is pressed, then the value of that key is stored in `Vx`.
+    /// Rather than spinning, `run` returns and the key is delivered later via `press_key`, or
+    /// via `release_key` with the `wait_for_release` quirk.
+    fn ld_vx_k(&mut self, x: u8) {
+        self.waiting_for_key = Some(x);
+        self.hooks.key_wait(x);
+        self.events.push(EmulatorEvent::WaitingForKey { x });
+    }
+
+    /// Set `delay timer = Vx`.
+    ///
+    /// `DT` is set equal to the value of `Vx`.
+    fn ld_dt_vx(&mut self, x: u8) {
+        self.timers.delay = self.registers[x as usize];
+    }
+
+    /// Set `sound timer = Vx`.
+    ///
+    /// `ST` is set equal to the value of `Vx`.
+    fn ld_st_vx(&mut self, x: u8) {
+        let sounding = self.timers.sound > 0;
+        self.timers.sound = self.registers[x as usize];
+
+        if !sounding && self.timers.sound > 0 {
+            self.sound_started();
+        } else if sounding && self.timers.sound == 0 {
+            self.sound_stopped();
+        }
+    }
+
+    /// Set `I = I + Vx`.
+    ///
+    /// The values of `I` and `Vx` are added, and the results are stored in `I`. With the
+    /// `add_i_sets_vf` quirk, `VF` is set to 1 if the result is past the end of memory, otherwise
+    /// 0. Returns whether `I` silently left memory.
+    fn add_i(&mut self, x: u8) -> bool {
+        let sum = self.index_register as usize + self.registers[x as usize] as usize;
+        self.index_register = sum as u16;
+        let outside = sum >= self.memory.len();
+
+        if self.quirks.add_i_sets_vf {
+            if outside {
+                self.registers[0xF] = 1;
+            } else {
+                self.registers[0xF] = 0;
+            }
+
+            false
+        } else {
+            outside
+        }
+    }
+
+    /// Set `I = location of sprite for digit Vx`.
+    ///
+    /// The value of `I` is set to the location for the hexadecimal sprite corresponding to the
+    /// value of `Vx`.
+    fn ld_f(&mut self, x: u8) {
+        let digit = (self.registers[x as usize] & 0xF) as usize;
+
+        self.index_register = (FONT_ADDR + digit * FONT_GLYPH_SIZE) as u16;
+    }
+
+    /// Store BCD representation of `Vx` in memory locations `I`, `I+1`, and `I+2`.
+    ///
+    /// The interpreter takes the decimal value of `Vx`, and places the hundreds digit in memory at
+    /// location in `I`, the tens digit at location `I+1`, and the ones digit at location `I+2`.
+    fn ld_b(&mut self, x: u8, pc: usize) -> Result<(), Chip8Error> {
+        let val = self.registers[x as usize];
+        let digits = [val / 100, (val / 10) % 10, val % 10];
+
+        self.memory
+            .write_slice(self.index_register as usize, &digits)
+            .map_err(|err| Chip8Error::from_memory(err, pc))
+    }
+
+    /// Load the 16 bytes at `I` as the audio pattern (XO-CHIP).
+    ///
+    /// The pattern's 128 bits are played in place of the usual tone while the sound timer runs,
+    /// at the rate set by `FX3A`.
+    fn ld_audio(&mut self, pc: usize) -> Result<(), Chip8Error> {
+        let bits = self
+            .memory
+            .read_slice(self.index_register as usize, 16)
+            .map_err(|err| Chip8Error::from_memory(err, pc))?;
+
+        let mut pattern = [0; 16];
+        pattern.copy_from_slice(bits);
+        self.audio_pattern = Some(pattern);
+        self.pattern_changed = true;
+
+        Ok(())
+    }
+
+    /// Set the audio pattern's pitch to `Vx` (XO-CHIP).
+    fn ld_pitch(&mut self, x: u8) {
+        self.pitch = self.registers[x as usize];
+        self.pattern_changed = true;
+    }
+
+    /// Store registers `V0` through `Vx` in memory starting at location `I`.
+    ///
+    /// The interpreter copies the values of registers `V0` through `Vx` into memory, starting at
+    /// the address in `I`.
+    fn ld_i_vx(&mut self, x: u8, pc: usize) -> Result<(), Chip8Error> {
+        let count = x as usize + 1;
+
+        self.memory
+            .write_slice(self.index_register as usize, &self.registers[..count])
+            .map_err(|err| Chip8Error::from_memory(err, pc))?;
+
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.index_register.wrapping_add(count as u16);
+        }
+
+        Ok(())
+    }
+
+    /// Read registers `V0` through `Vx` from memory starting at location `I`.
+    ///
+    /// The interpreter reads values from memory starting at location `I` into registers `V0`
+    /// through `Vx`.
+    fn ld_vx_i(&mut self, x: u8, pc: usize) -> Result<(), Chip8Error> {
+        let count = x as usize + 1;
+        let values = self
+            .memory
+            .read_slice(self.index_register as usize, count)
+            .map_err(|err| Chip8Error::from_memory(err, pc))?;
+
+        self.registers[..count].copy_from_slice(values);
+
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.index_register.wrapping_add(count as u16);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::Chip8;
+
+    fn cpu(rom: &[u8]) -> Chip8 {
+        Chip8::builder().rom(rom).build().unwrap()
+    }
+
+    /// A machine with `quirks` that has run the first `steps` instructions of `rom`.
+    fn ran(quirks: Quirks, rom: &[u8], steps: usize) -> Chip8 {
+        let mut cpu = Chip8::builder().rom(rom).quirks(quirks).build().unwrap();
+        for _ in 0..steps {
+            cpu.step().unwrap();
+        }
+        cpu
+    }
+
+    #[test]
+    fn drw_sets_vf_when_it_erases_a_pixel() {
+        #[rustfmt::skip]
+        let rom = [
+            0xF0, 0x29, // LD F, V0
+            0xD0, 0x05, // DRW V0, V0, 5
+            0xD0, 0x05, // DRW V0, V0, 5
+        ];
+
+        let cpu = ran(Quirks::default(), &rom, 2);
+        assert_eq!(cpu.registers()[0xF], 0);
+        assert!((0..4).all(|x| cpu.display().pixel(x, 0)));
+
+        let cpu = ran(Quirks::default(), &rom, 3);
+        assert_eq!(cpu.registers()[0xF], 1);
+        assert!(!(0..8).any(|x| cpu.display().pixel(x, 0)));
+    }
+
+    #[test]
+    fn drw_wraps_or_clips_with_the_quirk() {
+        #[rustfmt::skip]
+        let rom = [
+            0x61, 0x3E, // LD V1, 62
+            0xF0, 0x29, // LD F, V0
+            0xD1, 0x05, // DRW V1, V0, 5
+        ];
+        let top_row =
+            |cpu: &Chip8| -> [bool; 4] { [62, 63, 0, 1].map(|x| cpu.display().pixel(x, 0)) };
+
+        let wrapped = ran(Quirks::default(), &rom, 3);
+        assert_eq!(top_row(&wrapped), [true; 4]);
+
+        let clip = Quirks {
+            clip_sprites: true,
+            ..Quirks::default()
+        };
+        let clipped = ran(clip, &rom, 3);
+        assert_eq!(top_row(&clipped), [true, true, false, false]);
+    }
+
+    #[test]
+    fn run_ends_the_frame_while_waiting_for_a_key() {
+        #[rustfmt::skip]
+        let mut cpu = cpu(&[
+            0x65, 0x05, // LD V5, 5
+            0xF5, 0x15, // LD DT, V5
+            0xF0, 0x0A, // LD V0, K
+        ]);
+
+        assert_eq!(cpu.run().unwrap(), RunExit::WaitingForKey { x: 0 });
+        assert_eq!(cpu.timers().delay, 4);
+        assert_eq!(cpu.frame(), 1);
+
+        assert_eq!(cpu.run().unwrap(), RunExit::WaitingForKey { x: 0 });
+        assert_eq!(cpu.timers().delay, 3);
+        assert_eq!(cpu.frame(), 2);
+    }
+
+    #[test]
+    fn queued_input_for_a_later_frame_ends_the_wait() {
+        let mut cpu = cpu(&[0xF3, 0x0A, 0x12, 0x02]);
+        cpu.queue_input(InputEvent::press(7, 3));
+
+        for _ in 0..3 {
+            assert_eq!(cpu.run().unwrap(), RunExit::WaitingForKey { x: 3 });
+        }
+        assert_eq!(cpu.run().unwrap(), RunExit::FrameComplete { drew: false });
+        assert_eq!(cpu.registers()[3], 7);
+    }
+
+    #[test]
+    fn load_rom_at_rejects_addresses_past_the_end_of_memory() {
+        let mut cpu = cpu(&[]);
+        let len = cpu.memory().len();
+
+        assert!(matches!(
+            cpu.load_rom_at(len + 1, &[]),
+            Err(Chip8Error::LoadAddressOutOfBounds { addr, .. }) if addr == len + 1
+        ));
+        assert!(matches!(
+            cpu.load_rom_at(len - 1, &[0x12, 0x00]),
+            Err(Chip8Error::RomTooLarge { size: 2, max: 1 })
+        ));
+        cpu.load_rom_at(len, &[]).unwrap();
+        cpu.load_rom_at(len - 2, &[0x12, 0x00]).unwrap();
+    }
+
+    #[test]
+    fn load_store_increment_wraps_i_at_the_top_of_64k() {
+        let mut cpu = Chip8::builder()
+            .rom(&[0xFF, 0x55, 0xFF, 0x65]) // LD [I], VF; LD VF, [I]
+            .quirks(Quirks::VIP)
+            .build_sized::<0x10000, DEFAULT_STACK_DEPTH>()
+            .unwrap();
+
+        cpu.set_i(0xFFF0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.i(), 0);
+
+        cpu.set_i(0xFFF0);
+        cpu.step().unwrap();
+        assert_eq!(cpu.i(), 0);
+    }
+
+    #[test]
+    fn skp_and_sknp_follow_the_key_in_vx() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // LD V0, 5
+            0xE0, 0x9E, // SKP V0
+            0x61, 0x01, // LD V1, 1
+            0xE0, 0xA1, // SKNP V0
+            0x62, 0x01, // LD V2, 1
+        ];
+
+        let mut pressed = cpu(&rom);
+        pressed.press_key(5);
+        pressed.run_cycles(4).unwrap();
+        assert_eq!(pressed.registers()[1..3], [0, 1]);
+
+        let mut released = cpu(&rom);
+        released.press_key(4);
+        released.run_cycles(4).unwrap();
+        assert_eq!(released.registers()[1..3], [1, 0]);
+    }
+
+    #[test]
+    fn ld_vx_k_waits_until_a_key_is_pressed() {
+        #[rustfmt::skip]
+        let mut cpu = cpu(&[
+            0xF3, 0x0A, // LD V3, K
+            0x64, 0x01, // LD V4, 1
+        ]);
+
+        assert_eq!(cpu.step().unwrap(), StepOutcome::WaitingForKey);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::WaitingForKey);
+        assert_eq!(cpu.waiting_for_key(), Some(3));
+        assert_eq!(cpu.pc(), 0x202);
+
+        cpu.press_key(0xB);
+        assert_eq!(cpu.waiting_for_key(), None);
+        assert_eq!(cpu.registers()[3], 0xB);
+        assert_eq!(cpu.step().unwrap(), StepOutcome::Executed);
+        assert_eq!(cpu.registers()[4], 1);
+    }
+
+    #[test]
+    fn ld_vx_k_waits_for_the_release_with_the_quirk() {
+        let quirks = Quirks {
+            wait_for_release: true,
+            ..Quirks::default()
+        };
+        let mut cpu = ran(quirks, &[0xF3, 0x0A], 1);
+
+        cpu.press_key(0xB);
+        assert_eq!(cpu.waiting_for_key(), Some(3));
+        cpu.release_key(0xB);
+        assert_eq!(cpu.waiting_for_key(), None);
+        assert_eq!(cpu.registers()[3], 0xB);
+    }
+
+    #[test]
+    fn rnd_masks_the_rngs_byte() {
+        struct Fixed;
+        impl Rng for Fixed {
+            fn next_u8(&mut self) -> u8 {
+                0xAB
+            }
+        }
+
+        let mut cpu = Chip8::builder()
+            .rom(&[0xC0, 0x0F, 0xC1, 0xF0]) // RND V0, 0x0F; RND V1, 0xF0
+            .rng(Fixed)
+            .build()
+            .unwrap();
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.registers()[..2], [0x0B, 0xA0]);
+    }
+
+    #[test]
+    fn rnd_repeats_for_the_same_seed() {
+        let rom = [0xC0, 0xFF, 0xC1, 0xFF, 0xC2, 0xFF, 0xC3, 0xFF];
+        let registers = |seed| {
+            let mut cpu = Chip8::builder().rom(&rom).seed(seed).build().unwrap();
+            cpu.run_cycles(4).unwrap();
+            *cpu.registers()
+        };
+
+        assert_eq!(registers(7), registers(7));
+        assert_ne!(registers(7), registers(8));
+    }
+
+    #[test]
+    fn ld_i_sets_the_index_register() {
+        let cpu = ran(Quirks::default(), &[0xA3, 0x45], 1);
+        assert_eq!(cpu.i(), 0x345);
+
+        let cpu = ran(Quirks::default(), &[0xA3, 0x45, 0xAF, 0xFF], 2);
+        assert_eq!(cpu.i(), 0xFFF);
+    }
+
+    #[test]
+    fn add_i_sets_vf_on_overflow_only_with_the_quirk() {
+        #[rustfmt::skip]
+        let rom = [
+            0x6F, 0x05, // LD VF, 5
+            0x60, 0x01, // LD V0, 1
+            0xAF, 0xFF, // LD I, 0xFFF
+            0xF0, 0x1E, // ADD I, V0
+        ];
+        let amiga = Quirks {
+            add_i_sets_vf: true,
+            ..Quirks::default()
+        };
+
+        let cpu = ran(Quirks::default(), &rom, 4);
+        assert_eq!((cpu.i(), cpu.registers()[0xF]), (0x1000, 5));
+
+        let cpu = ran(amiga, &rom, 4);
+        assert_eq!((cpu.i(), cpu.registers()[0xF]), (0x1000, 1));
+
+        let mut cpu = ran(amiga, &rom, 3);
+        cpu.set_i(0x100);
+        cpu.step().unwrap();
+        assert_eq!((cpu.i(), cpu.registers()[0xF]), (0x101, 0));
+    }
+
+    #[test]
+    fn ld_i_vx_and_ld_vx_i_move_registers_through_memory() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x01, // LD V0, 1
+            0x61, 0x02, // LD V1, 2
+            0x62, 0x03, // LD V2, 3
+            0xA3, 0x00, // LD I, 0x300
+            0xF1, 0x55, // LD [I], V1
+            0xA3, 0x01, // LD I, 0x301
+            0xF2, 0x65, // LD V2, [I]
+        ];
+        let increments = Quirks {
+            load_store_increments_i: true,
+            ..Quirks::default()
+        };
+
+        let cpu = ran(Quirks::default(), &rom, 5);
+        assert_eq!(cpu.memory().read_slice(0x300, 3).unwrap(), [1, 2, 0]);
+        assert_eq!(cpu.i(), 0x300);
+        let cpu = ran(increments, &rom, 5);
+        assert_eq!(cpu.i(), 0x302);
+
+        let cpu = ran(Quirks::default(), &rom, 7);
+        assert_eq!(cpu.registers()[..3], [2, 0, 0]);
+        assert_eq!(cpu.i(), 0x301);
+        let cpu = ran(increments, &rom, 7);
+        assert_eq!(cpu.i(), 0x304);
+    }
+
+    #[test]
+    fn ld_b_writes_the_decimal_digits() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0xEA, // LD V0, 234
+            0xA3, 0x00, // LD I, 0x300
+            0xF0, 0x33, // LD B, V0
+            0x60, 0x07, // LD V0, 7
+            0xF0, 0x33, // LD B, V0
+        ];
+
+        let cpu = ran(Quirks::default(), &rom, 3);
+        assert_eq!(cpu.memory().read_slice(0x300, 3).unwrap(), [2, 3, 4]);
+        let cpu = ran(Quirks::default(), &rom, 5);
+        assert_eq!(cpu.memory().read_slice(0x300, 3).unwrap(), [0, 0, 7]);
+        assert_eq!(cpu.i(), 0x300);
+    }
+
+    #[test]
+    fn ld_f_points_i_at_the_digits_glyph() {
+        let cpu = ran(Quirks::default(), &[0x60, 0x1A, 0xF0, 0x29], 2); // LD V0, 0x1A; LD F, V0
+        let glyph = FONT_ADDR + 0xA * FONT_GLYPH_SIZE;
+
+        assert_eq!(cpu.i() as usize, glyph);
+        assert_eq!(
+            cpu.memory().read_slice(glyph, FONT_GLYPH_SIZE).unwrap(),
+            [0xF0, 0x90, 0xF0, 0x90, 0x90]
+        );
+    }
+
+    #[test]
+    fn timers_are_set_read_and_ticked() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x03, // LD V0, 3
+            0xF0, 0x15, // LD DT, V0
+            0xF0, 0x18, // LD ST, V0
+            0xF1, 0x07, // LD V1, DT
+        ];
+        let mut cpu = ran(Quirks::default(), &rom, 3);
+        assert_eq!((cpu.timers().delay, cpu.timers().sound), (3, 3));
+
+        cpu.tick_timers();
+        cpu.step().unwrap();
+        assert_eq!(cpu.registers()[1], 2);
+
+        for _ in 0..5 {
+            cpu.tick_timers();
+        }
+        assert_eq!((cpu.timers().delay, cpu.timers().sound), (0, 0));
+    }
+
+    #[test]
+    fn shifts_use_vx_or_vy_with_the_quirk() {
+        let shift = |opcode: u8, shift_uses_vy| {
+            let quirks = Quirks {
+                shift_uses_vy,
+                ..Quirks::default()
+            };
+            // LD V0, 2; LD V1, 0x81; SHR or SHL V0, V1
+            let cpu = ran(quirks, &[0x60, 0x02, 0x61, 0x81, 0x80, opcode], 3);
+            (cpu.registers()[0], cpu.registers()[0xF])
+        };
+
+        assert_eq!(shift(0x16, false), (0x01, 0));
+        assert_eq!(shift(0x16, true), (0x40, 1));
+        assert_eq!(shift(0x1E, false), (0x04, 0));
+        assert_eq!(shift(0x1E, true), (0x02, 1));
+    }
+
+    #[test]
+    fn sub_flags_no_borrow_when_the_operands_are_equal() {
+        let sub = |vx: u8, vy: u8| {
+            // LD V0, vx; LD V1, vy; SUB V0, V1
+            let cpu = ran(Quirks::default(), &[0x60, vx, 0x61, vy, 0x80, 0x15], 3);
+            (cpu.registers()[0], cpu.registers()[0xF])
+        };
+
+        assert_eq!(sub(5, 3), (2, 1));
+        assert_eq!(sub(3, 5), (0xFE, 0));
+        assert_eq!(sub(4, 4), (0, 1));
+    }
+
+    #[test]
+    fn subn_subtracts_vx_from_vy() {
+        let subn = |vx: u8, vy: u8| {
+            // LD V0, vx; LD V1, vy; SUBN V0, V1
+            let cpu = ran(Quirks::default(), &[0x60, vx, 0x61, vy, 0x80, 0x17], 3);
+            (cpu.registers()[0], cpu.registers()[0xF])
+        };
+
+        assert_eq!(subn(3, 5), (2, 1));
+        assert_eq!(subn(5, 3), (0xFE, 0));
+        assert_eq!(subn(4, 4), (0, 1));
+    }
+
+    #[test]
+    fn sne_reg_skips_when_the_registers_differ() {
+        let skipped = |v1: u8| {
+            // LD V0, 1; LD V1, v1; SNE V0, V1; LD V2, 1
+            let cpu = ran(
+                Quirks::default(),
+                &[0x60, 0x01, 0x61, v1, 0x90, 0x10, 0x62, 0x01],
+                4,
+            );
+            cpu.registers()[2] == 0
+        };
+
+        assert!(skipped(2));
+        assert!(!skipped(1));
+    }
+
+    #[test]
+    fn jp_v0_offsets_by_v0_or_vx_with_the_quirk() {
+        let target = |jump_with_vx| {
+            // LD V0, 2; LD V3, 4; JP V0, 0x300
+            let rom = [0x60, 0x02, 0x63, 0x04, 0xB3, 0x00];
+            let quirks = Quirks {
+                jump_with_vx,
+                ..Quirks::default()
+            };
+            ran(quirks, &rom, 3).pc()
+        };
+
+        assert_eq!(target(false), 0x302);
+        assert_eq!(target(true), 0x304);
+    }
+
+    #[test]
+    fn unknown_opcodes_stop_the_cpu_unless_ignored() {
+        // LD V0, 1; DW #800F; LD V1, 2
+        let rom = [0x60, 0x01, 0x80, 0x0F, 0x61, 0x02];
+
+        let mut strict = cpu(&rom);
+        strict.step().unwrap();
+        assert!(matches!(
+            strict.step(),
+            Err(Chip8Error::UnknownOpcode {
+                opcode: 0x800F,
+                pc: 0x202
+            })
+        ));
+
+        let mut lenient = cpu(&rom);
+        lenient.set_ignore_unknown_opcodes(true);
+        lenient.run_cycles(3).unwrap();
+        assert_eq!(lenient.registers()[..2], [1, 2]);
+    }
+
+    #[test]
+    fn add_byte_wraps_without_touching_vf() {
+        // LD VF, 5; LD V0, 0xFF; ADD V0, 2
+        let rom = [0x6F, 0x05, 0x60, 0xFF, 0x70, 0x02];
+        let cpu = ran(Quirks::default(), &rom, 3);
+        assert_eq!((cpu.registers()[0], cpu.registers()[0xF]), (1, 5));
+    }
+
+    #[test]
+    fn trap_overflow_stops_on_add_byte_and_add_i() {
+        // LD V0, 0xFF; ADD V0, 2
+        let mut add = cpu(&[0x60, 0xFF, 0x70, 0x02]);
+        add.set_trap_overflow(true);
+        add.step().unwrap();
+        assert!(matches!(
+            add.step(),
+            Err(Chip8Error::Overflow {
+                instruction: Instruction::AddByte { x: 0, kk: 2 },
+                pc: 0x202
+            })
+        ));
+
+        // LD V0, 1; LD I, 0xFFF; ADD I, V0
+        let mut add_i = cpu(&[0x60, 0x01, 0xAF, 0xFF, 0xF0, 0x1E]);
+        add_i.set_trap_overflow(true);
+        add_i.run_cycles(2).unwrap();
+        assert!(matches!(
+            add_i.step(),
+            Err(Chip8Error::Overflow {
+                instruction: Instruction::AddI { x: 0 },
+                pc: 0x204
+            })
+        ));
+    }
+
+    #[test]
+    fn add_i_overflows_at_the_end_of_memory() {
+        let amiga = Quirks {
+            add_i_sets_vf: true,
+            ..Quirks::default()
+        };
+        // LD V0, 1; ADD I, V0
+        let mut cpu = Chip8::builder()
+            .rom(&[0x60, 0x01, 0xF0, 0x1E, 0xF0, 0x1E])
+            .quirks(amiga)
+            .build_sized::<0x10000, DEFAULT_STACK_DEPTH>()
+            .unwrap();
+        cpu.step().unwrap();
+
+        cpu.set_i(0xFFF);
+        cpu.step().unwrap();
+        assert_eq!((cpu.i(), cpu.registers()[0xF]), (0x1000, 0));
+
+        cpu.set_i(0xFFFF);
+        cpu.step().unwrap();
+        assert_eq!((cpu.i(), cpu.registers()[0xF]), (0, 1));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn sys_calls_the_registered_routine() {
+        // SYS 0x123; SYS 0x123; SYS 0x456
+        let mut cpu = cpu(&[0x01, 0x23, 0x01, 0x23, 0x04, 0x56]);
+        cpu.register_routine(0x123, |cpu| cpu.set_i(cpu.i() + 1));
+
+        cpu.run_cycles(2).unwrap();
+        assert_eq!(cpu.i(), 2);
+        assert!(matches!(
+            cpu.step(),
+            Err(Chip8Error::UnknownOpcode {
+                opcode: 0x456,
+                pc: 0x204
+            })
+        ));
+    }
+
+    #[test]
+    fn frames_spread_a_fractional_clock_over_60hz() {
+        // ADD V0, 1; JP 0x200
+        let rom = [0x70, 0x01, 0x12, 0x00];
+
+        let mut framed = cpu(&rom);
+        framed.set_clock_hz(90);
+        let cycles: [usize; 4] = core::array::from_fn(|_| framed.frame_cycles());
+        assert_eq!(cycles, [1, 2, 1, 2]);
+
+        framed.set_clock_hz(90);
+        for _ in 0..60 {
+            framed.run_frame().unwrap();
+        }
+        assert_eq!(framed.frame(), 60);
+        assert_eq!(framed.registers()[0], 45);
+
+        let mut clocked = cpu(&rom);
+        clocked.set_clock_hz(90);
+        for _ in 0..90 {
+            clocked.step_clocked().unwrap();
+        }
+        assert_eq!(clocked.frame(), 60);
+        assert_eq!(clocked.registers()[0], 45);
+    }
+
+    #[test]
+    fn queued_input_lands_at_the_start_of_its_frame() {
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // LD V0, 5
+            0xE0, 0x9E, // SKP V0
+            0x12, 0x02, // JP 0x202
+            0x61, 0x01, // LD V1, 1
+            0x12, 0x08, // JP 0x208
+        ];
+        let mut cpu = cpu(&rom);
+        cpu.queue_input(InputEvent::press(5, 2));
+        cpu.queue_input(InputEvent::release(5, 2));
+        cpu.queue_input(InputEvent::press(7, 1));
+
+        cpu.run_frame().unwrap();
+        assert!(!cpu.keypad().is_down(7));
+        cpu.run_frame().unwrap();
+        assert!(cpu.keypad().is_down(7));
+        assert_eq!(cpu.registers()[1], 0);
+
+        // The tap is released at the end of frame 2, but `SKP` still saw it.
+        cpu.run_frame().unwrap();
+        assert!(!cpu.keypad().is_down(5));
+        assert_eq!(cpu.registers()[1], 1);
+    }
+
+    #[test]
+    fn reset_returns_to_the_power_on_state() {
+        #[rustfmt::skip]
+        let rom = [
+            0xC0, 0xFF, // RND V0, 0xFF
+            0xA2, 0x0A, // LD I, 0x20A
+            0xD0, 0x01, // DRW V0, V0, 1
+            0x71, 0x01, // ADD V1, 1
+            0x12, 0x00, // JP 0x200
+            0x80,
+        ];
+        let build = || {
+            let mut cpu = Chip8::builder().rom(&rom).seed(5).build().unwrap();
+            cpu.set_clock_hz(90);
+            cpu
+        };
+        let run = |cpu: &mut Chip8| {
+            for _ in 0..7 {
+                cpu.run_frame().unwrap();
+            }
+            cpu.state_hash()
+        };
+
+        let mut fresh = build();
+        let mut reset = build();
+        run(&mut reset);
+        reset.reset();
+
+        assert_eq!(reset.state_hash(), fresh.state_hash());
+        assert_eq!(run(&mut reset), run(&mut fresh));
+    }
+
+    #[test]
+    fn load_state_keeps_the_stack_policy() {
+        // CALL 0x200, forever.
+        let rom = [0x22, 0x00];
+        let mut wrapping = cpu(&rom);
+        wrapping.set_stack_policy(StackPolicy::Wrap);
+        wrapping.run_cycles(DEFAULT_STACK_DEPTH + 3).unwrap();
+
+        let mut strict = cpu(&rom);
+        strict.load_state(&wrapping.save_state());
+        assert_eq!(strict.stack_frames().count(), DEFAULT_STACK_DEPTH);
+        assert!(matches!(
+            strict.step(),
+            Err(Chip8Error::StackOverflow { pc: 0x200 })
+        ));
+    }
+
+    #[test]
+    fn call_overflows_at_full_depth_and_ret_underflows() {
+        // CALL 0x200, forever.
+        let mut calls = Chip8::builder()
+            .rom(&[0x22, 0x00])
+            .build_sized::<DEFAULT_MEMORY_SIZE, 4>()
+            .unwrap();
+        calls.run_cycles(4).unwrap();
+        assert!(calls.stack_frames().eq([0x202; 4]));
+        assert!(matches!(
+            calls.step(),
+            Err(Chip8Error::StackOverflow { pc: 0x200 })
+        ));
+
+        // CALL 0x204; RET on the way back.
+        let mut returns = cpu(&[0x22, 0x04, 0x00, 0xEE, 0x00, 0xEE]);
+        returns.run_cycles(2).unwrap();
+        assert_eq!(returns.pc(), 0x202);
+        assert!(matches!(
+            returns.step(),
+            Err(Chip8Error::StackUnderflow { pc: 0x202 })
+        ));
+    }
+
+    #[test]
+    fn stores_into_protected_memory_trap() {
+        let protected = |rom: &[u8], steps| {
+            let mut cpu = Chip8::builder()
+                .rom(rom)
+                .write_protect(true)
+                .build()
+                .unwrap();
+            cpu.run_cycles(steps).unwrap();
+            cpu.step()
+        };
+
+        // LD I, 0x1FF; LD [I], V1
+        assert!(matches!(
+            protected(&[0xA1, 0xFF, 0xF1, 0x55], 1),
+            Err(Chip8Error::WriteProtected {
+                addr: 0x1FF,
+                pc: 0x202
+            })
+        ));
+        // LD I, FONT_ADDR; LD B, V0
+        assert!(matches!(
+            protected(&[0xA0, 0x50, 0xF0, 0x33], 1),
+            Err(Chip8Error::WriteProtected {
+                addr: FONT_ADDR,
+                pc: 0x202
+            })
+        ));
+        // LD I, 0x300; LD [I], V1 is fine.
+        assert!(protected(&[0xA3, 0x00, 0xF1, 0x55], 1).is_ok());
+    }
+
+    #[test]
+    fn loads_past_the_end_of_memory_are_errors() {
+        // LD I, 0xFFE; LD V3, [I]
+        let mut cpu = cpu(&[0xAF, 0xFE, 0xF3, 0x65]);
+        cpu.step().unwrap();
+        assert!(matches!(
+            cpu.step(),
+            Err(Chip8Error::MemoryOutOfBounds {
+                addr: 0x1000,
+                pc: 0x202
+            })
+        ));
+    }
+
+    /// `code`, 12 bytes of it, followed by a solid 16x16 sprite at 0x20C.
+    fn with_big_sprite(code: [u8; 12]) -> [u8; 44] {
+        let mut rom = [0xFF; 44];
+        rom[..12].copy_from_slice(&code);
+        rom
+    }
+
+    #[test]
+    fn drw_draws_16x16_sprites_in_both_resolutions() {
+        for hires in [true, false] {
+            #[rustfmt::skip]
+            let rom = with_big_sprite([
+                0x00, if hires { 0xFF } else { 0xFE }, // HIGH or LOW
+                0x61, 0x02, // LD V1, 2
+                0xA2, 0x0C, // LD I, 0x20C
+                0xD1, 0x10, // DRW V1, V1, 0
+                0xD1, 0x10, // DRW V1, V1, 0
+                0x12, 0x0A, // JP 0x20A
+            ]);
+
+            let cpu = ran(Quirks::default(), &rom, 4);
+            let display = cpu.display();
+            assert!((2..18).all(|x| (2..18).all(|y| display.pixel(x, y))));
+            assert!(!display.pixel(18, 2) && !display.pixel(2, 18));
+            assert_eq!(cpu.registers()[0xF], 0);
+
+            // Every row collides, which hires counts.
+            let cpu = ran(Quirks::default(), &rom, 5);
+            assert!(!(2..18).any(|y| cpu.display().pixel(2, y)));
+            assert_eq!(cpu.registers()[0xF], if hires { 16 } else { 1 });
+        }
+    }
+
+    #[test]
+    fn drw_counts_clipped_rows_in_hires() {
+        #[rustfmt::skip]
+        let rom = with_big_sprite([
+            0x00, 0xFF, // HIGH
+            0x61, 0x3C, // LD V1, 60
+            0xA2, 0x0C, // LD I, 0x20C
+            0xD0, 0x10, // DRW V0, V1, 0
+            0xD0, 0x10, // DRW V0, V1, 0
+            0x12, 0x0A, // JP 0x20A
+        ]);
+        let clip = Quirks {
+            clip_sprites: true,
+            ..Quirks::default()
+        };
+
+        // 4 rows fit above the bottom and 12 are cut off it.
+        let clipped = ran(clip, &rom, 4);
+        assert!((60..64).all(|y| clipped.display().pixel(0, y)));
+        assert!(!clipped.display().pixel(0, 0));
+        assert_eq!(clipped.registers()[0xF], 12);
+        assert_eq!(ran(clip, &rom, 5).registers()[0xF], 4 + 12);
+
+        // Wrapped, the last 12 rows land at the top instead.
+        let wrapped = ran(Quirks::default(), &rom, 4);
+        assert!((0..12).all(|y| wrapped.display().pixel(0, y)));
+        assert!(!wrapped.display().pixel(0, 12));
+        assert_eq!(wrapped.registers()[0xF], 0);
+        assert_eq!(ran(Quirks::default(), &rom, 5).registers()[0xF], 16);
+    }
+
+    #[test]
+    fn scrolls_move_the_display_in_either_resolution() {
+        for hires in [true, false] {
+            #[rustfmt::skip]
+            let rom = [
+                0x00, if hires { 0xFF } else { 0xFE }, // HIGH or LOW
+                0x61, 0x08, // LD V1, 8
+                0xA0, 0x50, // LD I, FONT_ADDR
+                0xD1, 0x15, // DRW V1, V1, 5
+                0x00, 0xC2, // SCD 2
+                0x00, 0xFB, // SCR
+                0x00, 0xFC, // SCL
+                0x00, 0xFC, // SCL
+                0x00, 0xFF, // HIGH
+            ];
+            // The top left corner of the 0 glyph, whose top row is 4 pixels wide.
+            let corner = |cpu: &Chip8, x: usize, y: usize| {
+                let display = cpu.display();
+                display.pixel(x, y)
+                    && display.pixel(x + 3, y)
+                    && !display.pixel(x + 4, y)
+                    && !display.pixel(x - 1, y)
+                    && !display.pixel(x, y - 1)
+            };
+
+            let mut cpu = ran(Quirks::default(), &rom, 4);
+            assert!(corner(&cpu, 8, 8));
+            for (x, y) in [(8, 10), (12, 10), (8, 10), (4, 10)] {
+                cpu.mark_display_clean();
+                cpu.step().unwrap();
+                assert!(corner(&cpu, x, y), "{:?}", (x, y));
+                assert_eq!(cpu.dirty_rows(), DirtyRows::ALL);
+            }
+
+            // Lores scrolled whole lores pixels, twice as far as the same scroll in hires.
+            cpu.step().unwrap();
+            let (x, y) = if hires { (4, 10) } else { (8, 20) };
+            let display = cpu.display();
+            assert!(display.pixel(x, y) && !display.pixel(x - 1, y) && !display.pixel(x, y - 1));
+        }
+    }
+
+    #[test]
+    fn planes_are_drawn_and_cleared_separately() {
+        #[rustfmt::skip]
+        let code = [
+            0xF2, 0x01, // PLANE 2
+            0xA2, 0x14, // LD I, 0x214
+            0xD0, 0x01, // DRW V0, V0, 1
+            0xF3, 0x01, // PLANE 3
+            0x61, 0x04, // LD V1, 4
+            0xD0, 0x11, // DRW V0, V1, 1, reading a byte for each plane
+            0xF1, 0x01, // PLANE 1
+            0x00, 0xE0, // CLS
+            0x12, 0x10, // JP 0x210
+            0x00, 0x00,
+            0xF0, 0x0F, // Sprites at 0x214
+        ];
+        let colors = |cpu: &Chip8, y: usize| -> [u8; 8] {
+            core::array::from_fn(|x| cpu.display().color(x, y))
+        };
+
+        let cpu = ran(Quirks::default(), &code, 3);
+        assert_eq!(colors(&cpu, 0), [2, 2, 2, 2, 0, 0, 0, 0]);
+        assert_eq!(cpu.registers()[0xF], 0);
+
+        let cpu = ran(Quirks::default(), &code, 6);
+        assert_eq!(colors(&cpu, 0), [2, 2, 2, 2, 0, 0, 0, 0]);
+        assert_eq!(colors(&cpu, 4), [1, 1, 1, 1, 2, 2, 2, 2]);
+
+        let cpu = ran(Quirks::default(), &code, 8);
+        assert_eq!(colors(&cpu, 0), [2, 2, 2, 2, 0, 0, 0, 0]);
+        assert_eq!(colors(&cpu, 4), [0, 0, 0, 0, 2, 2, 2, 2]);
+    }
+}
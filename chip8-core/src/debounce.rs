@@ -0,0 +1,179 @@
+use crate::peripherals::KeyInput;
+
+/// How `Debouncer` filters a keypad. Each setting counts reads of the key, which `run_frame_with`
+/// does once per frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DebounceConfig {
+    /// How many reads in a row a key has to agree on before a press or release counts, so that
+    /// contact bounce shorter than that is ignored. 0 and 1 both take every change at once.
+    pub samples: u8,
+    /// How many reads after a key is released before it can be pressed again, for keypads that
+    /// chatter as a key comes up and would otherwise repeat it.
+    pub lockout: u8,
+}
+
+impl Default for DebounceConfig {
+    /// A change has to hold for two frames, with no lockout.
+    fn default() -> Self {
+        DebounceConfig {
+            samples: 2,
+            lockout: 0,
+        }
+    }
+}
+
+/// Wraps a `KeyInput`, e.g. the GPIO matrix of a hardware keypad, and filters out contact bounce
+/// and repeated presses as `config` says. Every key is filtered on its own, so holding several
+/// keys together works as well as holding one.
+#[derive(Debug, Clone)]
+pub struct Debouncer<K> {
+    keys: K,
+    config: DebounceConfig,
+    /// The filtered state, one bit per key.
+    held: u16,
+    /// How many reads in a row each key has differed from `held`.
+    streak: [u8; 16],
+    /// How many more reads each key's presses are ignored for.
+    lockout: [u8; 16],
+}
+
+impl<K: KeyInput> Debouncer<K> {
+    /// Filter `keys`, starting with every key up.
+    pub fn new(keys: K, config: DebounceConfig) -> Self {
+        Debouncer {
+            keys,
+            config,
+            held: 0,
+            streak: [0; 16],
+            lockout: [0; 16],
+        }
+    }
+
+    pub fn config(&self) -> DebounceConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: DebounceConfig) {
+        self.config = config;
+    }
+
+    /// The wrapped input.
+    pub fn inner(&mut self) -> &mut K {
+        &mut self.keys
+    }
+
+    pub fn into_inner(self) -> K {
+        self.keys
+    }
+}
+
+impl<K: KeyInput> KeyInput for Debouncer<K> {
+    fn is_down(&mut self, key: u8) -> bool {
+        let i = (key & 0xF) as usize;
+        let bit = 1 << i;
+        let held = self.held & bit != 0;
+        let mut down = self.keys.is_down(key);
+
+        if self.lockout[i] > 0 {
+            self.lockout[i] -= 1;
+            if !held {
+                down = false;
+            }
+        }
+
+        if down == held {
+            self.streak[i] = 0;
+            return held;
+        }
+
+        self.streak[i] = self.streak[i].saturating_add(1);
+        if self.streak[i] < self.config.samples {
+            return held;
+        }
+
+        self.streak[i] = 0;
+        self.held ^= bit;
+        if !down {
+            self.lockout[i] = self.config.lockout;
+        }
+
+        down
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Key 0 goes up and down as scripted, one step per read; every other key stays up.
+    struct Script {
+        steps: &'static [bool],
+        next: usize,
+    }
+
+    impl KeyInput for Script {
+        fn is_down(&mut self, key: u8) -> bool {
+            if key != 0 {
+                return false;
+            }
+            let down = self.steps[self.next];
+            self.next += 1;
+            down
+        }
+    }
+
+    fn debounced<const N: usize>(samples: u8, lockout: u8, steps: &'static [bool; N]) -> [bool; N] {
+        let config = DebounceConfig { samples, lockout };
+        let mut keys = Debouncer::new(Script { steps, next: 0 }, config);
+
+        let mut reads = [false; N];
+        for read in &mut reads {
+            *read = keys.is_down(0);
+        }
+        reads
+    }
+
+    const T: bool = true;
+    const F: bool = false;
+
+    #[test]
+    fn ignores_presses_shorter_than_samples() {
+        assert_eq!(debounced(3, 0, &[T, T, F, F, T, F]), [F; 6]);
+    }
+
+    #[test]
+    fn reports_a_held_key_after_samples_reads() {
+        assert_eq!(debounced(3, 0, &[T, T, T, T]), [F, F, T, T]);
+        // Releases are filtered the same way.
+        assert_eq!(debounced(2, 0, &[T, T, F, T, F, F]), [F, T, T, T, T, F]);
+    }
+
+    #[test]
+    fn lockout_suppresses_presses_after_a_release() {
+        assert_eq!(debounced(1, 3, &[T, F, T, T, T, T]), [T, F, F, F, F, T]);
+    }
+
+    #[test]
+    fn zero_or_one_sample_passes_changes_through() {
+        const STEPS: [bool; 6] = [T, F, T, T, F, T];
+        assert_eq!(debounced(0, 0, &STEPS), STEPS);
+        assert_eq!(debounced(1, 0, &STEPS), STEPS);
+    }
+
+    #[test]
+    fn filters_each_key_on_its_own() {
+        let config = DebounceConfig::default();
+        let mut keys = Debouncer::new(
+            Script {
+                steps: &[T, T],
+                next: 0,
+            },
+            config,
+        );
+
+        assert!(!keys.is_down(0));
+        assert!(!keys.is_down(1));
+        assert!(keys.is_down(0));
+        assert!(!keys.is_down(1));
+    }
+}
@@ -0,0 +1,558 @@
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+use core::ops::Range;
+
+/// The width of the original CHIP-8 screen, which SUPER-CHIP calls lores.
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+/// The width of SUPER-CHIP's hires screen, switched to with `00FF`.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+/// How many bit planes XO-CHIP draws to.
+pub const PLANES: usize = 2;
+
+/// The screen: 64x32 normally, or 128x64 in SUPER-CHIP's hires mode, with two bit planes for
+/// XO-CHIP's four colours.
+///
+/// Either way it's stored as a 128x64 canvas, one `u128` per row and plane with the leftmost
+/// pixel in the high bit. In lores each pixel covers a 2x2 block of the canvas, as on the HP48, so
+/// switching modes leaves the picture as it was. Coordinates passed to its methods are in the
+/// current resolution.
+///
+/// Drawing, clearing and scrolling only touch the planes selected with `select_planes`. That's
+/// just the first unless an XO-CHIP program picks others, so monochrome programs never see the
+/// second.
+///
+/// It knows nothing about rendering. Frontends read it with `width`, `height` and `pixel` or
+/// `color`, or `rows`, and draw it however they like.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Framebuffer {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays"))]
+    rows: [[u128; PLANES]; HIRES_HEIGHT],
+    hires: bool,
+    /// The selected planes, one bit each with the first in the low bit.
+    planes: u8,
+}
+
+impl Framebuffer {
+    pub fn new() -> Self {
+        Framebuffer {
+            rows: [[0; PLANES]; HIRES_HEIGHT],
+            hires: false,
+            planes: 0b01,
+        }
+    }
+
+    /// Whether the screen is in SUPER-CHIP's 128x64 mode.
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    /// Switch between 64x32 and 128x64. The canvas is kept, so lit pixels stay where they were
+    /// on the screen.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+    }
+
+    /// The planes that drawing affects, as a bit mask: 1 for the first, 2 for the second and 3
+    /// for both.
+    pub fn planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Pick the planes that drawing affects (XO-CHIP's `FN01`). Bits past the second plane are
+    /// ignored, and 0 selects none, so nothing gets drawn.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & 0b11;
+    }
+
+    /// Pixels across, in the current mode.
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            DISPLAY_WIDTH
+        }
+    }
+
+    /// Pixels down, in the current mode.
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            DISPLAY_HEIGHT
+        }
+    }
+
+    /// Canvas pixels per screen pixel, across and down.
+    fn scale(&self) -> usize {
+        if self.hires {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// Whether the pixel at `(x, y)` is lit in either plane.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.color(x, y) != 0
+    }
+
+    /// The colour of the pixel at `(x, y)`, from 0 to 3: bit 0 is set if it's lit in the first
+    /// plane and bit 1 if it's lit in the second.
+    pub fn color(&self, x: usize, y: usize) -> u8 {
+        assert!(x < self.width(), "x out of range: {}", x);
+        assert!(y < self.height(), "y out of range: {}", y);
+
+        let s = self.scale();
+        let row = &self.rows[y * s];
+        (0..PLANES).fold(0, |color, plane| {
+            color | ((row[plane] & Self::mask(x * s) != 0) as u8) << plane
+        })
+    }
+
+    /// Light or clear the pixel at `(x, y)` in the selected planes.
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        assert!(x < self.width(), "x out of range: {}", x);
+        assert!(y < self.height(), "y out of range: {}", y);
+
+        let s = self.scale();
+        let bits = self.block(x);
+        for row in &mut self.rows[y * s..y * s + s] {
+            for plane in selected(self.planes) {
+                if lit {
+                    row[plane] |= bits;
+                } else {
+                    row[plane] &= !bits;
+                }
+            }
+        }
+    }
+
+    /// The whole 128x64 canvas, top row first, with each row's planes in order and their leftmost
+    /// pixel in the high bit. In lores each screen pixel is two bits wide and two rows tall.
+    pub fn rows(&self) -> &[[u128; PLANES]; HIRES_HEIGHT] {
+        &self.rows
+    }
+
+    /// Turn every pixel off in the selected planes.
+    pub fn clear(&mut self) {
+        for plane in selected(self.planes) {
+            for row in &mut self.rows {
+                row[plane] = 0;
+            }
+        }
+    }
+
+    /// Flip the pixel at `(x, y)` in the selected planes, wrapping coordinates that fall off the
+    /// screen. Returns whether a lit pixel was erased.
+    pub fn toggle(&mut self, x: usize, y: usize) -> bool {
+        let (x, y) = (x % self.width(), y % self.height());
+        let (s, bits) = (self.scale(), self.block(x));
+        let mut erased = false;
+
+        for row in &mut self.rows[y * s..y * s + s] {
+            for plane in selected(self.planes) {
+                erased |= row[plane] & bits != 0;
+                row[plane] ^= bits;
+            }
+        }
+
+        erased
+    }
+
+    /// Move the selected planes down `n` pixels (SUPER-CHIP's `00CN`). Rows scrolled off the
+    /// bottom are lost and blank ones come in at the top.
+    ///
+    /// `n` is in the current resolution, so lores scrolls whole pixels like hires does, as in
+    /// Octo, rather than the half pixels of the original HP48 interpreter.
+    pub fn scroll_down(&mut self, n: usize) {
+        let n = (n * self.scale()).min(HIRES_HEIGHT);
+        for plane in selected(self.planes) {
+            for y in (0..HIRES_HEIGHT).rev() {
+                self.rows[y][plane] = if y >= n { self.rows[y - n][plane] } else { 0 };
+            }
+        }
+    }
+
+    /// Move the selected planes 4 pixels right (SUPER-CHIP's `00FB`), dropping what goes off the
+    /// edge.
+    pub fn scroll_right(&mut self) {
+        let n = 4 * self.scale();
+        for plane in selected(self.planes) {
+            for row in &mut self.rows {
+                row[plane] >>= n;
+            }
+        }
+    }
+
+    /// Move the selected planes 4 pixels left (SUPER-CHIP's `00FC`), dropping what goes off the
+    /// edge.
+    pub fn scroll_left(&mut self) {
+        let n = 4 * self.scale();
+        for plane in selected(self.planes) {
+            for row in &mut self.rows {
+                row[plane] <<= n;
+            }
+        }
+    }
+
+    /// XOR an 8-pixel-wide sprite onto the screen with its top-left corner at `(x, y)`, one byte
+    /// per row, wrapping around the edges. Returns whether any lit pixel was erased.
+    ///
+    /// With both planes selected, the first half of `sprite` is drawn to the first plane and the
+    /// second half to the second, as XO-CHIP lays them out.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw(x, y, sprite, 8, false) > 0
+    }
+
+    /// Like `draw_sprite`, but the parts of the sprite past the right and bottom edges are cut off
+    /// instead of wrapping around. The top-left corner still wraps onto the screen.
+    pub fn draw_sprite_clipped(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw(x, y, sprite, 8, true) > 0
+    }
+
+    /// Like `draw_sprite`, but 16 pixels wide with two bytes per row, as SUPER-CHIP's `DXY0`
+    /// draws from 32 bytes.
+    pub fn draw_wide_sprite(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw(x, y, sprite, 16, false) > 0
+    }
+
+    /// Like `draw_wide_sprite`, but cut off at the edges like `draw_sprite_clipped`.
+    pub fn draw_wide_sprite_clipped(&mut self, x: usize, y: usize, sprite: &[u8]) -> bool {
+        self.draw(x, y, sprite, 16, true) > 0
+    }
+
+    /// XOR a sprite `width` pixels wide, 8 or 16, onto the screen. Returns how many of its rows
+    /// erased a lit pixel, which SUPER-CHIP counts in hires.
+    pub(crate) fn draw(
+        &mut self,
+        x: usize,
+        y: usize,
+        sprite: &[u8],
+        width: usize,
+        clip: bool,
+    ) -> usize {
+        let (screen_width, height, s) = (self.width(), self.height(), self.scale());
+        let (x, y) = (x % screen_width, y % height);
+        let bytes = width / 8;
+        let planes = selected(self.planes).count();
+        if planes == 0 || sprite.is_empty() {
+            return 0;
+        }
+        // The screen rows where a lit pixel was erased, as in `DirtyRows`.
+        let mut collided = 0u64;
+
+        let per_plane = sprite.len().div_ceil(planes);
+        for (plane, data) in selected(self.planes).zip(sprite.chunks(per_plane)) {
+            for (dy, chunk) in data.chunks(bytes).enumerate() {
+                if clip && y + dy >= height {
+                    break;
+                }
+
+                // Widen the row to canvas pixels and place it at the left edge, then move it into
+                // position. Shifting drops the pixels off the right edge; rotating brings them back
+                // on the left.
+                let pixels = chunk
+                    .iter()
+                    .fold(0u128, |pixels, &byte| pixels << 8 | byte as u128);
+                let pixels = pixels << (8 * (bytes - chunk.len()));
+                let bits = if self.hires {
+                    pixels
+                } else {
+                    double(pixels as u16)
+                };
+                let bits = bits << (HIRES_WIDTH - width * s);
+                let bits = if clip {
+                    bits >> (x * s)
+                } else {
+                    bits.rotate_right((x * s) as u32)
+                };
+
+                let top = (y + dy) % height * s;
+                for row in &mut self.rows[top..top + s] {
+                    if row[plane] & bits != 0 {
+                        collided |= 1 << ((y + dy) % height);
+                    }
+                    row[plane] ^= bits;
+                }
+            }
+        }
+
+        collided.count_ones() as usize
+    }
+
+    /// Write the screen as text, one line per row ending in a newline: `.` for unlit pixels and
+    /// `#` for lit ones, or with XO-CHIP's second plane in use, `+` for pixels lit only in that
+    /// and `@` for both.
+    pub fn write_ascii(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                out.write_char(ASCII[self.color(x, y) as usize])?;
+            }
+            out.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+
+    /// The screen as text, like `write_ascii`. Handy for asserting on what a program drew.
+    #[cfg(feature = "alloc")]
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width() + 1) * self.height());
+        // Writing to a `String` can't fail.
+        let _ = self.write_ascii(&mut ascii);
+
+        ascii
+    }
+
+    /// The screen as a 1-bit bitmap, row by row, each row `width / 8` bytes with the leftmost
+    /// pixel in the high bit of the first, as in PBM files. A bit is set if its pixel is lit in
+    /// either plane.
+    #[cfg(feature = "alloc")]
+    pub fn to_bitmap(&self) -> Vec<u8> {
+        let mut bitmap = Vec::with_capacity(self.width() / 8 * self.height());
+        for y in 0..self.height() {
+            for byte in 0..self.width() / 8 {
+                bitmap.push((0..8).fold(0, |bits, bit| {
+                    bits | (self.pixel(byte * 8 + bit, y) as u8) << (7 - bit)
+                }));
+            }
+        }
+
+        bitmap
+    }
+
+    /// The screen as RGBA, row by row, with each pixel `palette[color]` for its `color`: unlit,
+    /// lit in the first plane, the second and both.
+    #[cfg(feature = "alloc")]
+    pub fn to_rgba(&self, palette: &[[u8; 4]; 4]) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.width() * self.height() * 4);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                rgba.extend_from_slice(&palette[self.color(x, y) as usize]);
+            }
+        }
+
+        rgba
+    }
+
+    /// The canvas bits covered by screen column `x`.
+    fn block(&self, x: usize) -> u128 {
+        let s = self.scale();
+        (u128::MAX << (HIRES_WIDTH - s)) >> (x * s)
+    }
+
+    /// The canvas bit for canvas column `x`.
+    fn mask(x: usize) -> u128 {
+        1 << (HIRES_WIDTH - 1 - x)
+    }
+}
+
+/// `write_ascii`'s character for each colour.
+const ASCII: [char; 4] = ['.', '#', '+', '@'];
+
+/// The planes selected in `mask`, in order.
+fn selected(mask: u8) -> impl Iterator<Item = usize> {
+    (0..PLANES).filter(move |plane| mask >> plane & 1 != 0)
+}
+
+/// Stretch a lores sprite row of up to 16 pixels to twice its width, each bit becoming two.
+fn double(pixels: u16) -> u128 {
+    (0..16).fold(0, |wide, bit| {
+        let set = (pixels >> bit & 1) as u128;
+        wide | set << (bit * 2) | set << (bit * 2 + 1)
+    })
+}
+
+/// Shows the picture, as `write_ascii` does, so that failed `assert_eq!`s on framebuffers show
+/// what's different.
+impl fmt::Debug for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Framebuffer:\n")?;
+        self.write_ascii(f)
+    }
+}
+
+impl Default for Framebuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A set of display rows, e.g. the ones that changed since the screen was last drawn, so that
+/// frontends where drawing is slow can redraw only those.
+///
+/// Rows are numbered in the screen's current resolution, so only the first 32 of the 64 mean
+/// anything in lores. Stored as one bit per row, with the top row in the low bit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DirtyRows(u64);
+
+impl DirtyRows {
+    pub const NONE: DirtyRows = DirtyRows(0);
+    pub const ALL: DirtyRows = DirtyRows(u64::MAX);
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn contains(&self, y: usize) -> bool {
+        y < HIRES_HEIGHT && self.0 & 1 << y != 0
+    }
+
+    /// Add row `y`, which must be on the screen.
+    pub fn insert(&mut self, y: usize) {
+        assert!(y < HIRES_HEIGHT, "y out of range: {}", y);
+
+        self.0 |= 1 << y;
+    }
+
+    /// Add every row in `other`.
+    pub fn union(&mut self, other: DirtyRows) {
+        self.0 |= other.0;
+    }
+
+    /// The rows, top first.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        let bits = self.0;
+
+        (0..HIRES_HEIGHT).filter(move |y| bits & 1 << y != 0)
+    }
+
+    /// Runs of adjacent rows as `start..end` ranges, top first, for blitting a few bands instead
+    /// of one row at a time.
+    pub fn spans(&self) -> impl Iterator<Item = Range<usize>> {
+        let mut bits = self.0;
+        let mut offset = 0;
+
+        core::iter::from_fn(move || {
+            if bits == 0 {
+                return None;
+            }
+
+            let skip = bits.trailing_zeros() as usize;
+            bits >>= skip;
+            let len = bits.trailing_ones() as usize;
+            // Shifting a u64 by 64 overflows, so a full-height run of 64 rows empties it instead.
+            bits = bits.checked_shr(len as u32).unwrap_or(0);
+
+            let start = offset + skip;
+            offset = start + len;
+            Some(start..offset)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn lit(fb: &Framebuffer) -> impl Iterator<Item = (usize, usize)> + '_ {
+        (0..fb.height()).flat_map(move |y| {
+            (0..fb.width()).filter_map(move |x| fb.pixel(x, y).then_some((x, y)))
+        })
+    }
+
+    #[test]
+    fn drawing_twice_erases_and_collides() {
+        let mut fb = Framebuffer::new();
+
+        assert!(!fb.draw_sprite(3, 4, &[0xF0]));
+        assert!((3..7).all(|x| fb.pixel(x, 4)));
+        assert_eq!(lit(&fb).count(), 4);
+
+        assert!(!fb.draw_sprite(7, 4, &[0x80]));
+        assert!(fb.draw_sprite(3, 4, &[0xF0]));
+        assert_eq!(lit(&fb).collect::<Vec<_>>(), [(7, 4)]);
+    }
+
+    #[test]
+    fn sprites_wrap_around_the_edges() {
+        let mut fb = Framebuffer::new();
+        fb.draw_sprite(60, 31, &[0xFF, 0x81]);
+
+        let mut expected: Vec<_> = [60, 61, 62, 63, 0, 1, 2, 3]
+            .into_iter()
+            .map(|x| (x, 31))
+            .chain([(3, 0), (60, 0)])
+            .collect();
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(lit(&fb).collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn clipped_sprites_are_cut_off_but_start_wrapped() {
+        let mut fb = Framebuffer::new();
+        fb.draw_sprite_clipped(60, 31, &[0xFF, 0xFF]);
+        assert_eq!(
+            lit(&fb).collect::<Vec<_>>(),
+            [(60, 31), (61, 31), (62, 31), (63, 31)]
+        );
+
+        let mut fb = Framebuffer::new();
+        fb.draw_sprite_clipped(64 + 2, 32 + 1, &[0x80]);
+        assert_eq!(lit(&fb).collect::<Vec<_>>(), [(2, 1)]);
+    }
+
+    #[test]
+    fn hires_is_128_by_64_on_the_same_canvas() {
+        let mut fb = Framebuffer::new();
+        fb.set_pixel(1, 1, true);
+
+        fb.set_hires(true);
+        assert_eq!((fb.width(), fb.height()), (HIRES_WIDTH, HIRES_HEIGHT));
+        assert_eq!(
+            lit(&fb).collect::<Vec<_>>(),
+            [(2, 2), (3, 2), (2, 3), (3, 3)]
+        );
+
+        assert!(!fb.draw_wide_sprite_clipped(120, 63, &[0xFF, 0xFF, 0xFF, 0xFF]));
+        assert!((120..128).all(|x| fb.pixel(x, 63)));
+        assert_eq!(lit(&fb).count(), 4 + 8);
+
+        assert!(fb.draw_wide_sprite(120, 63, &[0xFF, 0xFF]));
+        assert!(fb.pixel(2, 2) && !fb.pixel(127, 63));
+        assert!((0..8).all(|x| fb.pixel(x, 63)));
+    }
+
+    #[test]
+    fn scrolling_moves_whole_pixels_and_drops_the_edges() {
+        let mut fb = Framebuffer::new();
+        fb.set_pixel(0, 0, true);
+        fb.set_pixel(63, 31, true);
+
+        fb.scroll_down(1);
+        assert_eq!(lit(&fb).collect::<Vec<_>>(), [(0, 1)]);
+        fb.scroll_right();
+        assert_eq!(lit(&fb).collect::<Vec<_>>(), [(4, 1)]);
+        fb.scroll_left();
+        fb.scroll_left();
+        assert_eq!(lit(&fb).count(), 0);
+    }
+
+    #[test]
+    fn drawing_only_touches_the_selected_planes() {
+        let mut fb = Framebuffer::new();
+
+        fb.select_planes(0b10);
+        fb.draw_sprite(0, 0, &[0x80]);
+        assert_eq!(fb.color(0, 0), 2);
+
+        fb.select_planes(0b11);
+        fb.draw_sprite(1, 0, &[0x80, 0x80]);
+        assert_eq!(fb.color(1, 0), 3);
+
+        fb.select_planes(0b01);
+        fb.clear();
+        assert_eq!((fb.color(0, 0), fb.color(1, 0)), (2, 2));
+
+        fb.select_planes(0);
+        assert!(!fb.draw_sprite(0, 0, &[0x80]));
+        assert_eq!(fb.color(0, 0), 2);
+    }
+}
@@ -0,0 +1,80 @@
+use core::error::Error;
+use core::fmt;
+
+use crate::instruction::Instruction;
+use crate::memory::MemoryError;
+
+/// Why execution stopped abnormally.
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// An opcode the interpreter doesn't know how to execute, read from address `pc`.
+    UnknownOpcode { opcode: u16, pc: usize },
+    /// An arithmetic instruction without a carry flag overflowed while `trap_overflow` was set.
+    Overflow { instruction: Instruction, pc: usize },
+    /// `2NNN` was executed with every stack slot already in use, under `StackPolicy::Error`.
+    StackOverflow { pc: usize },
+    /// `00EE` was executed with nothing on the stack to return to.
+    StackUnderflow { pc: usize },
+    /// The instruction at `pc` tried to access `addr`, which is past the end of memory.
+    MemoryOutOfBounds { addr: usize, pc: usize },
+    /// The instruction at `pc` tried to write to `addr`, which is write-protected.
+    WriteProtected { addr: usize, pc: usize },
+    /// A ROM of `size` bytes doesn't fit in the `max` bytes available to it.
+    RomTooLarge { size: usize, max: usize },
+    /// A ROM was to be loaded at `addr`, which is past the end of the `len` bytes of memory.
+    LoadAddressOutOfBounds { addr: usize, len: usize },
+    /// The ROM has nothing in it.
+    EmptyRom,
+}
+
+impl Chip8Error {
+    /// Attribute a failed memory access to the instruction at `pc`.
+    pub fn from_memory(err: MemoryError, pc: usize) -> Self {
+        match err {
+            MemoryError::OutOfBounds { addr } => Chip8Error::MemoryOutOfBounds { addr, pc },
+            MemoryError::WriteProtected { addr } => Chip8Error::WriteProtected { addr, pc },
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:04x} at {:#05x}", opcode, pc)
+            }
+            Chip8Error::Overflow { instruction, pc } => {
+                write!(f, "arithmetic overflow in {:?} at {:#05x}", instruction, pc)
+            }
+            Chip8Error::StackOverflow { pc } => write!(f, "stack overflow at {:#05x}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "stack underflow at {:#05x}", pc),
+            Chip8Error::MemoryOutOfBounds { addr, pc } => {
+                write!(
+                    f,
+                    "out of bounds memory access to {:#x} at {:#05x}",
+                    addr, pc
+                )
+            }
+            Chip8Error::WriteProtected { addr, pc } => {
+                write!(f, "write to protected memory at {:#x} at {:#05x}", addr, pc)
+            }
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(
+                    f,
+                    "ROM is {} bytes, but only {} bytes are available",
+                    size, max
+                )
+            }
+            Chip8Error::LoadAddressOutOfBounds { addr, len } => {
+                write!(
+                    f,
+                    "can't load a ROM at {:#x}, past the end of {} bytes of memory",
+                    addr, len
+                )
+            }
+            Chip8Error::EmptyRom => write!(f, "the ROM is empty"),
+        }
+    }
+}
+
+impl Error for Chip8Error {}
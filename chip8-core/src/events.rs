@@ -0,0 +1,90 @@
+/// Something that happened while the machine ran, as drained with `Cpu::poll_event`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmulatorEvent {
+    /// The framebuffer changed.
+    DisplayUpdated,
+    /// The sound timer became nonzero.
+    SoundStarted,
+    /// The sound timer reached zero.
+    SoundStopped,
+    /// The delay timer counted down to zero.
+    DelayExpired,
+    /// `FX0A` started waiting for a key, which it will store in register `x`.
+    WaitingForKey { x: u8 },
+    /// The program reached a `0000` instruction.
+    Halted,
+}
+
+/// How many undrained events are kept. When full, the oldest is dropped.
+pub const EVENT_QUEUE_CAPACITY: usize = 32;
+
+/// A fixed-size ring buffer of events, so that queueing needs no allocator.
+#[derive(Debug, Clone)]
+pub(crate) struct EventQueue {
+    events: [EmulatorEvent; EVENT_QUEUE_CAPACITY],
+    head: usize,
+    len: usize,
+    /// The most recently pushed event, kept after it's drained so `push_once` can tell
+    /// whether it's already been reported.
+    last: Option<EmulatorEvent>,
+}
+
+impl EventQueue {
+    pub(crate) fn new() -> Self {
+        EventQueue {
+            events: [EmulatorEvent::DisplayUpdated; EVENT_QUEUE_CAPACITY],
+            head: 0,
+            len: 0,
+            last: None,
+        }
+    }
+
+    /// Queue `event`, unless it's identical to the one at the back of the queue, e.g. several
+    /// draws in one frame are reported as a single `DisplayUpdated`.
+    pub(crate) fn push(&mut self, event: EmulatorEvent) {
+        if self.len > 0 && self.last == Some(event) {
+            return;
+        }
+
+        if self.len == EVENT_QUEUE_CAPACITY {
+            self.head = (self.head + 1) % EVENT_QUEUE_CAPACITY;
+            self.len -= 1;
+        }
+
+        self.events[(self.head + self.len) % EVENT_QUEUE_CAPACITY] = event;
+        self.len += 1;
+        self.last = Some(event);
+    }
+
+    /// Queue `event` unless it was the last event pushed, even if that has been drained since.
+    /// For states like `Halted` that are re-detected on every step.
+    pub(crate) fn push_once(&mut self, event: EmulatorEvent) {
+        if self.last != Some(event) {
+            self.push(event);
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<EmulatorEvent> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.head];
+        self.head = (self.head + 1) % EVENT_QUEUE_CAPACITY;
+        self.len -= 1;
+
+        Some(event)
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+        self.last = None;
+    }
+}
+
+impl Default for EventQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
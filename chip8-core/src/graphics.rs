@@ -0,0 +1,142 @@
+//! Drawing the display to an `embedded-graphics` `DrawTarget`, such as the driver for an SSD1306
+//! OLED or an ST7789 LCD on a microcontroller.
+
+use embedded_graphics_core::draw_target::DrawTarget;
+use embedded_graphics_core::geometry::{Point, Size};
+use embedded_graphics_core::primitives::Rectangle;
+
+use crate::display::{DirtyRows, Framebuffer, HIRES_HEIGHT, HIRES_WIDTH, PLANES};
+use crate::peripherals::DisplaySink;
+
+/// A `DisplaySink` that draws to a `DrawTarget`, redrawing only the rows that changed.
+///
+/// The picture is always the 128x64 canvas the framebuffer keeps, times `scale`, so it doesn't
+/// change size when a SUPER-CHIP game switches resolution: at scale 1 a 128x64 SSD1306 shows
+/// lores pixels as 2x2 blocks and hires ones as they are.
+///
+/// `DisplaySink` can't fail, so errors from the target are kept for `take_error`, and the rows
+/// that didn't get drawn are tried again on the next present.
+pub struct DrawTargetDisplay<D: DrawTarget> {
+    target: D,
+    /// The colour for each of `Framebuffer::color`'s values.
+    palette: [D::Color; 4],
+    scale: u32,
+    offset: Point,
+    undrawn: DirtyRows,
+    error: Option<D::Error>,
+}
+
+impl<D: DrawTarget> DrawTargetDisplay<D> {
+    /// Draw to `target` at its top-left corner, with unlit pixels `off` and lit ones `on`.
+    /// XO-CHIP's second plane is drawn `on` too, until `with_palette` gives it colours of its own.
+    pub fn new(target: D, off: D::Color, on: D::Color) -> Self {
+        DrawTargetDisplay {
+            target,
+            palette: [off, on, on, on],
+            scale: 1,
+            offset: Point::zero(),
+            undrawn: DirtyRows::ALL,
+            error: None,
+        }
+    }
+
+    /// Colours for pixels lit in neither plane, the first, the second and both, in that order.
+    pub fn with_palette(mut self, palette: [D::Color; 4]) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Draw each canvas pixel as a `scale` by `scale` square. Must be at least 1.
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        assert!(scale > 0, "scale must be at least 1");
+        self.scale = scale;
+        self
+    }
+
+    /// Put the picture's top-left corner at `offset` on the target, e.g. to centre it.
+    pub fn with_offset(mut self, offset: Point) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// The size of the picture on the target.
+    pub fn size(&self) -> Size {
+        Size::new(HIRES_WIDTH as u32, HIRES_HEIGHT as u32) * self.scale
+    }
+
+    pub fn target(&self) -> &D {
+        &self.target
+    }
+
+    /// The target, e.g. to flush a buffered display driver after a frame.
+    pub fn target_mut(&mut self) -> &mut D {
+        &mut self.target
+    }
+
+    pub fn into_inner(self) -> D {
+        self.target
+    }
+
+    /// The last error the target returned while presenting, if there's been one since this was
+    /// last called.
+    pub fn take_error(&mut self) -> Option<D::Error> {
+        self.error.take()
+    }
+
+    /// Draw the `dirty` rows of `framebuffer`, numbered in its current resolution like
+    /// `Cpu::dirty_rows`. Pass `DirtyRows::ALL` to draw the whole thing, e.g. before the first
+    /// frame.
+    pub fn draw(&mut self, framebuffer: &Framebuffer, dirty: DirtyRows) -> Result<(), D::Error> {
+        let (height, scale) = (framebuffer.height(), self.scale);
+        // Canvas rows per display row.
+        let s = HIRES_HEIGHT / height;
+        let rows = framebuffer.rows();
+        let palette = self.palette;
+
+        for span in dirty.spans() {
+            let (top, bottom) = (span.start.min(height) * s, span.end.min(height) * s);
+            if top == bottom {
+                continue;
+            }
+
+            let area = Rectangle::new(
+                self.offset + Point::new(0, (top as u32 * scale) as i32),
+                Size::new(HIRES_WIDTH as u32, (bottom - top) as u32) * scale,
+            );
+            let colors = (top as u32 * scale..bottom as u32 * scale).flat_map(|y| {
+                let row = &rows[(y / scale) as usize];
+                (0..HIRES_WIDTH as u32 * scale)
+                    .map(move |x| palette[canvas_color(row, (x / scale) as usize) as usize])
+            });
+            self.target.fill_contiguous(&area, colors)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The colour of canvas column `x` in `row`, as in `Framebuffer::color`.
+fn canvas_color(row: &[u128; PLANES], x: usize) -> u8 {
+    (0..PLANES).fold(0, |color, plane| {
+        color | ((row[plane] >> (HIRES_WIDTH - 1 - x) & 1) as u8) << plane
+    })
+}
+
+impl<D: DrawTarget> DisplaySink for DrawTargetDisplay<D> {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        self.present_rows(framebuffer, DirtyRows::ALL);
+    }
+
+    fn present_rows(&mut self, framebuffer: &Framebuffer, dirty: DirtyRows) {
+        let mut rows = dirty;
+        rows.union(self.undrawn);
+
+        match self.draw(framebuffer, rows) {
+            Ok(()) => self.undrawn = DirtyRows::NONE,
+            Err(err) => {
+                self.undrawn = rows;
+                self.error = Some(err);
+            }
+        }
+    }
+}
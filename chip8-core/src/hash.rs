@@ -0,0 +1,139 @@
+use core::hash::Hasher;
+
+/// 64-bit FNV-1a, for fingerprints that have to come out the same on every platform and in
+/// every build, unlike `std`'s randomly keyed `DefaultHasher`.
+pub(crate) struct Fnv1a(u64);
+
+impl Fnv1a {
+    pub(crate) fn new() -> Self {
+        Fnv1a(0xCBF2_9CE4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The FNV-1a hash of `bytes`, e.g. to check a replay is run against the ROM it was recorded
+/// with.
+pub fn rom_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// The SHA-1 digest of `bytes`, which is how ROM databases, this crate's `KnownRoms` among
+/// them, tell well-known ROM files apart.
+pub fn rom_sha1(bytes: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xEFCD_AB89,
+        0x98BA_DCFE,
+        0x1032_5476,
+        0xC3D2_E1F0,
+    ];
+
+    // The message, a 1 bit, zeros up to 8 bytes short of a 64-byte block, then the length in
+    // bits, all in 64-byte blocks.
+    let bits = (bytes.len() as u64).wrapping_mul(8);
+    let mut tail = [0; 128];
+    let rest = bytes.chunks_exact(64).remainder();
+    tail[..rest.len()].copy_from_slice(rest);
+    tail[rest.len()] = 0x80;
+    let tail_len = if rest.len() < 56 { 64 } else { 128 };
+    tail[tail_len - 8..tail_len].copy_from_slice(&bits.to_be_bytes());
+
+    let blocks = bytes
+        .chunks_exact(64)
+        .chain(tail[..tail_len].chunks_exact(64));
+    for block in blocks {
+        let mut w = [0u32; 80];
+        for (word, chunk) in w.iter_mut().zip(block.chunks_exact(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ED9_EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1B_BCDC),
+                _ => (b ^ c ^ d, 0xCA62_C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (word, new) in state.iter_mut().zip([a, b, c, d, e]) {
+            *word = word.wrapping_add(new);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, word) in digest.chunks_exact_mut(4).zip(state) {
+        chunk.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(hex: &str) -> [u8; 20] {
+        let mut digest = [0; 20];
+        for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            *byte = u8::from_str_radix(core::str::from_utf8(pair).unwrap(), 16).unwrap();
+        }
+        digest
+    }
+
+    #[test]
+    fn rom_hash_is_fnv1a() {
+        assert_eq!(rom_hash(b""), 0xCBF2_9CE4_8422_2325);
+        assert_eq!(rom_hash(b"a"), 0xAF63_DC4C_8601_EC8C);
+    }
+
+    #[test]
+    fn rom_sha1_matches_known_digests() {
+        assert_eq!(
+            rom_sha1(b"abc"),
+            digest("a9993e364706816aba3e25717850c26c9cd0d89d")
+        );
+
+        // Sizes either side of where the padding spills into a second block.
+        let bytes: [u8; 1000] = core::array::from_fn(|i| (i % 251) as u8);
+        for (len, hex) in [
+            (0, "da39a3ee5e6b4b0d3255bfef95601890afd80709"),
+            (55, "8ae2d46729cfe68ff927af5eec9c7d1b66d65ac2"),
+            (56, "636e2ec698dac903498e648bd2f3af641d3c88cb"),
+            (63, "6d942da0c4392b123528f2905c713a3ce28364bd"),
+            (64, "c6138d514ffa2135bfce0ed0b8fac65669917ec7"),
+            (119, "41c89d06001bab4ab78736b44efe7ce18ce6ae08"),
+            (120, "d3dbd653bd8597b7475321b60a36891278e6a04a"),
+            (1000, "c9c960a0b925474fab83942cc27d504fc24ac37b"),
+        ] {
+            assert_eq!(rom_sha1(&bytes[..len]), digest(hex), "{} bytes", len);
+        }
+    }
+}
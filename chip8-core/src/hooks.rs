@@ -0,0 +1,99 @@
+use crate::display::Framebuffer;
+use crate::input::InputEvent;
+use crate::instruction::Instruction;
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+#[cfg(feature = "alloc")]
+type Hook<F> = Option<Box<F>>;
+
+/// Optional callbacks the core invokes as it runs, for tracers, debuggers and test harnesses.
+///
+/// Register them with `Cpu::on_instruction` and friends. They must be `Send + Sync` so that a
+/// `Cpu` can be moved to, and shared with, other threads.
+#[cfg(feature = "alloc")]
+#[derive(Default)]
+pub(crate) struct Hooks {
+    pub(crate) instruction: Hook<dyn FnMut(usize, Instruction) + Send + Sync>,
+    pub(crate) draw: Hook<dyn FnMut(&Framebuffer) + Send + Sync>,
+    pub(crate) sound_start: Hook<dyn FnMut() + Send + Sync>,
+    pub(crate) sound_stop: Hook<dyn FnMut() + Send + Sync>,
+    pub(crate) delay_expired: Hook<dyn FnMut() + Send + Sync>,
+    pub(crate) key_wait: Hook<dyn FnMut(u8) + Send + Sync>,
+    pub(crate) input: Hook<dyn FnMut(InputEvent) + Send + Sync>,
+}
+
+#[cfg(feature = "alloc")]
+impl Hooks {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn instruction(&mut self, pc: usize, instruction: Instruction) {
+        if let Some(hook) = &mut self.instruction {
+            hook(pc, instruction);
+        }
+    }
+
+    pub(crate) fn draw(&mut self, framebuffer: &Framebuffer) {
+        if let Some(hook) = &mut self.draw {
+            hook(framebuffer);
+        }
+    }
+
+    pub(crate) fn sound_start(&mut self) {
+        if let Some(hook) = &mut self.sound_start {
+            hook();
+        }
+    }
+
+    pub(crate) fn sound_stop(&mut self) {
+        if let Some(hook) = &mut self.sound_stop {
+            hook();
+        }
+    }
+
+    pub(crate) fn delay_expired(&mut self) {
+        if let Some(hook) = &mut self.delay_expired {
+            hook();
+        }
+    }
+
+    pub(crate) fn key_wait(&mut self, x: u8) {
+        if let Some(hook) = &mut self.key_wait {
+            hook(x);
+        }
+    }
+
+    pub(crate) fn input(&mut self, event: InputEvent) {
+        if let Some(hook) = &mut self.input {
+            hook(event);
+        }
+    }
+}
+
+/// Without an allocator there's nowhere to keep boxed callbacks, so every hook is a no-op.
+#[cfg(not(feature = "alloc"))]
+pub(crate) struct Hooks;
+
+#[cfg(not(feature = "alloc"))]
+impl Hooks {
+    pub(crate) fn new() -> Self {
+        Hooks
+    }
+
+    pub(crate) fn instruction(&mut self, _pc: usize, _instruction: Instruction) {}
+
+    pub(crate) fn draw(&mut self, _framebuffer: &Framebuffer) {}
+
+    pub(crate) fn sound_start(&mut self) {}
+
+    pub(crate) fn sound_stop(&mut self) {}
+
+    pub(crate) fn delay_expired(&mut self) {}
+
+    pub(crate) fn key_wait(&mut self, _x: u8) {}
+
+    pub(crate) fn input(&mut self, _event: InputEvent) {}
+}
@@ -0,0 +1,136 @@
+#[cfg(feature = "std")]
+use std::sync::mpsc::Sender;
+
+/// A key press or release for `Cpu::queue_input`, to be applied at the start of frame `frame`.
+///
+/// Frames are counted by `Cpu::frame`. Events stamped with a frame that's already started are
+/// applied at the next frame boundary, so a frontend that doesn't care about timing can stamp
+/// everything with 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputEvent {
+    /// The hex key. Only the low nibble is used.
+    pub key: u8,
+    /// Whether the key went down rather than up.
+    pub pressed: bool,
+    pub frame: u64,
+}
+
+impl InputEvent {
+    pub fn press(key: u8, frame: u64) -> Self {
+        InputEvent {
+            key,
+            pressed: true,
+            frame,
+        }
+    }
+
+    pub fn release(key: u8, frame: u64) -> Self {
+        InputEvent {
+            key,
+            pressed: false,
+            frame,
+        }
+    }
+}
+
+/// How many queued input events are kept. When full, the earliest is applied straight away to
+/// make room, so keys still end up in the right state, just sooner than asked.
+pub const INPUT_QUEUE_CAPACITY: usize = 64;
+
+/// Input events waiting for their frame, earliest first, in a fixed-size ring buffer so that
+/// queueing needs no allocator.
+#[derive(Debug, Clone)]
+pub(crate) struct InputQueue {
+    events: [InputEvent; INPUT_QUEUE_CAPACITY],
+    head: usize,
+    len: usize,
+}
+
+impl InputQueue {
+    pub(crate) fn new() -> Self {
+        InputQueue {
+            events: [InputEvent::release(0, 0); INPUT_QUEUE_CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Queue `event` after every event for the same frame or earlier. If the queue was full,
+    /// returns the earliest event, which the caller should apply now.
+    pub(crate) fn push(&mut self, event: InputEvent) -> Option<InputEvent> {
+        let evicted = if self.len == INPUT_QUEUE_CAPACITY {
+            self.pop()
+        } else {
+            None
+        };
+
+        // Shift later events back one place, then slot `event` in before them.
+        let mut i = self.len;
+        while i > 0 && self.get(i - 1).frame > event.frame {
+            self.events[self.index(i)] = self.get(i - 1);
+            i -= 1;
+        }
+        self.events[self.index(i)] = event;
+        self.len += 1;
+
+        evicted
+    }
+
+    /// Take the earliest event if it's due by `frame`.
+    pub(crate) fn pop_due(&mut self, frame: u64) -> Option<InputEvent> {
+        if self.len > 0 && self.get(0).frame <= frame {
+            self.pop()
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn pop(&mut self) -> Option<InputEvent> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.get(0);
+        self.head = (self.head + 1) % INPUT_QUEUE_CAPACITY;
+        self.len -= 1;
+
+        Some(event)
+    }
+
+    fn index(&self, i: usize) -> usize {
+        (self.head + i) % INPUT_QUEUE_CAPACITY
+    }
+
+    fn get(&self, i: usize) -> InputEvent {
+        self.events[self.index(i)]
+    }
+}
+
+impl Default for InputQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Queues input for a `Cpu` from anywhere, e.g. a UI or network thread, without needing the
+/// machine itself. Made with `Cpu::input_sender`, and cheap to clone.
+///
+/// Events wait in a channel until the machine's next frame boundary, when they join the queue
+/// as if they'd been passed to `Cpu::queue_input`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct InputSender(pub(crate) Sender<InputEvent>);
+
+#[cfg(feature = "std")]
+impl InputSender {
+    /// Queue `event`. Returns false if the machine has been dropped.
+    pub fn send(&self, event: InputEvent) -> bool {
+        self.0.send(event).is_ok()
+    }
+}
@@ -0,0 +1,580 @@
+//! A compact file format for recorded keypad input, for replays and regression tests.
+//!
+//! A log starts with the four bytes `C8IN` and a version byte, currently 2, followed by the
+//! `RunSetup`: the RNG seed and the clock in Hz as little-endian `u32`s, the quirks as a byte
+//! with one bit per `Quirks` field in declaration order from bit 0, and the ROM's hash as a
+//! little-endian `u64`.
+//!
+//! Each event follows as the number of frames since the previous event (or since frame 0, for
+//! the first) as an unsigned LEB128 varint, then one byte holding the key in its low nibble and
+//! 1 in bit 4 for a press or 0 for a release. Most events take two bytes.
+//!
+//! A log may end with a `RunEnd`: a frame count like an event's, then the byte `0x20` and the
+//! machine's `Cpu::state_hash` as a little-endian `u64`.
+
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::{self, Write};
+
+use crate::builder::{Chip8, Chip8Builder};
+use crate::cpu::{Cpu, Quirks, DEFAULT_CLOCK_HZ};
+use crate::error::Chip8Error;
+use crate::hash::rom_hash;
+use crate::input::InputEvent;
+use crate::rng::Rng;
+
+/// The bytes every input log starts with.
+pub const INPUT_LOG_MAGIC: [u8; 4] = *b"C8IN";
+
+/// The format version this crate writes and reads.
+const VERSION: u8 = 2;
+
+/// The length of the header: magic, version and `RunSetup`.
+const HEADER_LEN: usize = 22;
+
+/// Bit 4 of an event's key byte: set for a press, clear for a release.
+const PRESSED: u8 = 0x10;
+
+/// The byte in place of an event's key byte that marks the `RunEnd`.
+const END: u8 = 0x20;
+
+/// How a recorded machine was set up, so that a replay can start out the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunSetup {
+    pub seed: u32,
+    pub clock_hz: u32,
+    pub quirks: Quirks,
+    /// The `rom_hash` of the ROM that was played.
+    pub rom_hash: u64,
+}
+
+impl RunSetup {
+    /// `rom` on a machine built with the builder's defaults.
+    pub fn for_rom(rom: &[u8]) -> Self {
+        RunSetup {
+            seed: 0,
+            clock_hz: DEFAULT_CLOCK_HZ,
+            quirks: Quirks::default(),
+            rom_hash: rom_hash(rom),
+        }
+    }
+
+    /// A builder for the machine this describes, running `rom`.
+    pub fn builder<'a>(&self, rom: &'a [u8]) -> Chip8Builder<'a> {
+        Chip8Builder::new()
+            .rom(rom)
+            .seed(self.seed)
+            .clock_hz(self.clock_hz)
+            .quirks(self.quirks)
+    }
+}
+
+/// Where a recording stopped: after `frame` frames, in the state with `Cpu::state_hash`
+/// `state_hash`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunEnd {
+    pub frame: u64,
+    pub state_hash: u64,
+}
+
+impl RunEnd {
+    /// Where `cpu` is now, to end a recording of it with.
+    pub fn of<R: Rng, const MEM: usize, const STACK: usize>(cpu: &Cpu<R, MEM, STACK>) -> Self {
+        RunEnd {
+            frame: cpu.frame(),
+            state_hash: cpu.state_hash(),
+        }
+    }
+}
+
+/// Every keypad press and release of a run, in the order they happened, as recorded with
+/// `Cpu::on_input`, along with how the run started and, once it's over, how it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputLog {
+    setup: RunSetup,
+    events: Vec<InputEvent>,
+    end: Option<RunEnd>,
+}
+
+impl InputLog {
+    pub fn new(setup: RunSetup) -> Self {
+        InputLog {
+            setup,
+            events: Vec::new(),
+            end: None,
+        }
+    }
+
+    pub fn setup(&self) -> &RunSetup {
+        &self.setup
+    }
+
+    /// Add `event` to the end of the log.
+    pub fn push(&mut self, event: InputEvent) {
+        self.events.push(event);
+    }
+
+    pub fn events(&self) -> &[InputEvent] {
+        &self.events
+    }
+
+    pub fn end(&self) -> Option<RunEnd> {
+        self.end
+    }
+
+    /// Record where the run stopped. Events can't be added afterwards.
+    pub fn finish(&mut self, end: RunEnd) {
+        self.end = Some(end);
+    }
+
+    /// The log in the file format described in the module docs.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::from(header(&self.setup));
+        let mut frame = 0;
+
+        for &event in &self.events {
+            encode_event(&mut bytes, &mut frame, event);
+        }
+        if let Some(end) = self.end {
+            encode_end(&mut bytes, &mut frame, end);
+        }
+
+        bytes
+    }
+
+    /// Read a log written by `encode` or an `InputLogWriter`.
+    pub fn decode(bytes: &[u8]) -> Result<Self, InputLogError> {
+        let rest = bytes
+            .strip_prefix(&INPUT_LOG_MAGIC)
+            .ok_or(InputLogError::NotAnInputLog)?;
+        let (&version, _) = rest.split_first().ok_or(InputLogError::Truncated)?;
+        if version != VERSION {
+            return Err(InputLogError::UnsupportedVersion(version));
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(InputLogError::Truncated);
+        }
+        let (header, mut rest) = bytes.split_at(HEADER_LEN);
+
+        let u32_at =
+            |i: usize| u32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let mut hash = [0; 8];
+        hash.copy_from_slice(&header[14..]);
+        let mut log = InputLog::new(RunSetup {
+            seed: u32_at(5),
+            clock_hz: u32_at(9),
+            quirks: quirks_from_bits(header[13])?,
+            rom_hash: u64::from_le_bytes(hash),
+        });
+
+        let mut frame = 0u64;
+        while !rest.is_empty() {
+            let (delta, after) = read_varint(rest)?;
+            let (&byte, after) = after.split_first().ok_or(InputLogError::Truncated)?;
+            frame = frame.checked_add(delta).ok_or(InputLogError::BadFrame)?;
+
+            if byte == END {
+                let hash = after.get(..8).ok_or(InputLogError::Truncated)?;
+                if after.len() > 8 {
+                    return Err(InputLogError::AfterEnd);
+                }
+                let mut state_hash = [0; 8];
+                state_hash.copy_from_slice(hash);
+                log.finish(RunEnd {
+                    frame,
+                    state_hash: u64::from_le_bytes(state_hash),
+                });
+                break;
+            }
+            if byte & !(PRESSED | 0xF) != 0 {
+                return Err(InputLogError::BadEvent(byte));
+            }
+
+            log.push(InputEvent {
+                key: byte & 0xF,
+                pressed: byte & PRESSED != 0,
+                frame,
+            });
+            rest = after;
+        }
+
+        Ok(log)
+    }
+
+    /// Play the log back on a fresh machine set up like the recorded one, running `rom`, and
+    /// return the machine as it is at the end: after `end`'s frame count if the log has one, or
+    /// else the frame after the last event.
+    ///
+    /// Each event is queued just before its frame, and the same ROM and setup always give the
+    /// same result, so the machine's `state_hash` can be checked against `end`'s.
+    pub fn replay(&self, rom: &[u8]) -> Result<Chip8, Chip8Error> {
+        let mut cpu = self.setup.builder(rom).build()?;
+        let last = self.events.last().map_or(0, |event| event.frame + 1);
+        let frames = self.end.map_or(last, |end| end.frame);
+
+        let mut events = self.events.iter().peekable();
+        while cpu.frame() < frames {
+            while let Some(&event) = events.next_if(|event| event.frame <= cpu.frame()) {
+                cpu.queue_input(event);
+            }
+            cpu.run_frame()?;
+        }
+
+        Ok(cpu)
+    }
+}
+
+fn header(setup: &RunSetup) -> [u8; HEADER_LEN] {
+    let mut header = [0; HEADER_LEN];
+    header[..4].copy_from_slice(&INPUT_LOG_MAGIC);
+    header[4] = VERSION;
+    header[5..9].copy_from_slice(&setup.seed.to_le_bytes());
+    header[9..13].copy_from_slice(&setup.clock_hz.to_le_bytes());
+    header[13] = quirks_to_bits(&setup.quirks);
+    header[14..].copy_from_slice(&setup.rom_hash.to_le_bytes());
+    header
+}
+
+fn quirks_to_bits(quirks: &Quirks) -> u8 {
+    [
+        quirks.add_i_sets_vf,
+        quirks.load_store_increments_i,
+        quirks.shift_uses_vy,
+        quirks.jump_with_vx,
+        quirks.clip_sprites,
+        quirks.wait_for_release,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (i, &on)| bits | (on as u8) << i)
+}
+
+fn quirks_from_bits(bits: u8) -> Result<Quirks, InputLogError> {
+    if bits >> 6 != 0 {
+        return Err(InputLogError::UnknownQuirks(bits));
+    }
+
+    let on = |i: u8| bits & 1 << i != 0;
+    Ok(Quirks {
+        add_i_sets_vf: on(0),
+        load_store_increments_i: on(1),
+        shift_uses_vy: on(2),
+        jump_with_vx: on(3),
+        clip_sprites: on(4),
+        wait_for_release: on(5),
+    })
+}
+
+/// Append `event` to `bytes`, `frame` being the frame of the event before it. A log covers a
+/// single run, so an event stamped before the one before it, e.g. after a `reset`, is written as
+/// happening in the same frame.
+fn encode_event(bytes: &mut Vec<u8>, frame: &mut u64, event: InputEvent) {
+    encode_frame(bytes, frame, event.frame);
+    bytes.push(event.key & 0xF | if event.pressed { PRESSED } else { 0 });
+}
+
+fn encode_end(bytes: &mut Vec<u8>, frame: &mut u64, end: RunEnd) {
+    encode_frame(bytes, frame, end.frame);
+    bytes.push(END);
+    bytes.extend_from_slice(&end.state_hash.to_le_bytes());
+}
+
+/// Append the varint for how far `to` is past `frame`, and move `frame` up to it.
+fn encode_frame(bytes: &mut Vec<u8>, frame: &mut u64, to: u64) {
+    let mut delta = to.saturating_sub(*frame);
+    *frame = (*frame).max(to);
+
+    loop {
+        let byte = (delta & 0x7F) as u8;
+        delta >>= 7;
+        if delta == 0 {
+            bytes.push(byte);
+            break;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Read an unsigned LEB128 number from the start of `bytes`, returning it and what follows.
+fn read_varint(bytes: &[u8]) -> Result<(u64, &[u8]), InputLogError> {
+    let mut value = 0u64;
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        let shift = 7 * i as u32;
+        if shift >= u64::BITS || (byte & 0x7F) as u64 > u64::MAX >> shift {
+            return Err(InputLogError::BadFrame);
+        }
+
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+
+    Err(InputLogError::Truncated)
+}
+
+/// Why bytes couldn't be read as an `InputLog`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputLogError {
+    /// The bytes don't start with `INPUT_LOG_MAGIC`.
+    NotAnInputLog,
+    /// The log was written by a newer version of the format.
+    UnsupportedVersion(u8),
+    /// The log ends partway through an event.
+    Truncated,
+    /// An event's key byte has bits set other than the key and the press flag.
+    BadEvent(u8),
+    /// An event's frame number doesn't fit in 64 bits.
+    BadFrame,
+    /// The quirks byte has bits set that don't stand for any quirk.
+    UnknownQuirks(u8),
+    /// There are bytes after the `RunEnd`.
+    AfterEnd,
+}
+
+impl fmt::Display for InputLogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputLogError::NotAnInputLog => write!(f, "not an input log"),
+            InputLogError::UnsupportedVersion(version) => {
+                write!(f, "input log version {} isn't supported", version)
+            }
+            InputLogError::Truncated => write!(f, "input log ends partway through an event"),
+            InputLogError::BadEvent(byte) => write!(f, "bad input log event {:#04x}", byte),
+            InputLogError::BadFrame => write!(f, "input log frame number out of range"),
+            InputLogError::UnknownQuirks(bits) => {
+                write!(f, "unknown quirks {:#04x} in input log", bits)
+            }
+            InputLogError::AfterEnd => write!(f, "input log continues after its end"),
+        }
+    }
+}
+
+impl Error for InputLogError {}
+
+/// Writes an input log as it's recorded, e.g. from a `Cpu::on_input` hook, so that it survives
+/// however the program exits.
+///
+/// A hook has nowhere to report errors, so the first one writing an event is kept for `finish`
+/// to return, and the log stops there.
+#[cfg(feature = "std")]
+pub struct InputLogWriter<W: Write> {
+    writer: W,
+    frame: u64,
+    /// Scratch space for encoding one event.
+    buf: Vec<u8>,
+    /// The first error writing an event, if any.
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W: Write> InputLogWriter<W> {
+    /// Start a log of a machine set up as `setup` in `writer` by writing its header.
+    pub fn new(mut writer: W, setup: &RunSetup) -> io::Result<Self> {
+        writer.write_all(&header(setup))?;
+
+        Ok(InputLogWriter {
+            writer,
+            frame: 0,
+            buf: Vec::new(),
+            error: None,
+        })
+    }
+
+    /// Append `event` to the log, unless an earlier event couldn't be written.
+    pub fn write(&mut self, event: InputEvent) {
+        if self.error.is_some() {
+            return;
+        }
+
+        self.buf.clear();
+        encode_event(&mut self.buf, &mut self.frame, event);
+        if let Err(err) = self.writer.write_all(&self.buf) {
+            self.error = Some(err);
+        }
+    }
+
+    /// End the log by recording where the run stopped, or return the first error `write` hit.
+    pub fn finish(&mut self, end: RunEnd) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        self.buf.clear();
+        encode_end(&mut self.buf, &mut self.frame, end);
+        self.writer.write_all(&self.buf)?;
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> InputLog {
+        let setup = RunSetup {
+            seed: 7,
+            clock_hz: 600,
+            quirks: Quirks {
+                shift_uses_vy: true,
+                wait_for_release: true,
+                ..Quirks::default()
+            },
+            rom_hash: 0x0123_4567_89AB_CDEF,
+        };
+        let mut log = InputLog::new(setup);
+        log.push(InputEvent::press(0xA, 0));
+        log.push(InputEvent::release(0xA, 3));
+        log.push(InputEvent::press(0x1, 203));
+        log.finish(RunEnd {
+            frame: 210,
+            state_hash: 42,
+        });
+        log
+    }
+
+    #[test]
+    fn encode_and_decode_roundtrip() {
+        let bytes = log().encode();
+        assert_eq!(bytes[..5], *b"C8IN\x02");
+        assert_eq!(bytes[13], 0b100100);
+        #[rustfmt::skip]
+        assert_eq!(bytes[HEADER_LEN..], [
+            0x00, 0x1A, // press A in frame 0
+            0x03, 0x0A, // release A 3 frames later
+            0xC8, 0x01, 0x11, // press 1 200 frames after that
+            0x07, END, 42, 0, 0, 0, 0, 0, 0, 0, // end 7 frames after that
+        ]);
+        assert_eq!(InputLog::decode(&bytes), Ok(log()));
+    }
+
+    #[test]
+    fn decode_rejects_damaged_logs() {
+        let bytes = log().encode();
+        assert_eq!(
+            InputLog::decode(b"C8XX\x02"),
+            Err(InputLogError::NotAnInputLog)
+        );
+        assert_eq!(
+            InputLog::decode(&bytes[..bytes.len() - 1]),
+            Err(InputLogError::Truncated)
+        );
+
+        let mut after_end = bytes.clone();
+        after_end.push(0);
+        assert_eq!(InputLog::decode(&after_end), Err(InputLogError::AfterEnd));
+
+        let mut bad_quirks = bytes;
+        bad_quirks[13] = 0x80;
+        assert_eq!(
+            InputLog::decode(&bad_quirks),
+            Err(InputLogError::UnknownQuirks(0x80))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn writer_matches_encode() {
+        let log = log();
+        let mut writer = InputLogWriter::new(Vec::new(), log.setup()).unwrap();
+        for &event in log.events() {
+            writer.write(event);
+        }
+        writer.finish(log.end().unwrap()).unwrap();
+
+        assert_eq!(writer.into_inner(), log.encode());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn writer_keeps_its_first_error_for_finish() {
+        /// Takes `room` bytes, then fails every write.
+        struct Full {
+            written: Vec<u8>,
+            room: usize,
+        }
+
+        impl Write for Full {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.written.len() + buf.len() > self.room {
+                    return Err(io::Error::new(io::ErrorKind::StorageFull, "full"));
+                }
+                self.written.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let log = log();
+        let full = Full {
+            written: Vec::new(),
+            room: header(log.setup()).len() + 1,
+        };
+        let mut writer = InputLogWriter::new(full, log.setup()).unwrap();
+        for &event in log.events() {
+            writer.write(event);
+        }
+        writer.writer.room = usize::MAX;
+        writer.write(InputEvent::press(0x2, 300));
+
+        let err = writer.finish(log.end().unwrap()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+        // Once the first event failed, nothing more was written, even with room again.
+        assert_eq!(writer.into_inner().written, header(log.setup()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn replay_reaches_the_recorded_state() {
+        use std::sync::{Arc, Mutex};
+
+        #[rustfmt::skip]
+        let rom = [
+            0x60, 0x05, // LD V0, 5
+            0xC2, 0xFF, // RND V2, 0xFF
+            0x83, 0x24, // ADD V3, V2
+            0xE0, 0xA1, // SKNP V0
+            0x74, 0x01, // ADD V4, 1
+            0x12, 0x02, // JP 0x202
+        ];
+        let setup = RunSetup {
+            seed: 99,
+            ..RunSetup::for_rom(&rom)
+        };
+
+        let log = Arc::new(Mutex::new(InputLog::new(setup)));
+        let mut cpu = setup.builder(&rom).build().unwrap();
+        let recorder = Arc::clone(&log);
+        cpu.on_input(move |event| recorder.lock().unwrap().push(event));
+        for frame in 0..30 {
+            match frame {
+                4 => cpu.press_key(5),
+                9 => cpu.release_key(5),
+                20 => cpu.queue_input(InputEvent::press(5, 21)),
+                _ => {}
+            }
+            cpu.run_frame().unwrap();
+        }
+        let mut log = log.lock().unwrap().clone();
+        log.finish(RunEnd::of(&cpu));
+        assert_eq!(log.events().len(), 3);
+
+        let replayed = log.replay(&rom).unwrap();
+        assert_eq!(RunEnd::of(&replayed), log.end().unwrap());
+
+        let mut missing_a_press = InputLog::new(setup);
+        missing_a_press.finish(log.end().unwrap());
+        let replayed = missing_a_press.replay(&rom).unwrap();
+        assert_ne!(replayed.state_hash(), cpu.state_hash());
+    }
+}
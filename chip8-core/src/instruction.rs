@@ -0,0 +1,307 @@
+use core::fmt;
+
+/// A decoded CHIP-8 instruction.
+///
+/// Variants are named after the mnemonics in Cowgod's Chip-8 Technical Reference. `x` and `y`
+/// are register indices, `kk` is an 8-bit immediate, `n` is a 4-bit immediate and `addr` is the
+/// 12-bit address also known as `nnn`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `0000`: stop execution.
+    Halt,
+    /// `0nnn`: call the host's machine routine at `nnn`.
+    Sys { addr: u16 },
+    /// `00Cn`: scroll the display down `n` pixels (SUPER-CHIP).
+    Scd { n: u8 },
+    /// `00E0`: clear the display.
+    Cls,
+    /// `00EE`: return from a subroutine.
+    Ret,
+    /// `00FB`: scroll the display right 4 pixels (SUPER-CHIP).
+    Scr,
+    /// `00FC`: scroll the display left 4 pixels (SUPER-CHIP).
+    Scl,
+    /// `00FE`: switch to the 64x32 lores screen (SUPER-CHIP).
+    Low,
+    /// `00FF`: switch to the 128x64 hires screen (SUPER-CHIP).
+    High,
+    /// `1nnn`: jump to `nnn`.
+    Jp { addr: u16 },
+    /// `2nnn`: call subroutine at `nnn`.
+    Call { addr: u16 },
+    /// `3xkk`: skip next instruction if `Vx = kk`.
+    SeByte { x: u8, kk: u8 },
+    /// `4xkk`: skip next instruction if `Vx != kk`.
+    SneByte { x: u8, kk: u8 },
+    /// `5xy0`: skip next instruction if `Vx = Vy`.
+    SeReg { x: u8, y: u8 },
+    /// `6xkk`: set `Vx = kk`.
+    LdByte { x: u8, kk: u8 },
+    /// `7xkk`: set `Vx = Vx + kk`.
+    AddByte { x: u8, kk: u8 },
+    /// `8xy0`: set `Vx = Vy`.
+    LdReg { x: u8, y: u8 },
+    /// `8xy1`: set `Vx = Vx OR Vy`.
+    Or { x: u8, y: u8 },
+    /// `8xy2`: set `Vx = Vx AND Vy`.
+    And { x: u8, y: u8 },
+    /// `8xy3`: set `Vx = Vx XOR Vy`.
+    Xor { x: u8, y: u8 },
+    /// `8xy4`: set `Vx = Vx + Vy`, set `VF = carry`.
+    AddReg { x: u8, y: u8 },
+    /// `8xy5`: set `Vx = Vx - Vy`, set `VF = NOT borrow`.
+    Sub { x: u8, y: u8 },
+    /// `8xy6`: set `Vx = Vx SHR 1`.
+    Shr { x: u8, y: u8 },
+    /// `8xy7`: set `Vx = Vy - Vx`, set `VF = NOT borrow`.
+    Subn { x: u8, y: u8 },
+    /// `8xyE`: set `Vx = Vx SHL 1`.
+    Shl { x: u8, y: u8 },
+    /// `9xy0`: skip next instruction if `Vx != Vy`.
+    SneReg { x: u8, y: u8 },
+    /// `Annn`: set `I = nnn`.
+    LdI { addr: u16 },
+    /// `Bnnn`: jump to `nnn + V0`.
+    JpV0 { x: u8, addr: u16 },
+    /// `Cxkk`: set `Vx = random byte AND kk`.
+    Rnd { x: u8, kk: u8 },
+    /// `Dxyn`: draw an `n`-byte sprite from `I` at `(Vx, Vy)`, set `VF = collision`. `Dxy0`
+    /// draws a 16x16 sprite from 32 bytes (SUPER-CHIP).
+    Drw { x: u8, y: u8, n: u8 },
+    /// `Ex9E`: skip next instruction if the key in `Vx` is pressed.
+    Skp { x: u8 },
+    /// `ExA1`: skip next instruction if the key in `Vx` is not pressed.
+    Sknp { x: u8 },
+    /// `Fn01`: draw to the bit planes in mask `n` (XO-CHIP).
+    Plane { n: u8 },
+    /// `F002`: load the 16 bytes at `I` as the audio pattern (XO-CHIP).
+    Audio,
+    /// `Fx07`: set `Vx = delay timer value`.
+    LdVxDt { x: u8 },
+    /// `Fx0A`: wait for a key press, store the value of the key in `Vx`.
+    LdVxK { x: u8 },
+    /// `Fx15`: set `delay timer = Vx`.
+    LdDtVx { x: u8 },
+    /// `Fx18`: set `sound timer = Vx`.
+    LdStVx { x: u8 },
+    /// `Fx1E`: set `I = I + Vx`.
+    AddI { x: u8 },
+    /// `Fx29`: set `I = location of sprite for digit Vx`.
+    LdF { x: u8 },
+    /// `Fx33`: store BCD representation of `Vx` at `I`, `I+1`, and `I+2`.
+    LdB { x: u8 },
+    /// `Fx3A`: set the audio pattern's pitch to `Vx` (XO-CHIP).
+    Pitch { x: u8 },
+    /// `Fx55`: store registers `V0` through `Vx` in memory starting at `I`.
+    LdIVx { x: u8 },
+    /// `Fx65`: read registers `V0` through `Vx` from memory starting at `I`.
+    LdVxI { x: u8 },
+    /// Any opcode this interpreter doesn't recognize.
+    Unknown(u16),
+}
+
+/// Formats the instruction in Cowgod's assembly syntax, e.g. `LD V1, #0A` or `DRW V0, V1, 5`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Halt => write!(f, "HALT"),
+            Sys { addr } => write!(f, "SYS #{:03X}", addr),
+            Scd { n } => write!(f, "SCD {}", n),
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            Scr => write!(f, "SCR"),
+            Scl => write!(f, "SCL"),
+            Low => write!(f, "LOW"),
+            High => write!(f, "HIGH"),
+            Jp { addr } => write!(f, "JP #{:03X}", addr),
+            Call { addr } => write!(f, "CALL #{:03X}", addr),
+            SeByte { x, kk } => write!(f, "SE V{:X}, #{:02X}", x, kk),
+            SneByte { x, kk } => write!(f, "SNE V{:X}, #{:02X}", x, kk),
+            SeReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            LdByte { x, kk } => write!(f, "LD V{:X}, #{:02X}", x, kk),
+            AddByte { x, kk } => write!(f, "ADD V{:X}, #{:02X}", x, kk),
+            LdReg { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddReg { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Shr { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Subn { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Shl { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SneReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LdI { addr } => write!(f, "LD I, #{:03X}", addr),
+            JpV0 { addr, .. } => write!(f, "JP V0, #{:03X}", addr),
+            Rnd { x, kk } => write!(f, "RND V{:X}, #{:02X}", x, kk),
+            Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Skp { x } => write!(f, "SKP V{:X}", x),
+            Sknp { x } => write!(f, "SKNP V{:X}", x),
+            Plane { n } => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LdVxDt { x } => write!(f, "LD V{:X}, DT", x),
+            LdVxK { x } => write!(f, "LD V{:X}, K", x),
+            LdDtVx { x } => write!(f, "LD DT, V{:X}", x),
+            LdStVx { x } => write!(f, "LD ST, V{:X}", x),
+            AddI { x } => write!(f, "ADD I, V{:X}", x),
+            LdF { x } => write!(f, "LD F, V{:X}", x),
+            LdB { x } => write!(f, "LD B, V{:X}", x),
+            Pitch { x } => write!(f, "PITCH V{:X}", x),
+            LdIVx { x } => write!(f, "LD [I], V{:X}", x),
+            LdVxI { x } => write!(f, "LD V{:X}, [I]", x),
+            Unknown(opcode) => write!(f, "DW #{:04X}", opcode),
+        }
+    }
+}
+
+/// Decode a raw opcode into an `Instruction`.
+pub fn decode(opcode: u16) -> Instruction {
+    use Instruction::*;
+
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+
+    let kk = (opcode & 0x00FF) as u8;
+    let op_minor = (opcode & 0x000F) as u8;
+    let addr = opcode & 0x0FFF; // Also known as `nnn`
+
+    match opcode {
+        0x0000 => Halt,
+        0x00C0..=0x00CF => Scd { n: op_minor },
+        0x00E0 => Cls,
+        0x00EE => Ret,
+        0x00FB => Scr,
+        0x00FC => Scl,
+        0x00FE => Low,
+        0x00FF => High,
+        0x0001..=0x0FFF => Sys { addr },
+        0x1000..=0x1FFF => Jp { addr },
+        0x2000..=0x2FFF => Call { addr },
+        0x3000..=0x3FFF => SeByte { x, kk },
+        0x4000..=0x4FFF => SneByte { x, kk },
+        0x5000..=0x5FFF if op_minor == 0 => SeReg { x, y },
+        0x6000..=0x6FFF => LdByte { x, kk },
+        0x7000..=0x7FFF => AddByte { x, kk },
+        0x8000..=0x8FFF => match op_minor {
+            0 => LdReg { x, y },
+            1 => Or { x, y },
+            2 => And { x, y },
+            3 => Xor { x, y },
+            4 => AddReg { x, y },
+            5 => Sub { x, y },
+            6 => Shr { x, y },
+            7 => Subn { x, y },
+            0xE => Shl { x, y },
+            _ => Unknown(opcode),
+        },
+        0x9000..=0x9FFF if op_minor == 0 => SneReg { x, y },
+        0xA000..=0xAFFF => LdI { addr },
+        0xB000..=0xBFFF => JpV0 { x, addr },
+        0xC000..=0xCFFF => Rnd { x, kk },
+        0xD000..=0xDFFF => Drw { x, y, n: op_minor },
+        0xE000..=0xEFFF => match kk {
+            0x9E => Skp { x },
+            0xA1 => Sknp { x },
+            _ => Unknown(opcode),
+        },
+        0xF000..=0xFFFF => match kk {
+            0x01 => Plane { n: x },
+            0x02 if x == 0 => Audio,
+            0x07 => LdVxDt { x },
+            0x0A => LdVxK { x },
+            0x15 => LdDtVx { x },
+            0x18 => LdStVx { x },
+            0x1E => AddI { x },
+            0x29 => LdF { x },
+            0x33 => LdB { x },
+            0x3A => Pitch { x },
+            0x55 => LdIVx { x },
+            0x65 => LdVxI { x },
+            _ => Unknown(opcode),
+        },
+        _ => Unknown(opcode),
+    }
+}
+
+/// Encode an `Instruction` back into its opcode, so that `decode(encode(i)) == i` for anything
+/// `decode` returned.
+pub fn encode(instruction: Instruction) -> u16 {
+    use Instruction::*;
+
+    let xy = |base: u16, x: u8, y: u8| base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4;
+    let xkk = |base: u16, x: u8, kk: u8| base | (x as u16 & 0xF) << 8 | kk as u16;
+
+    match instruction {
+        Halt => 0x0000,
+        Sys { addr } => addr & 0x0FFF,
+        Scd { n } => 0x00C0 | (n as u16 & 0xF),
+        Cls => 0x00E0,
+        Ret => 0x00EE,
+        Scr => 0x00FB,
+        Scl => 0x00FC,
+        Low => 0x00FE,
+        High => 0x00FF,
+        Jp { addr } => 0x1000 | (addr & 0x0FFF),
+        Call { addr } => 0x2000 | (addr & 0x0FFF),
+        SeByte { x, kk } => xkk(0x3000, x, kk),
+        SneByte { x, kk } => xkk(0x4000, x, kk),
+        SeReg { x, y } => xy(0x5000, x, y),
+        LdByte { x, kk } => xkk(0x6000, x, kk),
+        AddByte { x, kk } => xkk(0x7000, x, kk),
+        LdReg { x, y } => xy(0x8000, x, y),
+        Or { x, y } => xy(0x8001, x, y),
+        And { x, y } => xy(0x8002, x, y),
+        Xor { x, y } => xy(0x8003, x, y),
+        AddReg { x, y } => xy(0x8004, x, y),
+        Sub { x, y } => xy(0x8005, x, y),
+        Shr { x, y } => xy(0x8006, x, y),
+        Subn { x, y } => xy(0x8007, x, y),
+        Shl { x, y } => xy(0x800E, x, y),
+        SneReg { x, y } => xy(0x9000, x, y),
+        LdI { addr } => 0xA000 | (addr & 0x0FFF),
+        // `addr` already holds `x` in its top nibble.
+        JpV0 { addr, .. } => 0xB000 | (addr & 0x0FFF),
+        Rnd { x, kk } => xkk(0xC000, x, kk),
+        Drw { x, y, n } => xy(0xD000, x, y) | (n as u16 & 0xF),
+        Skp { x } => xkk(0xE000, x, 0x9E),
+        Sknp { x } => xkk(0xE000, x, 0xA1),
+        Plane { n } => xkk(0xF000, n, 0x01),
+        Audio => 0xF002,
+        LdVxDt { x } => xkk(0xF000, x, 0x07),
+        LdVxK { x } => xkk(0xF000, x, 0x0A),
+        LdDtVx { x } => xkk(0xF000, x, 0x15),
+        LdStVx { x } => xkk(0xF000, x, 0x18),
+        AddI { x } => xkk(0xF000, x, 0x1E),
+        LdF { x } => xkk(0xF000, x, 0x29),
+        LdB { x } => xkk(0xF000, x, 0x33),
+        Pitch { x } => xkk(0xF000, x, 0x3A),
+        LdIVx { x } => xkk(0xF000, x, 0x55),
+        LdVxI { x } => xkk(0xF000, x, 0x65),
+        Unknown(opcode) => opcode,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_inverts_decode_for_every_opcode() {
+        for opcode in 0..=u16::MAX {
+            let instruction = decode(opcode);
+            assert_eq!(encode(instruction), opcode, "{:?}", instruction);
+            assert_eq!(decode(encode(instruction)), instruction);
+        }
+    }
+
+    #[test]
+    fn decode_reads_the_operands() {
+        assert_eq!(decode(0x5120), Instruction::SeReg { x: 1, y: 2 });
+        assert_eq!(decode(0x5121), Instruction::Unknown(0x5121));
+        assert_eq!(decode(0x9AB0), Instruction::SneReg { x: 0xA, y: 0xB });
+        assert_eq!(decode(0xD34F), Instruction::Drw { x: 3, y: 4, n: 0xF });
+        assert_eq!(decode(0xB2F0), Instruction::JpV0 { x: 2, addr: 0x2F0 });
+        assert_eq!(decode(0xF265), Instruction::LdVxI { x: 2 });
+    }
+}
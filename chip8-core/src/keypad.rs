@@ -0,0 +1,63 @@
+/// Which of the 16 hexadecimal keys are currently held down.
+///
+/// It doesn't care where presses come from: a frontend can call `press` and `release` as key
+/// events arrive, or poll its input source every frame and hand over the whole state at once
+/// with `set_mask`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Keypad {
+    /// One bit per key, with key 0 in the low bit.
+    mask: u16,
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A keypad with the keys in `mask` held down, one bit per key with key 0 in the low bit.
+    pub fn from_mask(mask: u16) -> Self {
+        Keypad { mask }
+    }
+
+    /// Hold `key` down. Only the low nibble of `key` is used.
+    pub fn press(&mut self, key: u8) {
+        self.set(key, true);
+    }
+
+    /// Let go of `key`. Only the low nibble of `key` is used.
+    pub fn release(&mut self, key: u8) {
+        self.set(key, false);
+    }
+
+    /// Set whether `key` is held down. Only the low nibble of `key` is used.
+    pub fn set(&mut self, key: u8, down: bool) {
+        let bit = 1 << (key & 0xF);
+        if down {
+            self.mask |= bit;
+        } else {
+            self.mask &= !bit;
+        }
+    }
+
+    /// Whether `key` is held down. Only the low nibble of `key` is used.
+    pub fn is_down(&self, key: u8) -> bool {
+        self.mask & 1 << (key & 0xF) != 0
+    }
+
+    /// The keys held down, one bit per key with key 0 in the low bit.
+    pub fn mask(&self) -> u16 {
+        self.mask
+    }
+
+    /// Hold down exactly the keys in `mask`, releasing the rest.
+    pub fn set_mask(&mut self, mask: u16) {
+        self.mask = mask;
+    }
+
+    /// The keys held down, lowest first.
+    pub fn pressed(&self) -> impl Iterator<Item = u8> {
+        let mask = self.mask;
+        (0..16).filter(move |&key| mask & 1 << key != 0)
+    }
+}
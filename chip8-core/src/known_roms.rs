@@ -0,0 +1,277 @@
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+use core::error::Error;
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use crate::cpu::Cpu;
+use crate::cpu::{Quirks, DEFAULT_CLOCK_HZ};
+use crate::hash::rom_sha1;
+#[cfg(feature = "alloc")]
+use crate::rng::Rng;
+
+/// A ROM a `KnownRoms` database has the settings for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownRom<'a> {
+    /// The SHA-1 of the ROM file.
+    pub sha1: [u8; 20],
+    pub title: &'a str,
+    /// The interpreter the game needs to behave like.
+    pub quirks: Quirks,
+    /// How many instructions per second it plays right at.
+    pub clock_hz: u32,
+}
+
+/// A database of well-known ROMs and the quirks and clock speed each needs, so that someone
+/// playing one doesn't have to know that it, say, draws with sprites clipped at the edges.
+///
+/// The database is text, one ROM per line, as `SHA1 PROFILE IPS TITLE`; `BUILT_IN` spells out the
+/// format. Frontends call `settings` when they load a ROM, on a user's file or on `BUILT_IN`
+/// itself, and `RomSettings` does it for them, e.g. in `chip8-frontend`'s `Controls::load_rom`.
+#[derive(Debug, Clone, Copy)]
+pub struct KnownRoms<'a> {
+    text: &'a str,
+}
+
+impl KnownRoms<'static> {
+    /// The ROMs this crate ships settings for.
+    pub const BUILT_IN: KnownRoms<'static> = KnownRoms {
+        text: include_str!("known_roms.txt"),
+    };
+}
+
+impl<'a> KnownRoms<'a> {
+    /// A database from `text`, e.g. a user's file, checking every line of it up front so that a
+    /// typo isn't just a ROM that never matches.
+    pub fn parse(text: &'a str) -> Result<Self, KnownRomsError> {
+        for (i, line) in text.lines().enumerate() {
+            if let Some(Err(reason)) = parse_line(line) {
+                return Err(KnownRomsError {
+                    line: i + 1,
+                    reason,
+                });
+            }
+        }
+
+        Ok(KnownRoms { text })
+    }
+
+    /// Every ROM in the database, in the order it lists them.
+    pub fn iter(&self) -> impl Iterator<Item = KnownRom<'a>> {
+        self.text
+            .lines()
+            .filter_map(parse_line)
+            .filter_map(Result::ok)
+    }
+
+    /// The settings for the ROM with SHA-1 `sha1`, if the database has it.
+    pub fn get(&self, sha1: &[u8; 20]) -> Option<KnownRom<'a>> {
+        self.iter().find(|known| known.sha1 == *sha1)
+    }
+
+    /// The settings for `rom`, the contents of a ROM file, if the database has it.
+    pub fn lookup(&self, rom: &[u8]) -> Option<KnownRom<'a>> {
+        self.get(&rom_sha1(rom))
+    }
+
+    /// The quirks and clock speed to play `rom` with: `quirks` and `clock_hz` where they're
+    /// given, e.g. on the command line, and otherwise what this database has for the ROM, then
+    /// what `BUILT_IN` has, then the defaults.
+    pub fn settings(
+        &self,
+        rom: &[u8],
+        quirks: Option<Quirks>,
+        clock_hz: Option<u32>,
+    ) -> (Quirks, u32) {
+        let sha1 = rom_sha1(rom);
+        let known = self.get(&sha1).or_else(|| KnownRoms::BUILT_IN.get(&sha1));
+
+        (
+            quirks
+                .or(known.map(|known| known.quirks))
+                .unwrap_or_default(),
+            clock_hz
+                .or(known.map(|known| known.clock_hz))
+                .unwrap_or(DEFAULT_CLOCK_HZ),
+        )
+    }
+}
+
+/// The quirks and clock speed a frontend plays every ROM it loads with, the first one and any
+/// started later, e.g. from a library: the ones it was told to use, and otherwise what a user's
+/// `KnownRoms` database or `KnownRoms::BUILT_IN` has for the ROM.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct RomSettings {
+    /// A user's database, already checked by `KnownRoms::parse`.
+    known_roms: String,
+    quirks: Option<Quirks>,
+    clock_hz: Option<u32>,
+}
+
+#[cfg(feature = "alloc")]
+impl RomSettings {
+    /// Play every ROM with `quirks` and at `clock_hz`, where they're given.
+    pub fn new(quirks: Option<Quirks>, clock_hz: Option<u32>) -> Self {
+        RomSettings {
+            known_roms: String::new(),
+            quirks,
+            clock_hz,
+        }
+    }
+
+    /// Look ROMs up in the database `text` before `KnownRoms::BUILT_IN`.
+    pub fn with_known_roms(mut self, text: String) -> Result<Self, KnownRomsError> {
+        KnownRoms::parse(&text)?;
+        self.known_roms = text;
+
+        Ok(self)
+    }
+
+    /// The quirks and clock speed to play `rom` with, as `KnownRoms::settings` picks them.
+    pub fn for_rom(&self, rom: &[u8]) -> (Quirks, u32) {
+        let known_roms = KnownRoms {
+            text: &self.known_roms,
+        };
+        known_roms.settings(rom, self.quirks, self.clock_hz)
+    }
+
+    /// Set `cpu` up to play `rom` as `for_rom` says.
+    pub fn apply<R: Rng, const MEM: usize, const STACK: usize>(
+        &self,
+        cpu: &mut Cpu<R, MEM, STACK>,
+        rom: &[u8],
+    ) {
+        let (quirks, clock_hz) = self.for_rom(rom);
+        *cpu.quirks_mut() = quirks;
+        cpu.set_clock_hz(clock_hz);
+    }
+}
+
+/// A line of a `KnownRoms` database, or `None` for a blank line or a comment.
+fn parse_line(line: &str) -> Option<Result<KnownRom<'_>, &'static str>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    Some(parse_entry(line))
+}
+
+fn parse_entry(line: &str) -> Result<KnownRom<'_>, &'static str> {
+    let mut fields = line.splitn(4, char::is_whitespace);
+    let (Some(sha1), Some(profile), Some(ips), Some(title)) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        return Err("expected SHA1 PROFILE IPS TITLE");
+    };
+
+    Ok(KnownRom {
+        sha1: parse_sha1(sha1).ok_or("the SHA-1 should be 40 hex digits")?,
+        quirks: Quirks::named(profile)
+            .ok_or("the profile should be modern, vip, schip or xo-chip")?,
+        clock_hz: ips
+            .parse()
+            .ok()
+            .filter(|&hz| hz > 0)
+            .ok_or("the instructions per second should be a whole number above 0")?,
+        title: title.trim(),
+    })
+}
+
+fn parse_sha1(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut sha1 = [0; 20];
+    for (byte, pair) in sha1.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        let pair = core::str::from_utf8(pair).ok()?;
+        *byte = u8::from_str_radix(pair, 16).ok()?;
+    }
+    Some(sha1)
+}
+
+/// Why a `KnownRoms` database couldn't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KnownRomsError {
+    /// The line it went wrong on, counting from 1.
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for KnownRomsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl Error for KnownRomsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database with `b"abc"`, whose SHA-1 this is, standing in for a ROM.
+    const DB: &str = "# A comment\n\n\
+                      a9993e364706816aba3e25717850c26c9cd0d89d schip 1000 The Alphabet\n";
+
+    #[test]
+    fn lookup_finds_rom_by_hash() {
+        let known_roms = KnownRoms::parse(DB).unwrap();
+
+        let known = known_roms.lookup(b"abc").unwrap();
+        assert_eq!(known.title, "The Alphabet");
+        assert_eq!(known.quirks, Quirks::SCHIP);
+        assert_eq!(known.clock_hz, 1000);
+        assert_eq!(known_roms.lookup(b"abd"), None);
+        assert_eq!(known_roms.iter().count(), 1);
+    }
+
+    #[test]
+    fn parse_reports_the_line_that_is_wrong() {
+        let err = KnownRoms::parse("# fine\nabc vip 600 Short").unwrap_err();
+        assert_eq!(
+            err,
+            KnownRomsError {
+                line: 2,
+                reason: "the SHA-1 should be 40 hex digits"
+            }
+        );
+
+        for text in [
+            "a9993e364706816aba3e25717850c26c9cd0d89d cosmac 600 x",
+            "a9993e364706816aba3e25717850c26c9cd0d89d vip 0 x",
+            "a9993e364706816aba3e25717850c26c9cd0d89d vip 600",
+        ] {
+            assert_eq!(KnownRoms::parse(text).unwrap_err().line, 1);
+        }
+    }
+
+    #[test]
+    fn built_in_entries_parse() {
+        KnownRoms::parse(KnownRoms::BUILT_IN.text).unwrap();
+    }
+
+    #[test]
+    fn settings_prefer_what_was_given() {
+        let known_roms = KnownRoms::parse(DB).unwrap();
+
+        assert_eq!(
+            known_roms.settings(b"abc", None, None),
+            (Quirks::SCHIP, 1000)
+        );
+        assert_eq!(
+            known_roms.settings(b"abc", Some(Quirks::VIP), None),
+            (Quirks::VIP, 1000)
+        );
+        assert_eq!(
+            known_roms.settings(b"abc", None, Some(700)),
+            (Quirks::SCHIP, 700)
+        );
+        assert_eq!(
+            known_roms.settings(b"xyz", None, None),
+            (Quirks::default(), DEFAULT_CLOCK_HZ)
+        );
+    }
+}
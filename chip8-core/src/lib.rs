@@ -0,0 +1,110 @@
+//! A CHIP-8 interpreter.
+//!
+//! `Cpu` holds the whole machine: registers, memory, the stack, the framebuffer, the keypad and
+//! the timers. Construct one with `Chip8::builder()` and call `run()` once per frame, or
+//! `step()` through it one instruction at a time.
+//!
+//! The crate is `no_std` when built without default features, and needs no allocator at all.
+//! The features add:
+//!
+//! - `alloc`: callbacks (hooks and `0NNN` machine routines), `InputLog` and `SaveSlots`.
+//! - `std`, on by default and implying `alloc`: `InputLogWriter` and `Cpu::input_sender`.
+//! - `serde`: serializable machine state, for savestates and snapshot testing.
+//! - `heapless`: `Cpu::take_events`.
+//! - `embedded-graphics`: `DrawTargetDisplay`, which shows the display on any `embedded-graphics`
+//!   `DrawTarget`, e.g. a microcontroller's SPI screen.
+//!
+//! `Debouncer`, in every build, cleans up the input from a hardware keypad, and `PatternPlayer`
+//! turns XO-CHIP audio patterns into samples for a frontend's sound device.
+//!
+//! `step` and `run_frame` never allocate, whatever the features, except that
+//! `StackPolicy::Grow` spills deep call stacks onto the heap.
+//!
+//! `Cpu` is `Send + Sync`, and with `std`, `Cpu::input_sender` lets any thread queue timestamped
+//! input for the next frame boundary.
+//!
+//! `Quirks::named` has the interpreter profiles frontends offer by name, `KnownRoms` has the
+//! profile and clock speed well-known ROMs need, and `encode` turns an `Instruction` back into its
+//! opcode, for assemblers.
+//!
+//! Frontends live in their own crates (`chip8-cli`, `chip8-gui`) so that their dependencies
+//! never reach embedded or WASM users of the core. What they share, like hotkeys, keymaps,
+//! palettes, frame pacing, ROM libraries, file watching and remote input, is in
+//! `chip8-frontend`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Tests can collect into `Vec`s whatever the features.
+#[cfg(any(feature = "alloc", test))]
+extern crate alloc;
+
+mod audio;
+mod builder;
+mod cpu;
+mod debounce;
+mod display;
+mod error;
+mod events;
+#[cfg(feature = "embedded-graphics")]
+mod graphics;
+mod hash;
+mod hooks;
+mod input;
+#[cfg(feature = "alloc")]
+mod input_log;
+mod instruction;
+mod keypad;
+mod known_roms;
+mod memory;
+mod peripherals;
+mod rng;
+mod rom;
+mod save_state;
+#[cfg(feature = "serde")]
+mod serde_arrays;
+mod stack;
+mod timers;
+mod trace;
+
+pub use audio::{AudioPattern, PatternPlayer, DEFAULT_PITCH};
+pub use builder::{Chip8, Chip8Builder, PROGRAM_START};
+#[cfg(feature = "alloc")]
+pub use cpu::MachineRoutine;
+pub use cpu::{
+    Cpu, Quirks, RunExit, StepOutcome, DEFAULT_CLOCK_HZ, DEFAULT_CYCLES_PER_FRAME,
+    DEFAULT_STACK_DEPTH,
+};
+pub use debounce::{DebounceConfig, Debouncer};
+pub use display::{
+    DirtyRows, Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH, PLANES,
+};
+pub use error::Chip8Error;
+pub use events::{EmulatorEvent, EVENT_QUEUE_CAPACITY};
+#[cfg(feature = "embedded-graphics")]
+pub use graphics::DrawTargetDisplay;
+pub use hash::{rom_hash, rom_sha1};
+#[cfg(feature = "std")]
+pub use input::InputSender;
+pub use input::{InputEvent, INPUT_QUEUE_CAPACITY};
+#[cfg(feature = "std")]
+pub use input_log::InputLogWriter;
+#[cfg(feature = "alloc")]
+pub use input_log::{InputLog, InputLogError, RunEnd, RunSetup, INPUT_LOG_MAGIC};
+pub use instruction::{decode, encode, Instruction};
+pub use keypad::Keypad;
+#[cfg(feature = "alloc")]
+pub use known_roms::RomSettings;
+pub use known_roms::{KnownRom, KnownRoms, KnownRomsError};
+pub use memory::{Memory, MemoryError, DEFAULT_MEMORY_SIZE, FONT_ADDR, PROTECTED_END};
+pub use peripherals::{
+    AudioSink, DisplaySink, KeyInput, NullAudio, NullDisplay, NullKeyInput, PeripheralSet,
+    Peripherals,
+};
+pub use rng::{Rng, XorShiftRng};
+pub use rom::{detect_variant, validate_rom, RomWarning, Variant};
+pub use save_state::SaveState;
+#[cfg(feature = "alloc")]
+pub use save_state::{SaveSlots, SAVE_SLOTS};
+pub use stack::StackPolicy;
+pub use timers::Timers;
+pub use trace::{CpuDelta, Instructions};
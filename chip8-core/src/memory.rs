@@ -0,0 +1,209 @@
+/// The size of a classic CHIP-8's memory. XO-CHIP machines have 64K instead.
+pub const DEFAULT_MEMORY_SIZE: usize = 0x1000;
+
+/// Where the built-in font lives in memory.
+pub const FONT_ADDR: usize = 0x050;
+
+/// Each glyph is 5 bytes tall.
+pub const FONT_GLYPH_SIZE: usize = 5;
+
+/// The standard 4x5 sprites for the hexadecimal digits `0` through `F`.
+const FONT: [u8; 16 * FONT_GLYPH_SIZE] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Where the interpreter area ends. `Memory::set_write_protected` guards everything below this.
+pub const PROTECTED_END: usize = 0x200;
+
+/// Why a memory access failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryError {
+    /// `addr` is past the end of memory.
+    OutOfBounds { addr: usize },
+    /// `addr` is in the write-protected interpreter area.
+    WriteProtected { addr: usize },
+}
+
+/// The machine's `SIZE` bytes of RAM, with the built-in font loaded at `FONT_ADDR`.
+///
+/// Programs access it through `read` and `write`, which report bad addresses as errors rather
+/// than panicking. The interpreter area below `PROTECTED_END`, which holds the font, can
+/// optionally be made read-only to catch ROMs that scribble over it.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Memory<const SIZE: usize = DEFAULT_MEMORY_SIZE> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays"))]
+    bytes: [u8; SIZE],
+    write_protected: bool,
+}
+
+impl<const SIZE: usize> Memory<SIZE> {
+    /// Panics if `SIZE` is too small to hold the font and a program at 0x200.
+    pub fn new() -> Self {
+        assert!(
+            SIZE > PROTECTED_END,
+            "memory must be larger than 0x200 bytes"
+        );
+
+        let mut memory = Memory {
+            bytes: [0; SIZE],
+            write_protected: false,
+        };
+        memory.restore_font();
+
+        memory
+    }
+
+    /// Zero everything except the font, keeping the write protection setting.
+    pub fn clear(&mut self) {
+        self.bytes = [0; SIZE];
+        self.restore_font();
+    }
+
+    /// Rewrite the built-in font, in case a program overwrote it.
+    pub fn restore_font(&mut self) {
+        self.bytes[FONT_ADDR..FONT_ADDR + FONT.len()].copy_from_slice(&FONT);
+    }
+
+    pub fn len(&self) -> usize {
+        SIZE
+    }
+
+    pub fn is_empty(&self) -> bool {
+        SIZE == 0
+    }
+
+    /// Make writes below `PROTECTED_END` fail with `MemoryError::WriteProtected`.
+    pub fn set_write_protected(&mut self, protected: bool) {
+        self.write_protected = protected;
+    }
+
+    pub fn read(&self, addr: usize) -> Result<u8, MemoryError> {
+        self.bytes
+            .get(addr)
+            .copied()
+            .ok_or(MemoryError::OutOfBounds { addr })
+    }
+
+    /// Borrow the `len` bytes starting at `addr`.
+    pub fn read_slice(&self, addr: usize, len: usize) -> Result<&[u8], MemoryError> {
+        self.check_bounds(addr, len)?;
+
+        Ok(&self.bytes[addr..addr + len])
+    }
+
+    pub fn write(&mut self, addr: usize, value: u8) -> Result<(), MemoryError> {
+        self.write_slice(addr, &[value])
+    }
+
+    /// Copy `values` into memory starting at `addr`. Nothing is written if any byte would land
+    /// out of bounds or in protected memory.
+    pub fn write_slice(&mut self, addr: usize, values: &[u8]) -> Result<(), MemoryError> {
+        self.check_bounds(addr, values.len())?;
+
+        if self.write_protected && addr < PROTECTED_END && !values.is_empty() {
+            return Err(MemoryError::WriteProtected { addr });
+        }
+
+        self.bytes[addr..addr + values.len()].copy_from_slice(values);
+
+        Ok(())
+    }
+
+    /// The raw contents of memory, for debuggers and frontends.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The raw contents of memory, bypassing write protection. Meant for the host, e.g. to patch
+    /// a ROM, rather than for anything the emulated program does.
+    pub fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    fn check_bounds(&self, addr: usize, len: usize) -> Result<(), MemoryError> {
+        if addr.checked_add(len).is_none_or(|end| end > SIZE) {
+            Err(MemoryError::OutOfBounds {
+                addr: addr.max(SIZE),
+            })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<const SIZE: usize> Default for Memory<SIZE> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accesses_past_the_end_are_errors() {
+        let mut memory = Memory::<0x300>::new();
+        assert_eq!(memory.read(0x2FF), Ok(0));
+        assert_eq!(
+            memory.read(0x300),
+            Err(MemoryError::OutOfBounds { addr: 0x300 })
+        );
+        assert_eq!(
+            memory.read_slice(0x2FE, 3),
+            Err(MemoryError::OutOfBounds { addr: 0x300 })
+        );
+        assert_eq!(
+            memory.read_slice(usize::MAX, 2),
+            Err(MemoryError::OutOfBounds { addr: usize::MAX })
+        );
+        assert_eq!(
+            memory.write_slice(0x2FF, &[1, 2]),
+            Err(MemoryError::OutOfBounds { addr: 0x300 })
+        );
+        assert_eq!(memory.read(0x2FF), Ok(0));
+    }
+
+    #[test]
+    fn write_protection_guards_the_interpreter_area() {
+        let mut memory = Memory::<DEFAULT_MEMORY_SIZE>::new();
+        assert_eq!(memory.read_slice(FONT_ADDR, FONT.len()), Ok(&FONT[..]));
+        memory.write(FONT_ADDR, 0xFF).unwrap();
+
+        memory.set_write_protected(true);
+        assert_eq!(
+            memory.write(FONT_ADDR + 1, 0xFF),
+            Err(MemoryError::WriteProtected {
+                addr: FONT_ADDR + 1
+            })
+        );
+        assert_eq!(
+            memory.write_slice(PROTECTED_END - 1, &[1, 2]),
+            Err(MemoryError::WriteProtected {
+                addr: PROTECTED_END - 1
+            })
+        );
+        assert_eq!(memory.read(PROTECTED_END), Ok(0));
+        memory.write(PROTECTED_END, 1).unwrap();
+
+        // The host can still get at it, e.g. to put the font back.
+        memory.restore_font();
+        assert_eq!(memory.read_slice(FONT_ADDR, FONT.len()), Ok(&FONT[..]));
+    }
+}
@@ -0,0 +1,135 @@
+use crate::audio::AudioPattern;
+use crate::display::{DirtyRows, Framebuffer};
+
+/// Somewhere to show the framebuffer, e.g. a window or a terminal.
+pub trait DisplaySink {
+    /// Called at the end of a frame in which the display changed.
+    fn present(&mut self, _framebuffer: &Framebuffer) {}
+
+    /// Like `present`, but also told which rows changed since the last frame presented, for
+    /// displays where a full redraw is expensive. By default, it just calls `present`.
+    fn present_rows(&mut self, framebuffer: &Framebuffer, _dirty: DirtyRows) {
+        self.present(framebuffer);
+    }
+}
+
+/// A source of keypad input, e.g. a keyboard or a gamepad.
+///
+/// This is named `KeyInput` rather than `Keypad` because `Keypad` is the core's own model of
+/// which keys are held down, which is updated from this at the start of every frame.
+pub trait KeyInput {
+    /// Whether hex `key` (0x0 through 0xF) is currently held down.
+    fn is_down(&mut self, _key: u8) -> bool {
+        false
+    }
+}
+
+/// Something that can play the buzzer.
+pub trait AudioSink {
+    /// Called when the sound timer becomes nonzero.
+    fn start_beep(&mut self) {}
+
+    /// Called when the sound timer reaches zero.
+    fn stop_beep(&mut self) {}
+
+    /// Called when an XO-CHIP program loads a new sample pattern with `F002` or changes its
+    /// pitch with `FX3A`, before any beep that should play it, and with `None` when a reset
+    /// goes back to a plain tone. A `PatternPlayer` turns the pattern into samples. Sinks that
+    /// can only play a tone can ignore this.
+    fn set_pattern(&mut self, _pattern: Option<AudioPattern>) {}
+
+    /// The rate to make samples for `push_samples` at, for sinks that would rather be handed
+    /// the buzzer's sound than make their own. `None`, the default, leaves them to it.
+    fn sample_rate(&self) -> Option<u32> {
+        None
+    }
+
+    /// Called at the end of every frame, when `sample_rate` is set, with that frame's 60th of a
+    /// second of sound, from -1.0 to 1.0, possibly over several calls: the program's audio
+    /// pattern, or `AudioPattern::BUZZER` if it hasn't loaded one, while the sound timer runs,
+    /// and silence otherwise.
+    fn push_samples(&mut self, _samples: &[f32]) {}
+}
+
+/// Everything `Cpu::run_frame_with` talks to. Implemented for any type implementing all of
+/// `DisplaySink`, `KeyInput` and `AudioSink`, so a frontend can be a single struct.
+pub trait Peripherals: DisplaySink + KeyInput + AudioSink {}
+
+impl<T: DisplaySink + KeyInput + AudioSink> Peripherals for T {}
+
+/// A display that throws frames away.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullDisplay;
+
+impl DisplaySink for NullDisplay {}
+
+/// Input with no keys ever pressed.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullKeyInput;
+
+impl KeyInput for NullKeyInput {}
+
+/// Silence, for headless runs and frontends without a sound device.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {}
+
+/// Combines separate display, input and audio implementations into one `Peripherals`.
+///
+/// Fields default to the null implementations, so a frontend only needs to provide the parts it
+/// cares about.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PeripheralSet<D = NullDisplay, K = NullKeyInput, A = NullAudio> {
+    pub display: D,
+    pub keys: K,
+    pub audio: A,
+}
+
+impl<D, K, A> PeripheralSet<D, K, A> {
+    pub fn new(display: D, keys: K, audio: A) -> Self {
+        PeripheralSet {
+            display,
+            keys,
+            audio,
+        }
+    }
+}
+
+impl<D: DisplaySink, K, A> DisplaySink for PeripheralSet<D, K, A> {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        self.display.present(framebuffer);
+    }
+
+    fn present_rows(&mut self, framebuffer: &Framebuffer, dirty: DirtyRows) {
+        self.display.present_rows(framebuffer, dirty);
+    }
+}
+
+impl<D, K: KeyInput, A> KeyInput for PeripheralSet<D, K, A> {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys.is_down(key)
+    }
+}
+
+impl<D, K, A: AudioSink> AudioSink for PeripheralSet<D, K, A> {
+    fn start_beep(&mut self) {
+        self.audio.start_beep();
+    }
+
+    fn stop_beep(&mut self) {
+        self.audio.stop_beep();
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.audio.set_pattern(pattern);
+    }
+
+    fn sample_rate(&self) -> Option<u32> {
+        self.audio.sample_rate()
+    }
+
+    fn push_samples(&mut self, samples: &[f32]) {
+        self.audio.push_samples(samples);
+    }
+}
@@ -0,0 +1,44 @@
+/// A source of random bytes for `CXKK`.
+///
+/// Implement this to control how random numbers are produced, e.g. to replay a run exactly.
+pub trait Rng {
+    /// Return the next random byte.
+    fn next_u8(&mut self) -> u8;
+
+    /// Go back to the start of the sequence, as `Cpu::reset` does. Generators that can't, e.g.
+    /// ones reading a hardware entropy source, carry on where they were.
+    fn restart(&mut self) {}
+}
+
+/// A small, seedable xorshift32 generator. The same seed always produces the same sequence.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct XorShiftRng {
+    seed: u32,
+    state: u32,
+}
+
+impl XorShiftRng {
+    pub fn new(seed: u32) -> Self {
+        // xorshift gets stuck at zero, so nudge a zero seed to something else.
+        let state = if seed == 0 { 0x2545_F491 } else { seed };
+
+        XorShiftRng { seed, state }
+    }
+}
+
+impl Rng for XorShiftRng {
+    fn next_u8(&mut self) -> u8 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        (x >> 24) as u8
+    }
+
+    fn restart(&mut self) {
+        *self = XorShiftRng::new(self.seed);
+    }
+}
@@ -0,0 +1,183 @@
+use core::fmt;
+
+use crate::error::Chip8Error;
+use crate::instruction::{decode, Instruction};
+
+/// Check a ROM file before playing it in `max` bytes of memory, e.g.
+/// `DEFAULT_MEMORY_SIZE - PROGRAM_START` on a machine from `Chip8::builder()`.
+///
+/// An empty ROM is `Chip8Error::EmptyRom` and one that doesn't fit is `Chip8Error::RomTooLarge`,
+/// caught here with the file's name to hand rather than at load time. Anything else loads, but
+/// comes back with what looks wrong about it, for a frontend to warn about.
+pub fn validate_rom(
+    rom: &[u8],
+    max: usize,
+) -> Result<impl Iterator<Item = RomWarning>, Chip8Error> {
+    if rom.is_empty() {
+        return Err(Chip8Error::EmptyRom);
+    }
+    if rom.len() > max {
+        return Err(Chip8Error::RomTooLarge {
+            size: rom.len(),
+            max,
+        });
+    }
+
+    let odd = (!rom.len().is_multiple_of(2)).then_some(RomWarning::OddLength { size: rom.len() });
+    let opcode = u16::from_be_bytes([rom[0], rom.get(1).copied().unwrap_or(0)]);
+    let garbage =
+        matches!(decode(opcode), Instruction::Unknown(_)).then_some(RomWarning::NotCode { opcode });
+
+    Ok(odd.into_iter().chain(garbage))
+}
+
+/// Something about a ROM that `validate_rom` lets through, but that suggests it isn't a
+/// CHIP-8 program, or isn't all there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomWarning {
+    /// The ROM is `size` bytes, which isn't a whole number of instructions. Its last byte is
+    /// only ever data.
+    OddLength { size: usize },
+    /// The first instruction is `opcode`, which isn't one, so the program will stop as soon as
+    /// it starts.
+    NotCode { opcode: u16 },
+}
+
+impl fmt::Display for RomWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomWarning::OddLength { size } => {
+                write!(
+                    f,
+                    "the ROM is {} bytes, an odd number, so its last byte can't be an instruction",
+                    size
+                )
+            }
+            RomWarning::NotCode { opcode } => write!(
+                f,
+                "the ROM starts with {:04x}, which isn't an instruction, so it may not be a \
+                 CHIP-8 program",
+                opcode
+            ),
+        }
+    }
+}
+
+/// The extension of the CHIP-8 family a ROM is written for, as far as `detect_variant` can
+/// tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variant {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Variant {
+    /// The name it usually goes by, e.g. `SUPER-CHIP`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Chip8 => "CHIP-8",
+            Variant::SuperChip => "SUPER-CHIP",
+            Variant::XoChip => "XO-CHIP",
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Guess which variant `rom` is written for from the instructions in it: XO-CHIP if any are
+/// XO-CHIP's own, otherwise SUPER-CHIP if any are its own, and otherwise plain CHIP-8.
+///
+/// Every pair of bytes counts, since telling code from data would mean running the program,
+/// so the odd sprite that happens to look like an extension's opcode can give a false
+/// positive.
+pub fn detect_variant(rom: &[u8]) -> Variant {
+    rom.chunks_exact(2)
+        .map(|pair| opcode_variant(u16::from_be_bytes([pair[0], pair[1]])))
+        .max()
+        .unwrap_or(Variant::Chip8)
+}
+
+fn opcode_variant(opcode: u16) -> Variant {
+    match opcode {
+        // 00Dn scrolls up, F000 loads a 16-bit address and F002 an audio pattern.
+        0x00D0..=0x00DF | 0xF000 | 0xF002 => Variant::XoChip,
+        // 5xy2 and 5xy3 save and load a range of registers.
+        _ if matches!(opcode & 0xF00F, 0x5002 | 0x5003) => Variant::XoChip,
+        // Fn01 picks the planes, Fx3A sets the pitch.
+        _ if matches!(opcode & 0xF0FF, 0xF001 | 0xF03A) => Variant::XoChip,
+        // 00Cn scrolls down, 00FB to 00FF scroll sideways, exit and switch resolution.
+        0x00C0..=0x00CF | 0x00FB..=0x00FF => Variant::SuperChip,
+        // Dxy0 draws a 16x16 sprite.
+        _ if opcode & 0xF00F == 0xD000 => Variant::SuperChip,
+        // Fx30 points at the big font, Fx75 and Fx85 save and load the flag registers.
+        _ if matches!(opcode & 0xF0FF, 0xF030 | 0xF075 | 0xF085) => Variant::SuperChip,
+        _ => Variant::Chip8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn warnings(rom: &[u8], max: usize) -> Vec<RomWarning> {
+        validate_rom(rom, max).unwrap().collect()
+    }
+
+    #[test]
+    fn validate_rom_rejects_empty_and_oversized_roms() {
+        assert!(matches!(validate_rom(&[], 10), Err(Chip8Error::EmptyRom)));
+        assert!(matches!(
+            validate_rom(&[0x12, 0x00, 0x00], 2),
+            Err(Chip8Error::RomTooLarge { size: 3, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn validate_rom_warns_about_odd_lengths_and_garbage() {
+        assert_eq!(warnings(&[0x12, 0x00], 2), []);
+        assert_eq!(
+            warnings(&[0x12, 0x00, 0xFF], 3),
+            [RomWarning::OddLength { size: 3 }]
+        );
+        assert_eq!(
+            warnings(&[0xFF, 0xFF, 0x00], 3),
+            [
+                RomWarning::OddLength { size: 3 },
+                RomWarning::NotCode { opcode: 0xFFFF }
+            ]
+        );
+        // The missing low byte of a one-byte ROM reads as zero.
+        assert_eq!(
+            warnings(&[0xE0], 1),
+            [
+                RomWarning::OddLength { size: 1 },
+                RomWarning::NotCode { opcode: 0xE000 }
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_variant_picks_the_newest_extension_used() {
+        assert_eq!(detect_variant(&[]), Variant::Chip8);
+        assert_eq!(detect_variant(&[0x00, 0xE0, 0xD0, 0x15]), Variant::Chip8);
+        assert_eq!(
+            detect_variant(&[0x00, 0xE0, 0x00, 0xFF]),
+            Variant::SuperChip
+        );
+        assert_eq!(detect_variant(&[0xD1, 0x20]), Variant::SuperChip);
+        assert_eq!(detect_variant(&[0xF3, 0x75]), Variant::SuperChip);
+        assert_eq!(
+            detect_variant(&[0x00, 0xFF, 0xF2, 0x01, 0x00, 0xC4]),
+            Variant::XoChip
+        );
+        assert_eq!(detect_variant(&[0x51, 0x22]), Variant::XoChip);
+        assert_eq!(detect_variant(&[0xF0, 0x00, 0x12, 0x34]), Variant::XoChip);
+    }
+}
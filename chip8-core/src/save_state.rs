@@ -0,0 +1,119 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use crate::cpu::Cpu;
+use crate::cpu::DEFAULT_STACK_DEPTH;
+use crate::display::Framebuffer;
+use crate::keypad::Keypad;
+use crate::memory::{Memory, DEFAULT_MEMORY_SIZE};
+use crate::rng::{Rng, XorShiftRng};
+use crate::stack::CallStack;
+use crate::timers::Timers;
+
+/// A copy of everything a running program can see or change, from `Cpu::save_state`, to go
+/// back to later with `Cpu::load_state`.
+///
+/// Configuration such as quirks, the clock speed, hooks and machine routines isn't saved, so
+/// loading a state never changes how the machine behaves from then on.
+#[derive(Clone)]
+pub struct SaveState<
+    R: Rng = XorShiftRng,
+    const MEM: usize = DEFAULT_MEMORY_SIZE,
+    const STACK: usize = DEFAULT_STACK_DEPTH,
+> {
+    pub(crate) registers: [u8; 16],
+    pub(crate) index_register: u16,
+    pub(crate) memory: Memory<MEM>,
+    pub(crate) position_in_memory: usize,
+    pub(crate) stack: CallStack<STACK>,
+    pub(crate) display: Framebuffer,
+    pub(crate) keypad: Keypad,
+    pub(crate) waiting_for_key: Option<u8>,
+    pub(crate) waiting_key_down: Option<u8>,
+    pub(crate) timers: Timers,
+    pub(crate) audio_pattern: Option<[u8; 16]>,
+    pub(crate) pitch: u8,
+    pub(crate) frame: u64,
+    pub(crate) rng: R,
+    pub(crate) cycle_remainder: u32,
+    pub(crate) frame_progress: u32,
+}
+
+impl<R: Rng, const MEM: usize, const STACK: usize> SaveState<R, MEM, STACK> {
+    /// The frame the state was saved at, as counted by `Cpu::frame`.
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+}
+
+/// How many slots `SaveSlots` has.
+#[cfg(feature = "alloc")]
+pub const SAVE_SLOTS: usize = 10;
+
+/// Numbered save states, for frontends' save and load hotkeys: `SAVE_SLOTS` slots, one of them
+/// selected, each empty until something is saved to it.
+#[cfg(feature = "alloc")]
+pub struct SaveSlots<
+    R: Rng = XorShiftRng,
+    const MEM: usize = DEFAULT_MEMORY_SIZE,
+    const STACK: usize = DEFAULT_STACK_DEPTH,
+> {
+    slots: Vec<Option<SaveState<R, MEM, STACK>>>,
+    current: usize,
+}
+
+#[cfg(feature = "alloc")]
+impl<R: Rng + Clone, const MEM: usize, const STACK: usize> SaveSlots<R, MEM, STACK> {
+    /// Empty slots, with slot 0 selected.
+    pub fn new() -> Self {
+        SaveSlots {
+            slots: (0..SAVE_SLOTS).map(|_| None).collect(),
+            current: 0,
+        }
+    }
+
+    /// The selected slot, from 0 to `SAVE_SLOTS - 1`.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Select the next slot, going round from the last to the first.
+    pub fn next(&mut self) {
+        self.current = (self.current + 1) % SAVE_SLOTS;
+    }
+
+    /// Select the previous slot, going round from the first to the last.
+    pub fn previous(&mut self) {
+        self.current = (self.current + SAVE_SLOTS - 1) % SAVE_SLOTS;
+    }
+
+    /// Save `cpu` to the selected slot, replacing whatever was there.
+    pub fn save(&mut self, cpu: &Cpu<R, MEM, STACK>) {
+        self.slots[self.current] = Some(cpu.save_state());
+    }
+
+    /// Put `cpu` back how it was saved in the selected slot. Returns false, leaving `cpu` alone,
+    /// if nothing has been saved there.
+    pub fn load(&self, cpu: &mut Cpu<R, MEM, STACK>) -> bool {
+        match &self.slots[self.current] {
+            Some(state) => {
+                cpu.load_state(state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The state in the selected slot, if any.
+    pub fn get(&self) -> Option<&SaveState<R, MEM, STACK>> {
+        self.slots[self.current].as_ref()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<R: Rng + Clone, const MEM: usize, const STACK: usize> Default for SaveSlots<R, MEM, STACK> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,195 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// What `2NNN` does when every slot of the call stack is in use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StackPolicy {
+    /// Stop with `Chip8Error::StackOverflow`.
+    #[default]
+    Error,
+    /// Forget the oldest return address to make room, like a ring buffer. The most recent
+    /// `STACK` calls still return correctly.
+    Wrap,
+    /// Keep going past `STACK` frames on the heap, for ROMs that recurse deeper than the
+    /// original hardware allowed.
+    #[cfg(feature = "alloc")]
+    Grow,
+}
+
+/// The subroutine call stack: up to `DEPTH` return addresses, plus any that overflowed onto the
+/// heap under `StackPolicy::Grow`.
+///
+/// All indexing goes through `push` and `pop`, which do their own bounds checks, so the stack
+/// pointer can never run off either end.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(crate) struct CallStack<const DEPTH: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_arrays"))]
+    frames: [u16; DEPTH],
+    /// Index of the oldest frame in `frames`, which moves when `Wrap` overwrites it.
+    start: usize,
+    len: usize,
+    #[cfg(feature = "alloc")]
+    spilled: Vec<u16>,
+    /// Configuration rather than state, so it's neither serialized nor copied by `load`.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    policy: StackPolicy,
+}
+
+impl<const DEPTH: usize> CallStack<DEPTH> {
+    pub(crate) fn new() -> Self {
+        CallStack {
+            frames: [0; DEPTH],
+            start: 0,
+            len: 0,
+            #[cfg(feature = "alloc")]
+            spilled: Vec::new(),
+            policy: StackPolicy::default(),
+        }
+    }
+
+    pub(crate) fn set_policy(&mut self, policy: StackPolicy) {
+        self.policy = policy;
+    }
+
+    /// Push a return address. Returns `false`, leaving the stack untouched, if it's full and the
+    /// policy is `Error`.
+    pub(crate) fn push(&mut self, addr: u16) -> bool {
+        if self.len < DEPTH {
+            self.frames[(self.start + self.len) % DEPTH] = addr;
+            self.len += 1;
+            return true;
+        }
+
+        match self.policy {
+            StackPolicy::Error => false,
+            StackPolicy::Wrap if DEPTH == 0 => false,
+            StackPolicy::Wrap => {
+                self.frames[self.start] = addr;
+                self.start = (self.start + 1) % DEPTH;
+                true
+            }
+            #[cfg(feature = "alloc")]
+            StackPolicy::Grow => {
+                self.spilled.push(addr);
+                true
+            }
+        }
+    }
+
+    /// Pop the most recent return address, or `None` if the stack is empty.
+    pub(crate) fn pop(&mut self) -> Option<u16> {
+        #[cfg(feature = "alloc")]
+        if let Some(addr) = self.spilled.pop() {
+            return Some(addr);
+        }
+
+        if self.len == 0 {
+            return None;
+        }
+
+        self.len -= 1;
+        Some(self.frames[(self.start + self.len) % DEPTH])
+    }
+
+    /// The return addresses, outermost first.
+    pub(crate) fn frames(&self) -> impl Iterator<Item = u16> + '_ {
+        let ring = (0..self.len).map(move |i| self.frames[(self.start + i) % DEPTH]);
+
+        #[cfg(feature = "alloc")]
+        let ring = ring.chain(self.spilled.iter().copied());
+
+        ring
+    }
+
+    /// Take on `saved`'s return addresses, keeping this stack's policy.
+    pub(crate) fn load(&mut self, saved: &Self) {
+        *self = CallStack {
+            policy: self.policy,
+            ..saved.clone()
+        };
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.start = 0;
+        self.len = 0;
+        #[cfg(feature = "alloc")]
+        self.spilled.clear();
+    }
+}
+
+impl<const DEPTH: usize> Default for CallStack<DEPTH> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full<const DEPTH: usize>(policy: StackPolicy) -> CallStack<DEPTH> {
+        let mut stack = CallStack::new();
+        stack.set_policy(policy);
+        for addr in 0..DEPTH as u16 {
+            assert!(stack.push(0x200 + addr));
+        }
+        stack
+    }
+
+    #[test]
+    fn error_refuses_to_overflow_or_underflow() {
+        let mut stack = full::<3>(StackPolicy::Error);
+        assert!(!stack.push(0x300));
+        assert_eq!(
+            [stack.pop(), stack.pop(), stack.pop()],
+            [0x202, 0x201, 0x200].map(Some)
+        );
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn wrap_overwrites_the_oldest_frame() {
+        let mut stack = full::<3>(StackPolicy::Wrap);
+        assert!(stack.push(0x300));
+        assert!(stack.push(0x301));
+        assert!(stack.frames().eq([0x202, 0x300, 0x301]));
+
+        assert_eq!(stack.pop(), Some(0x301));
+        assert!(stack.push(0x302));
+        assert_eq!(
+            [stack.pop(), stack.pop(), stack.pop()],
+            [0x302, 0x300, 0x202].map(Some)
+        );
+        assert_eq!(stack.pop(), None);
+    }
+
+    #[test]
+    fn wrap_with_no_room_is_an_overflow() {
+        assert!(!full::<0>(StackPolicy::Wrap).push(0x200));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn grow_spills_past_the_depth() {
+        let mut stack = full::<2>(StackPolicy::Grow);
+        assert!(stack.push(0x300));
+        assert!(stack.push(0x301));
+        assert!(stack.frames().eq([0x200, 0x201, 0x300, 0x301]));
+
+        let popped: [_; 5] = core::array::from_fn(|_| stack.pop());
+        assert_eq!(
+            popped,
+            [Some(0x301), Some(0x300), Some(0x201), Some(0x200), None]
+        );
+    }
+
+    #[test]
+    fn load_keeps_the_policy() {
+        let mut stack = CallStack::<2>::new();
+        stack.load(&full::<2>(StackPolicy::Wrap));
+        assert!(stack.frames().eq([0x200, 0x201]));
+        assert!(!stack.push(0x300));
+    }
+}
@@ -0,0 +1,20 @@
+/// The delay and sound timers, which count down to zero at 60Hz.
+#[derive(Debug, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Timers {
+    pub delay: u8,
+    /// The buzzer sounds while this is nonzero.
+    pub sound: u8,
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count both timers down by one.
+    pub fn tick(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+}
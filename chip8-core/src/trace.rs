@@ -0,0 +1,100 @@
+use crate::cpu::{Cpu, StepOutcome};
+use crate::error::Chip8Error;
+use crate::instruction::Instruction;
+use crate::rng::Rng;
+
+/// How one instruction changed the machine, as yielded by `Cpu::instructions`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuDelta {
+    pub registers_before: [u8; 16],
+    pub registers_after: [u8; 16],
+    pub index_before: u16,
+    pub index_after: u16,
+    /// The program counter after the instruction, i.e. where execution continues.
+    pub next_pc: usize,
+    /// Whether the instruction changed the display.
+    pub drew: bool,
+}
+
+impl CpuDelta {
+    /// The registers the instruction changed, as `(register, old value, new value)`.
+    pub fn changed_registers(&self) -> impl Iterator<Item = (u8, u8, u8)> + '_ {
+        self.registers_before
+            .iter()
+            .zip(&self.registers_after)
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(reg, (&before, &after))| (reg as u8, before, after))
+    }
+}
+
+/// Executes a `Cpu` one instruction per item. Returned by `Cpu::instructions`.
+///
+/// The iterator ends when the program halts, blocks on `FX0A` or fails. In the last case the
+/// error is kept and can be retrieved with `error`.
+pub struct Instructions<'a, R: Rng, const MEM: usize, const STACK: usize> {
+    cpu: &'a mut Cpu<R, MEM, STACK>,
+    error: Option<Chip8Error>,
+}
+
+impl<R: Rng, const MEM: usize, const STACK: usize> Instructions<'_, R, MEM, STACK> {
+    /// The error that ended the iteration, if any.
+    pub fn error(&self) -> Option<&Chip8Error> {
+        self.error.as_ref()
+    }
+}
+
+impl<R: Rng, const MEM: usize, const STACK: usize> Iterator for Instructions<'_, R, MEM, STACK> {
+    type Item = (usize, Instruction, CpuDelta);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+
+        let (pc, instruction) = match self.cpu.fetch() {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                self.error = Some(err);
+                return None;
+            }
+        };
+
+        let registers_before = *self.cpu.registers();
+        let index_before = self.cpu.i();
+
+        let drew = match self.cpu.step() {
+            Ok(StepOutcome::Executed) => false,
+            Ok(StepOutcome::Drew) => true,
+            // FX0A itself ran; it's the next step that would block.
+            Ok(StepOutcome::WaitingForKey) if self.cpu.pc() != pc => false,
+            Ok(StepOutcome::WaitingForKey | StepOutcome::Halted) => return None,
+            Err(err) => {
+                self.error = Some(err);
+                return None;
+            }
+        };
+
+        let delta = CpuDelta {
+            registers_before,
+            registers_after: *self.cpu.registers(),
+            index_before,
+            index_after: self.cpu.i(),
+            next_pc: self.cpu.pc(),
+            drew,
+        };
+
+        Some((pc, instruction, delta))
+    }
+}
+
+impl<R: Rng, const MEM: usize, const STACK: usize> Cpu<R, MEM, STACK> {
+    /// Execute instructions lazily, yielding each one with the address it was read from and the
+    /// changes it made, e.g. `cpu.instructions().take(100).collect::<Vec<_>>()` for a trace.
+    pub fn instructions(&mut self) -> Instructions<'_, R, MEM, STACK> {
+        Instructions {
+            cpu: self,
+            error: None,
+        }
+    }
+}
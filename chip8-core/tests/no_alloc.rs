@@ -0,0 +1,61 @@
+//! `step` and `run_frame` must never touch the heap, so that embedded targets can run the core
+//! without an allocator and frontends get predictable frame times.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chip8_core::Chip8;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Draws a font glyph, beeps, calls a subroutine and a machine routine, and loops forever.
+#[rustfmt::skip]
+const ROM: [u8; 22] = [
+    0x60, 0x05, // 0x200: LD V0, 5
+    0xF0, 0x29, // 0x202: LD F, V0
+    0xD1, 0x15, // 0x204: DRW V1, V1, 5
+    0xF0, 0x18, // 0x206: LD ST, V0
+    0x22, 0x12, // 0x208: CALL 0x212
+    0x03, 0x00, // 0x20A: SYS 0x300
+    0xC2, 0xFF, // 0x20C: RND V2, 0xFF
+    0x12, 0x00, // 0x20E: JP 0x200
+    0x00, 0x00, // 0x210: (unused)
+    0x71, 0x01, // 0x212: ADD V1, 1
+    0x00, 0xEE, // 0x214: RET
+];
+
+#[test]
+fn stepping_does_not_allocate() {
+    let mut cpu = Chip8::builder().rom(&ROM).build().unwrap();
+    cpu.register_routine(0x300, |cpu| cpu.registers_mut()[3] += 1);
+    cpu.on_instruction(|_, _| {});
+    cpu.on_draw(|_| {});
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+
+    for _ in 0..100 {
+        cpu.step().unwrap();
+    }
+    for _ in 0..100 {
+        cpu.run_frame().unwrap();
+    }
+
+    assert_eq!(ALLOCATIONS.load(Ordering::Relaxed), before);
+    assert!(cpu.registers()[3] > 0);
+}
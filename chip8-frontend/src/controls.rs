@@ -0,0 +1,222 @@
+use chip8_core::{
+    Chip8Error, Cpu, Rng, RomSettings, SaveSlots, XorShiftRng, DEFAULT_MEMORY_SIZE,
+    DEFAULT_STACK_DEPTH,
+};
+
+use crate::hotkeys::HotkeyAction;
+
+/// How many frames run for every frame of real time in turbo mode.
+pub const TURBO_FRAMES: usize = 4;
+
+/// What the hotkeys that act on the machine have set up: whether it's paused or in turbo mode,
+/// and its save states. Every frontend keeps one and passes it the `HotkeyAction`s it reads.
+pub struct Controls<
+    R: Rng = XorShiftRng,
+    const MEM: usize = DEFAULT_MEMORY_SIZE,
+    const STACK: usize = DEFAULT_STACK_DEPTH,
+> {
+    paused: bool,
+    turbo: bool,
+    slots: SaveSlots<R, MEM, STACK>,
+    rom_settings: RomSettings,
+}
+
+impl<R: Rng + Clone, const MEM: usize, const STACK: usize> Controls<R, MEM, STACK> {
+    /// Running at normal speed, with empty save state slots, and loading ROMs with the quirks
+    /// and clock speed `KnownRoms::BUILT_IN` has for them.
+    pub fn new() -> Self {
+        Self::with_rom_settings(RomSettings::default())
+    }
+
+    /// Like `new`, but loading ROMs with `rom_settings`, e.g. the ones the first ROM was
+    /// started with.
+    pub fn with_rom_settings(rom_settings: RomSettings) -> Self {
+        Controls {
+            paused: false,
+            turbo: false,
+            slots: SaveSlots::new(),
+            rom_settings,
+        }
+    }
+
+    /// Carry out `action` on `cpu` if it's `Pause`, `Reset`, `Turbo` or one of the save state
+    /// actions, and return what happened, e.g. `"saved slot 3"`, for frontends that have
+    /// somewhere to show it. The rest are up to the frontend, and return `None`.
+    pub fn apply(&mut self, action: HotkeyAction, cpu: &mut Cpu<R, MEM, STACK>) -> Option<String> {
+        let slot = self.slots.current();
+        let message = match action {
+            HotkeyAction::Pause => {
+                self.paused = !self.paused;
+                if self.paused { "paused" } else { "resumed" }.to_string()
+            }
+            HotkeyAction::Reset => {
+                cpu.reset();
+                "reset".to_string()
+            }
+            HotkeyAction::Turbo => {
+                self.turbo = !self.turbo;
+                if self.turbo { "turbo on" } else { "turbo off" }.to_string()
+            }
+            HotkeyAction::SaveState => {
+                self.slots.save(cpu);
+                format!("saved slot {}", slot)
+            }
+            HotkeyAction::LoadState => {
+                if self.slots.load(cpu) {
+                    format!("loaded slot {}", slot)
+                } else {
+                    format!("slot {} is empty", slot)
+                }
+            }
+            HotkeyAction::NextSlot | HotkeyAction::PreviousSlot => {
+                if action == HotkeyAction::NextSlot {
+                    self.slots.next();
+                } else {
+                    self.slots.previous();
+                }
+                let empty = if self.slots.get().is_none() {
+                    " (empty)"
+                } else {
+                    ""
+                };
+                format!("slot {}{}", self.slots.current(), empty)
+            }
+            _ => return None,
+        };
+
+        Some(message)
+    }
+
+    /// Start `rom` on `cpu` from scratch in place of the program it was running, e.g. one
+    /// dropped on the window, with the quirks and clock speed the `RomSettings` pick for it,
+    /// and resume if paused. The save state slots are emptied, since loading one would bring
+    /// the old program back.
+    pub fn load_rom(&mut self, cpu: &mut Cpu<R, MEM, STACK>, rom: &[u8]) -> Result<(), Chip8Error> {
+        cpu.reset_with_rom(rom)?;
+        self.rom_settings.apply(cpu, rom);
+        self.paused = false;
+        self.slots = SaveSlots::new();
+
+        Ok(())
+    }
+
+    /// How many frames to run this frame of real time: none while paused, `TURBO_FRAMES` in
+    /// turbo mode and otherwise one.
+    pub fn frames(&self) -> usize {
+        match (self.paused, self.turbo) {
+            (true, _) => 0,
+            (false, true) => TURBO_FRAMES,
+            (false, false) => 1,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pause or resume, e.g. from a button or when the program fails.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.turbo
+    }
+
+    pub fn slots(&self) -> &SaveSlots<R, MEM, STACK> {
+        &self.slots
+    }
+}
+
+impl<R: Rng + Clone, const MEM: usize, const STACK: usize> Default for Controls<R, MEM, STACK> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chip8_core::{Chip8, Quirks, SAVE_SLOTS};
+
+    use super::*;
+
+    const DB: &str = "# A comment\n\n\
+        a9993e364706816aba3e25717850c26c9cd0d89d schip 1000 The Alphabet\n";
+
+    #[test]
+    fn load_rom_applies_the_settings_for_the_new_rom() {
+        let settings = RomSettings::new(None, Some(900))
+            .with_known_roms(DB.into())
+            .unwrap();
+        let mut controls = Controls::with_rom_settings(settings);
+        let mut cpu = Chip8::builder().build().unwrap();
+
+        controls.load_rom(&mut cpu, b"abc").unwrap();
+        assert_eq!(*cpu.quirks(), Quirks::SCHIP);
+        assert_eq!(cpu.clock_hz(), 900);
+
+        controls.load_rom(&mut cpu, b"xyz").unwrap();
+        assert_eq!(*cpu.quirks(), Quirks::default());
+        assert_eq!(cpu.clock_hz(), 900);
+    }
+
+    #[test]
+    fn pause_and_turbo_toggle_the_frames_to_run() {
+        let mut controls = Controls::new();
+        let mut cpu = Chip8::builder().build().unwrap();
+        assert_eq!(controls.frames(), 1);
+
+        let message = controls.apply(HotkeyAction::Turbo, &mut cpu);
+        assert_eq!(message.as_deref(), Some("turbo on"));
+        assert_eq!(controls.frames(), TURBO_FRAMES);
+
+        let message = controls.apply(HotkeyAction::Pause, &mut cpu);
+        assert_eq!(message.as_deref(), Some("paused"));
+        assert_eq!(controls.frames(), 0);
+
+        controls.apply(HotkeyAction::Pause, &mut cpu);
+        controls.apply(HotkeyAction::Turbo, &mut cpu);
+        assert!(!controls.is_paused() && !controls.is_turbo());
+        assert_eq!(controls.frames(), 1);
+
+        // Actions that are up to the frontend are passed back.
+        assert_eq!(controls.apply(HotkeyAction::Screenshot, &mut cpu), None);
+    }
+
+    #[test]
+    fn saves_and_loads_the_selected_slot() {
+        let mut controls = Controls::new();
+        let mut cpu = Chip8::builder().build().unwrap();
+
+        let message = controls.apply(HotkeyAction::LoadState, &mut cpu);
+        assert_eq!(message.as_deref(), Some("slot 0 is empty"));
+
+        cpu.registers_mut()[0] = 7;
+        let saved = cpu.state_hash();
+        let message = controls.apply(HotkeyAction::SaveState, &mut cpu);
+        assert_eq!(message.as_deref(), Some("saved slot 0"));
+
+        cpu.registers_mut()[0] = 8;
+        let message = controls.apply(HotkeyAction::LoadState, &mut cpu);
+        assert_eq!(message.as_deref(), Some("loaded slot 0"));
+        assert_eq!(cpu.state_hash(), saved);
+
+        let message = controls.apply(HotkeyAction::NextSlot, &mut cpu);
+        assert_eq!(message.as_deref(), Some("slot 1 (empty)"));
+        let message = controls.apply(HotkeyAction::PreviousSlot, &mut cpu);
+        assert_eq!(message.as_deref(), Some("slot 0"));
+    }
+
+    #[test]
+    fn slots_wrap_around() {
+        let mut controls = Controls::new();
+        let mut cpu = Chip8::builder().build().unwrap();
+
+        let message = controls.apply(HotkeyAction::PreviousSlot, &mut cpu);
+        assert_eq!(message, Some(format!("slot {} (empty)", SAVE_SLOTS - 1)));
+
+        let message = controls.apply(HotkeyAction::NextSlot, &mut cpu);
+        assert_eq!(message.as_deref(), Some("slot 0 (empty)"));
+        assert_eq!(controls.slots().current(), 0);
+    }
+}
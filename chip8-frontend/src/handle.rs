@@ -0,0 +1,152 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use chip8_core::{
+    AudioPattern, AudioSink, Chip8Error, Cpu, DisplaySink, Framebuffer, KeyInput, Rng,
+};
+
+use crate::pacer::FramePacer;
+
+/// A message from the UI to the emulation thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    KeyDown(u8),
+    KeyUp(u8),
+    /// Stop running frames until `Resume`. Key presses are still recorded.
+    Pause,
+    Resume,
+    /// End the emulation thread. `Chip8Handle::join` then returns the machine.
+    Stop,
+}
+
+/// A message from the emulation thread to the UI.
+pub enum Event {
+    /// The display changed during the last frame.
+    Frame(Box<Framebuffer>),
+    BeepStarted,
+    BeepStopped,
+    /// The beep should play this XO-CHIP pattern from now on, or a plain tone for `None`.
+    Pattern(Option<AudioPattern>),
+    /// Execution failed. The thread stops after sending this.
+    Error(Chip8Error),
+}
+
+/// Runs a `Cpu` at 60 frames per second on a worker thread, so that a UI loop never blocks on
+/// emulation.
+///
+/// Input and control go in with `send`; frames, beeps and errors come out of `try_recv`.
+pub struct Chip8Handle<R: Rng, const MEM: usize, const STACK: usize> {
+    commands: Sender<Command>,
+    events: Receiver<Event>,
+    thread: JoinHandle<Cpu<R, MEM, STACK>>,
+}
+
+impl<R, const MEM: usize, const STACK: usize> Chip8Handle<R, MEM, STACK>
+where
+    R: Rng + Send + 'static,
+{
+    /// Move `cpu` to a new thread and start running it.
+    pub fn spawn(cpu: Cpu<R, MEM, STACK>) -> Self {
+        let (commands, command_rx) = mpsc::channel();
+        let (event_tx, events) = mpsc::channel();
+
+        let thread = thread::spawn(move || worker(cpu, command_rx, event_tx));
+
+        Chip8Handle {
+            commands,
+            events,
+            thread,
+        }
+    }
+
+    /// Deliver `command` to the emulation thread. It's applied at the start of the next frame.
+    pub fn send(&self, command: Command) {
+        // The worker only hangs up after an error, which `try_recv` reports.
+        let _ = self.commands.send(command);
+    }
+
+    /// The next pending event, if there is one.
+    pub fn try_recv(&self) -> Option<Event> {
+        self.events.try_recv().ok()
+    }
+
+    /// Stop the emulation thread and take the machine back, e.g. to save its state.
+    pub fn join(self) -> Cpu<R, MEM, STACK> {
+        self.send(Command::Stop);
+
+        match self.thread.join() {
+            Ok(cpu) => cpu,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+}
+
+/// The worker's `Peripherals`: keys come from commands, output goes to the event channel.
+struct ChannelPeripherals {
+    keys: [bool; 16],
+    events: Sender<Event>,
+}
+
+impl DisplaySink for ChannelPeripherals {
+    fn present(&mut self, framebuffer: &Framebuffer) {
+        let _ = self
+            .events
+            .send(Event::Frame(Box::new(framebuffer.clone())));
+    }
+}
+
+impl KeyInput for ChannelPeripherals {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys[(key & 0xF) as usize]
+    }
+}
+
+impl AudioSink for ChannelPeripherals {
+    fn start_beep(&mut self) {
+        let _ = self.events.send(Event::BeepStarted);
+    }
+
+    fn stop_beep(&mut self) {
+        let _ = self.events.send(Event::BeepStopped);
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        let _ = self.events.send(Event::Pattern(pattern));
+    }
+}
+
+fn worker<R: Rng, const MEM: usize, const STACK: usize>(
+    mut cpu: Cpu<R, MEM, STACK>,
+    commands: Receiver<Command>,
+    events: Sender<Event>,
+) -> Cpu<R, MEM, STACK> {
+    let mut io = ChannelPeripherals {
+        keys: [false; 16],
+        events,
+    };
+    let mut paused = false;
+    let mut pacer = FramePacer::new();
+
+    loop {
+        loop {
+            match commands.try_recv() {
+                Ok(Command::KeyDown(key)) => io.keys[(key & 0xF) as usize] = true,
+                Ok(Command::KeyUp(key)) => io.keys[(key & 0xF) as usize] = false,
+                Ok(Command::Pause) => paused = true,
+                Ok(Command::Resume) => paused = false,
+                Ok(Command::Stop) | Err(TryRecvError::Disconnected) => return cpu,
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+
+        if !paused {
+            if let Err(err) = cpu.run_frame_with(&mut io) {
+                let _ = io.events.send(Event::Error(err));
+                return cpu;
+            }
+        }
+
+        // Doesn't try to catch up after falling behind, e.g. while the machine was suspended.
+        pacer.wait();
+    }
+}
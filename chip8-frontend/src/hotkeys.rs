@@ -0,0 +1,416 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Something a frontend does when its hotkey is pressed, instead of passing the key on to the
+/// game. Frontends ignore the ones that don't apply to them, e.g. `Crt` in a terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyAction {
+    Quit,
+    /// Pause or resume the game.
+    Pause,
+    /// Restart the game with `Cpu::reset`.
+    Reset,
+    /// Save the machine to the selected `SaveSlots` slot.
+    SaveState,
+    /// Go back to the state saved in the selected slot.
+    LoadState,
+    /// Select the next save state slot.
+    NextSlot,
+    /// Select the previous save state slot.
+    PreviousSlot,
+    /// Run `TURBO_FRAMES` frames for every frame of real time, or go back to normal speed.
+    Turbo,
+    Screenshot,
+    Fullscreen,
+    /// Turn the CRT filter on or off.
+    Crt,
+    /// Start or stop recording a GIF.
+    RecordGif,
+    /// Silence the buzzer, or bring it back.
+    Mute,
+    VolumeDown,
+    VolumeUp,
+    /// Open the ROM library to pick another game, or close it.
+    Library,
+}
+
+impl HotkeyAction {
+    /// Every action, in the order `Hotkeys` stores them.
+    pub const ALL: [HotkeyAction; 16] = [
+        HotkeyAction::Quit,
+        HotkeyAction::Pause,
+        HotkeyAction::Reset,
+        HotkeyAction::SaveState,
+        HotkeyAction::LoadState,
+        HotkeyAction::NextSlot,
+        HotkeyAction::PreviousSlot,
+        HotkeyAction::Turbo,
+        HotkeyAction::Screenshot,
+        HotkeyAction::Fullscreen,
+        HotkeyAction::Crt,
+        HotkeyAction::RecordGif,
+        HotkeyAction::Mute,
+        HotkeyAction::VolumeDown,
+        HotkeyAction::VolumeUp,
+        HotkeyAction::Library,
+    ];
+
+    /// The name `Hotkeys::bind` knows the action by, e.g. `save-state`.
+    pub fn name(self) -> &'static str {
+        match self {
+            HotkeyAction::Quit => "quit",
+            HotkeyAction::Pause => "pause",
+            HotkeyAction::Reset => "reset",
+            HotkeyAction::SaveState => "save-state",
+            HotkeyAction::LoadState => "load-state",
+            HotkeyAction::NextSlot => "next-slot",
+            HotkeyAction::PreviousSlot => "prev-slot",
+            HotkeyAction::Turbo => "turbo",
+            HotkeyAction::Screenshot => "screenshot",
+            HotkeyAction::Fullscreen => "fullscreen",
+            HotkeyAction::Crt => "crt",
+            HotkeyAction::RecordGif => "record-gif",
+            HotkeyAction::Mute => "mute",
+            HotkeyAction::VolumeDown => "volume-down",
+            HotkeyAction::VolumeUp => "volume-up",
+            HotkeyAction::Library => "library",
+        }
+    }
+
+    /// The action called `name`, as returned by `name`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        HotkeyAction::ALL
+            .into_iter()
+            .find(|action| action.name() == name)
+    }
+}
+
+/// A key a hotkey can be bound to, named independently of any windowing library so that every
+/// frontend can share one set of bindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HotkeyKey {
+    /// A function key, from F1 to F24.
+    F(u8),
+    /// The key that types this character. Letters are always lower case, whether or not Shift
+    /// is held.
+    Char(char),
+    Escape,
+    Tab,
+    Space,
+    Enter,
+    Backspace,
+}
+
+/// A key, and whether Ctrl and Shift are held with it.
+///
+/// Written as the key's name after any modifiers, joined with `+`: `F5`, `Shift+F5`, `Ctrl+R`,
+/// `Esc`, `Tab`, `Space`, `Enter`, `Backspace`, or any single character.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Hotkey {
+    pub key: HotkeyKey,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl Hotkey {
+    /// `key` on its own. A `Char` is folded to lower case, so frontends can pass on whatever
+    /// character their key events carry.
+    pub fn new(key: HotkeyKey) -> Self {
+        let key = match key {
+            HotkeyKey::Char(c) => HotkeyKey::Char(c.to_lowercase().next().unwrap_or(c)),
+            key => key,
+        };
+
+        Hotkey {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    pub fn with_ctrl(self, ctrl: bool) -> Self {
+        Hotkey { ctrl, ..self }
+    }
+
+    pub fn with_shift(self, shift: bool) -> Self {
+        Hotkey { shift, ..self }
+    }
+
+    const fn plain(key: HotkeyKey) -> Self {
+        Hotkey {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = HotkeyError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let (modifiers, name) = match text.rsplit_once('+') {
+            Some((modifiers, name)) if !name.is_empty() => (modifiers, name),
+            _ => ("", text),
+        };
+
+        let mut hotkey = Hotkey::new(key_named(name).ok_or(HotkeyError::UnknownKey)?);
+        for modifier in modifiers.split('+').filter(|modifier| !modifier.is_empty()) {
+            if modifier.eq_ignore_ascii_case("ctrl") {
+                hotkey.ctrl = true;
+            } else if modifier.eq_ignore_ascii_case("shift") {
+                hotkey.shift = true;
+            } else {
+                return Err(HotkeyError::UnknownKey);
+            }
+        }
+
+        Ok(hotkey)
+    }
+}
+
+fn key_named(name: &str) -> Option<HotkeyKey> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(HotkeyKey::Char(c));
+    }
+
+    let named = [
+        ("esc", HotkeyKey::Escape),
+        ("escape", HotkeyKey::Escape),
+        ("tab", HotkeyKey::Tab),
+        ("space", HotkeyKey::Space),
+        ("enter", HotkeyKey::Enter),
+        ("return", HotkeyKey::Enter),
+        ("backspace", HotkeyKey::Backspace),
+    ];
+    if let Some(&(_, key)) = named.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)) {
+        return Some(key);
+    }
+
+    let number = name.strip_prefix(['f', 'F'])?.parse().ok()?;
+    (1..=24).contains(&number).then_some(HotkeyKey::F(number))
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+
+        match self.key {
+            HotkeyKey::F(n) => write!(f, "F{}", n),
+            HotkeyKey::Char(c) => write!(f, "{}", c.to_uppercase()),
+            HotkeyKey::Escape => write!(f, "Esc"),
+            HotkeyKey::Tab => write!(f, "Tab"),
+            HotkeyKey::Space => write!(f, "Space"),
+            HotkeyKey::Enter => write!(f, "Enter"),
+            HotkeyKey::Backspace => write!(f, "Backspace"),
+        }
+    }
+}
+
+/// Which hotkey, if any, does each `HotkeyAction`.
+///
+/// The defaults keep clear of the keys any `Keymap` preset uses for the keypad: Esc quits, F1 opens
+/// the ROM library, F2 toggles the CRT filter, F3 pauses, F4 resets, F5 saves a state and F8 loads
+/// it, F6 and F7 pick the slot, Tab toggles turbo, F9 records a GIF, F10 mutes, `-` and `=` turn
+/// the volume down and up, F11 toggles fullscreen and F12 takes a screenshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hotkeys {
+    /// Indexed like `HotkeyAction::ALL`.
+    keys: [Option<Hotkey>; HotkeyAction::ALL.len()],
+}
+
+impl Hotkeys {
+    /// No hotkeys at all.
+    pub const NONE: Hotkeys = Hotkeys {
+        keys: [None; HotkeyAction::ALL.len()],
+    };
+
+    pub const DEFAULT: Hotkeys = Hotkeys {
+        keys: [
+            Some(Hotkey::plain(HotkeyKey::Escape)),
+            Some(Hotkey::plain(HotkeyKey::F(3))),
+            Some(Hotkey::plain(HotkeyKey::F(4))),
+            Some(Hotkey::plain(HotkeyKey::F(5))),
+            Some(Hotkey::plain(HotkeyKey::F(8))),
+            Some(Hotkey::plain(HotkeyKey::F(7))),
+            Some(Hotkey::plain(HotkeyKey::F(6))),
+            Some(Hotkey::plain(HotkeyKey::Tab)),
+            Some(Hotkey::plain(HotkeyKey::F(12))),
+            Some(Hotkey::plain(HotkeyKey::F(11))),
+            Some(Hotkey::plain(HotkeyKey::F(2))),
+            Some(Hotkey::plain(HotkeyKey::F(9))),
+            Some(Hotkey::plain(HotkeyKey::F(10))),
+            Some(Hotkey::plain(HotkeyKey::Char('-'))),
+            Some(Hotkey::plain(HotkeyKey::Char('='))),
+            Some(Hotkey::plain(HotkeyKey::F(1))),
+        ],
+    };
+
+    /// The hotkey for `action`.
+    pub fn key(&self, action: HotkeyAction) -> Option<Hotkey> {
+        self.keys[action as usize]
+    }
+
+    /// The action `hotkey` does, if any.
+    pub fn action(&self, hotkey: Hotkey) -> Option<HotkeyAction> {
+        let index = self.keys.iter().position(|&key| key == Some(hotkey))?;
+
+        Some(HotkeyAction::ALL[index])
+    }
+
+    /// Bind `action` to `hotkey`, or unbind it with `None`. Whatever else `hotkey` did is
+    /// unbound, since one key can only do one thing.
+    pub fn set(&mut self, action: HotkeyAction, hotkey: Option<Hotkey>) {
+        if hotkey.is_some() {
+            for key in &mut self.keys {
+                if *key == hotkey {
+                    *key = None;
+                }
+            }
+        }
+
+        self.keys[action as usize] = hotkey;
+    }
+
+    /// Parse one binding, `ACTION=KEY`, where `ACTION` is a `HotkeyAction::name` and `KEY` a
+    /// `Hotkey` or `none`, and apply it.
+    pub fn bind(&mut self, binding: &str) -> Result<(), HotkeyError> {
+        let (action, key) = binding.split_once('=').ok_or(HotkeyError::MissingKey)?;
+        let action = HotkeyAction::from_name(action.trim()).ok_or(HotkeyError::UnknownAction)?;
+
+        let hotkey = match key.trim() {
+            "none" => None,
+            key => Some(key.parse()?),
+        };
+        self.set(action, hotkey);
+
+        Ok(())
+    }
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Hotkeys::DEFAULT
+    }
+}
+
+/// Why a hotkey or binding couldn't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HotkeyError {
+    /// A binding had no `=KEY`.
+    MissingKey,
+    /// A binding named an action that doesn't exist.
+    UnknownAction,
+    /// A hotkey wasn't a key name, optionally after `Ctrl+` and `Shift+`.
+    UnknownKey,
+}
+
+impl fmt::Display for HotkeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HotkeyError::MissingKey => write!(f, "a hotkey binding looks like ACTION=KEY"),
+            HotkeyError::UnknownAction => {
+                write!(f, "the hotkey actions are ")?;
+                for (i, action) in HotkeyAction::ALL.iter().enumerate() {
+                    let sep = match i {
+                        0 => "",
+                        _ if i == HotkeyAction::ALL.len() - 1 => " and ",
+                        _ => ", ",
+                    };
+                    write!(f, "{}{}", sep, action.name())?;
+                }
+                Ok(())
+            }
+            HotkeyError::UnknownKey => write!(
+                f,
+                "hotkeys are F1-F24, Esc, Tab, Space, Enter, Backspace or a character, after \
+                 any of Ctrl+ and Shift+"
+            ),
+        }
+    }
+}
+
+impl Error for HotkeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_round_trip_through_their_names() {
+        for action in HotkeyAction::ALL {
+            assert_eq!(HotkeyAction::from_name(action.name()), Some(action));
+
+            let hotkey = Hotkeys::DEFAULT.key(action).unwrap();
+            assert_eq!(hotkey.to_string().parse(), Ok(hotkey), "{}", hotkey);
+            assert_eq!(Hotkeys::DEFAULT.action(hotkey), Some(action));
+        }
+    }
+
+    #[test]
+    fn parses_modifiers_and_names_in_any_case() {
+        let ctrl_r = Hotkey::new(HotkeyKey::Char('r')).with_ctrl(true);
+        assert_eq!("Ctrl+R".parse(), Ok(ctrl_r));
+        assert_eq!("ctrl+r".parse(), Ok(ctrl_r));
+        assert_eq!(ctrl_r.to_string(), "Ctrl+R");
+
+        let both = Hotkey::new(HotkeyKey::F(5))
+            .with_ctrl(true)
+            .with_shift(true);
+        assert_eq!("shift+CTRL+f5".parse(), Ok(both));
+        assert_eq!(both.to_string(), "Ctrl+Shift+F5");
+
+        assert_eq!("escape".parse(), Ok(Hotkey::new(HotkeyKey::Escape)));
+        assert_eq!("Return".parse(), Ok(Hotkey::new(HotkeyKey::Enter)));
+        assert_eq!("+".parse(), Ok(Hotkey::new(HotkeyKey::Char('+'))));
+    }
+
+    #[test]
+    fn rejects_unknown_keys_and_bindings() {
+        for text in ["", "F0", "F25", "Alt+F1", "Ctrl+", "Super"] {
+            assert_eq!(
+                text.parse::<Hotkey>(),
+                Err(HotkeyError::UnknownKey),
+                "{}",
+                text
+            );
+        }
+
+        let mut hotkeys = Hotkeys::DEFAULT;
+        assert_eq!(hotkeys.bind("pause"), Err(HotkeyError::MissingKey));
+        assert_eq!(hotkeys.bind("jump=F1"), Err(HotkeyError::UnknownAction));
+        assert_eq!(hotkeys.bind("pause=Meta+P"), Err(HotkeyError::UnknownKey));
+        assert_eq!(hotkeys, Hotkeys::DEFAULT);
+    }
+
+    #[test]
+    fn binding_a_key_takes_it_from_its_old_action() {
+        let mut hotkeys = Hotkeys::DEFAULT;
+        let f3 = Hotkey::new(HotkeyKey::F(3));
+
+        hotkeys.bind("reset = F3").unwrap();
+        assert_eq!(hotkeys.key(HotkeyAction::Reset), Some(f3));
+        assert_eq!(hotkeys.key(HotkeyAction::Pause), None);
+        assert_eq!(hotkeys.action(f3), Some(HotkeyAction::Reset));
+
+        hotkeys.bind("reset=none").unwrap();
+        assert_eq!(hotkeys.action(f3), None);
+
+        let p = Hotkey::new(HotkeyKey::Char('p'));
+        hotkeys.set(HotkeyAction::Pause, Some(p));
+        hotkeys.set(HotkeyAction::Quit, Some(p));
+        assert_eq!(hotkeys.key(HotkeyAction::Pause), None);
+        assert_eq!(hotkeys.action(p), Some(HotkeyAction::Quit));
+
+        // Unbinding one action leaves the others alone.
+        hotkeys.set(HotkeyAction::Mute, None);
+        assert_eq!(hotkeys.key(HotkeyAction::Quit), Some(p));
+    }
+}
@@ -0,0 +1,317 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Which keyboard key stands for each hex key.
+///
+/// A layout is written as the 16 characters that go on the keypad, row by row as it's laid out
+/// on the COSMAC VIP:
+///
+/// ```text
+/// 1 2 3 C
+/// 4 5 6 D
+/// 7 8 9 E
+/// A 0 B F
+/// ```
+///
+/// so the QWERTY preset is `1234qwerasdfzxcv`. Letters match whichever case is typed. There are
+/// presets that put the keypad on the same physical keys on other common layouts, by name with
+/// `Keymap::named` or as consts.
+///
+/// The default, `Keymap::PHYSICAL`, goes by where keys are rather than what they type, so the
+/// block at the left of the keyboard is the keypad whatever the layout. Frontends that know
+/// which physical key was pressed look it up with `Keymap::position_key`; the rest, and keys
+/// outside the block, fall back to its characters, the QWERTY ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Keymap {
+    /// The character for each hex key, indexed by key.
+    chars: [char; 16],
+    /// Whether the keypad follows key positions, where they're known.
+    physical: bool,
+}
+
+/// The hex keys in the order a layout lists them.
+const LAYOUT_ORDER: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+impl Keymap {
+    /// The 4x4 block at the left of the keyboard, by position, on any layout.
+    pub const PHYSICAL: Keymap = Keymap {
+        physical: true,
+        ..Keymap::QWERTY
+    };
+
+    /// The keypad on the left of a QWERTY keyboard: `1234qwerasdfzxcv`.
+    pub const QWERTY: Keymap = Keymap {
+        chars: [
+            'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'z', 'c', '4', 'r', 'f', 'v',
+        ],
+        physical: false,
+    };
+
+    /// The same keys on a French AZERTY keyboard: `&é"'azerqsdfwxcv`, the number row's
+    /// unshifted characters.
+    pub const AZERTY: Keymap = Keymap {
+        chars: [
+            'x', '&', 'é', '"', 'a', 'z', 'e', 'q', 's', 'd', 'w', 'c', '\'', 'r', 'f', 'v',
+        ],
+        physical: false,
+    };
+
+    /// The same keys on a German QWERTZ keyboard: `1234qwerasdfyxcv`.
+    pub const QWERTZ: Keymap = Keymap {
+        chars: [
+            'x', '1', '2', '3', 'q', 'w', 'e', 'a', 's', 'd', 'y', 'c', '4', 'r', 'f', 'v',
+        ],
+        physical: false,
+    };
+
+    /// The same keys on a Colemak keyboard: `1234qwfparstzxcv`.
+    pub const COLEMAK: Keymap = Keymap {
+        chars: [
+            'x', '1', '2', '3', 'q', 'w', 'f', 'a', 'r', 's', 'z', 'c', '4', 'p', 't', 'v',
+        ],
+        physical: false,
+    };
+
+    /// The preset called `name`: `physical`, `qwerty`, `azerty`, `qwertz` or `colemak`.
+    pub fn named(name: &str) -> Option<Keymap> {
+        let keymap = match name {
+            "physical" => Keymap::PHYSICAL,
+            "qwerty" => Keymap::QWERTY,
+            "azerty" => Keymap::AZERTY,
+            "qwertz" => Keymap::QWERTZ,
+            "colemak" => Keymap::COLEMAK,
+            _ => return None,
+        };
+
+        Some(keymap)
+    }
+
+    /// Parse a layout of 16 different characters in keypad order, e.g. `&é"'azerqsdfwxcv` for
+    /// AZERTY.
+    pub fn from_layout(layout: &str) -> Result<Self, KeymapError> {
+        let mut chars = ['\0'; 16];
+        let mut count = 0;
+
+        for c in layout.chars() {
+            let c = c.to_lowercase().next().unwrap_or(c);
+            if count < 16 {
+                if chars.contains(&c) {
+                    return Err(KeymapError::Duplicate(c));
+                }
+                chars[LAYOUT_ORDER[count] as usize] = c;
+            }
+            count += 1;
+        }
+
+        if count != 16 {
+            return Err(KeymapError::WrongLength(count));
+        }
+
+        Ok(Keymap {
+            chars,
+            physical: false,
+        })
+    }
+
+    /// Whether the keypad follows key positions, so frontends should try `position_key` before
+    /// `hex_key`.
+    pub fn is_physical(&self) -> bool {
+        self.physical
+    }
+
+    /// The hex key at a physical position in the block at the left of the keyboard, for a
+    /// `physical` keymap. `row` 0 is the number row and `column` 0 the column with 1, Q, A and Z
+    /// on a US keyboard, so the block is rows and columns 0 to 3; anything else is `None`, as is
+    /// every position for a keymap that goes by characters.
+    pub fn position_key(&self, row: u8, column: u8) -> Option<u8> {
+        if !self.physical || row >= 4 || column >= 4 {
+            return None;
+        }
+
+        Some(LAYOUT_ORDER[(row * 4 + column) as usize])
+    }
+
+    /// The hex key a typed character stands for, if any.
+    pub fn hex_key(&self, c: char) -> Option<u8> {
+        let c = c.to_lowercase().next().unwrap_or(c);
+
+        self.chars.iter().position(|&k| k == c).map(|key| key as u8)
+    }
+
+    /// The character that types hex `key`. Only the low nibble of `key` is used.
+    pub fn key_char(&self, key: u8) -> char {
+        self.chars[(key & 0xF) as usize]
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::PHYSICAL
+    }
+}
+
+impl FromStr for Keymap {
+    type Err = KeymapError;
+
+    /// Parse either a preset's name or a layout of 16 characters.
+    fn from_str(layout: &str) -> Result<Self, Self::Err> {
+        match Keymap::named(layout) {
+            Some(keymap) => Ok(keymap),
+            None => Keymap::from_layout(layout),
+        }
+    }
+}
+
+/// Why a keymap layout couldn't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeymapError {
+    /// The layout had this many characters instead of 16.
+    WrongLength(usize),
+    /// The character was given for two keys.
+    Duplicate(char),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::WrongLength(n) => {
+                write!(
+                    f,
+                    "a keymap needs 16 characters, one per key, not {} (or a preset: physical, \
+                     qwerty, azerty, qwertz or colemak)",
+                    n
+                )
+            }
+            KeymapError::Duplicate(c) => write!(f, "{:?} is in the keymap twice", c),
+        }
+    }
+}
+
+impl Error for KeymapError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_layout_in_keypad_order() {
+        let keymap: Keymap = "1234qwerasdfzxcv".parse().unwrap();
+        assert_eq!(keymap, Keymap::QWERTY);
+        assert!(!keymap.is_physical());
+
+        assert_eq!(keymap.hex_key('1'), Some(0x1));
+        assert_eq!(keymap.hex_key('4'), Some(0xC));
+        assert_eq!(keymap.hex_key('x'), Some(0x0));
+        assert_eq!(keymap.hex_key('v'), Some(0xF));
+        assert_eq!(keymap.hex_key('p'), None);
+    }
+
+    #[test]
+    fn letters_match_either_case() {
+        let keymap = Keymap::from_layout("1234QWERasdfZXCV").unwrap();
+        assert_eq!(keymap, Keymap::QWERTY);
+        assert_eq!(keymap.hex_key('Q'), Some(0x4));
+        assert_eq!(keymap.hex_key('q'), Some(0x4));
+        assert_eq!(keymap.key_char(0x4), 'q');
+
+        assert_eq!(
+            Keymap::from_layout("1234qwerasdfzxcQ"),
+            Err(KeymapError::Duplicate('q'))
+        );
+    }
+
+    #[test]
+    fn layouts_need_16_different_characters() {
+        assert_eq!(
+            Keymap::from_layout("1234qwer"),
+            Err(KeymapError::WrongLength(8))
+        );
+        assert_eq!(
+            Keymap::from_layout("1234qwerasdfzxcvb"),
+            Err(KeymapError::WrongLength(17))
+        );
+        assert_eq!(Keymap::from_layout(""), Err(KeymapError::WrongLength(0)));
+        assert_eq!(
+            Keymap::from_layout("1234qwerasdfzxc1"),
+            Err(KeymapError::Duplicate('1'))
+        );
+
+        // Lengths are counted in characters, not bytes.
+        assert!(Keymap::from_layout("&é\"'azerqsdfwxcv").is_ok());
+        assert_eq!(
+            Keymap::from_layout("&é\"'azerqsdfwxc"),
+            Err(KeymapError::WrongLength(15))
+        );
+    }
+
+    #[test]
+    fn hex_key_and_key_char_are_inverses() {
+        for keymap in [Keymap::QWERTY, Keymap::AZERTY] {
+            for key in 0..16 {
+                assert_eq!(keymap.hex_key(keymap.key_char(key)), Some(key));
+            }
+        }
+        assert_eq!(Keymap::QWERTY.key_char(0x1F), Keymap::QWERTY.key_char(0xF));
+    }
+
+    #[test]
+    fn presets_have_16_different_characters() {
+        let presets = [
+            ("physical", Keymap::PHYSICAL),
+            ("qwerty", Keymap::QWERTY),
+            ("azerty", Keymap::AZERTY),
+            ("qwertz", Keymap::QWERTZ),
+            ("colemak", Keymap::COLEMAK),
+        ];
+
+        for (name, preset) in presets {
+            assert_eq!(Keymap::named(name), Some(preset), "{}", name);
+            assert_eq!(name.parse(), Ok(preset), "{}", name);
+
+            let layout: String = LAYOUT_ORDER
+                .iter()
+                .map(|&key| preset.key_char(key))
+                .collect();
+            assert_eq!(layout.chars().count(), 16, "{}", name);
+            let parsed = Keymap::from_layout(&layout).unwrap();
+            assert_eq!(parsed.chars, preset.chars, "{}", name);
+        }
+
+        assert_eq!(Keymap::named("dvorak"), None);
+        assert_eq!(Keymap::default(), Keymap::PHYSICAL);
+    }
+
+    #[test]
+    fn position_key_follows_the_keypad_layout() {
+        let keymap = Keymap::PHYSICAL;
+        assert!(keymap.is_physical());
+
+        for row in 0..4 {
+            for column in 0..4 {
+                let key = LAYOUT_ORDER[(row * 4 + column) as usize];
+                assert_eq!(keymap.position_key(row, column), Some(key));
+                // Without positions, the same key falls back to its QWERTY character.
+                assert_eq!(keymap.hex_key(Keymap::QWERTY.key_char(key)), Some(key));
+            }
+        }
+        assert_eq!(keymap.position_key(0, 3), Some(0xC));
+        assert_eq!(keymap.position_key(3, 0), Some(0xA));
+
+        assert_eq!(keymap.position_key(4, 0), None);
+        assert_eq!(keymap.position_key(0, 4), None);
+        assert_eq!(keymap.position_key(255, 255), None);
+    }
+
+    #[test]
+    fn keymaps_by_character_have_no_positions() {
+        for keymap in [Keymap::QWERTY, Keymap::AZERTY, Keymap::COLEMAK] {
+            assert!(!keymap.is_physical());
+            assert_eq!(keymap.position_key(0, 0), None);
+        }
+        let custom = Keymap::from_layout("0123456789abcdef").unwrap();
+        assert_eq!(custom.position_key(1, 1), None);
+    }
+}
@@ -0,0 +1,46 @@
+//! The parts of a CHIP-8 frontend that aren't about any one UI, shared by `chip8-cli` and
+//! `chip8-gui`.
+//!
+//! `read_rom` reads a ROM file and checks that it fits, warning about anything off about it, and
+//! `random_seed` picks a seed that's different every run.
+//! `scan_library` lists a directory of ROMs for a frontend's picker, with the `Variant` each is
+//! written for. `RomWatcher` notices the ROM file being rewritten, for frontends that reload it
+//! as it's worked on. `serve_input` takes keypad commands from scripts connecting over a
+//! socket and queues them through `Cpu::input_sender`, and `run_commands` does the same for any
+//! reader, e.g. standard input.
+//!
+//! `Hotkeys` binds keys to the `HotkeyAction`s that `Controls` carries out on a `Cpu`: pausing,
+//! turbo, save slots and reloading with the right `RomSettings`. `Keymap` maps keyboard
+//! characters to the keypad, `Palette` has the colour schemes frontends offer by name, and
+//! `FramePacer` holds a frontend's loop to 60 frames a second. `Chip8Handle` runs a `Cpu` on a
+//! worker thread and talks to the UI over channels, and `record_input` writes the keypad input
+//! to an input log as a game is played.
+//!
+//! They need the filesystem, sockets and threads, or are only of use to a desktop frontend, so
+//! they live here rather than in `chip8-core`, which stays usable on embedded and WASM targets.
+
+mod controls;
+mod handle;
+mod hotkeys;
+mod keymap;
+mod library;
+mod pacer;
+mod palette;
+mod record;
+mod remote;
+mod rom;
+mod seed;
+mod watch;
+
+pub use controls::{Controls, TURBO_FRAMES};
+pub use handle::{Chip8Handle, Command, Event};
+pub use hotkeys::{Hotkey, HotkeyAction, HotkeyError, HotkeyKey, Hotkeys};
+pub use keymap::{Keymap, KeymapError};
+pub use library::{scan_library, RomEntry, ROM_EXTENSIONS};
+pub use pacer::FramePacer;
+pub use palette::Palette;
+pub use record::{record_input, Recording};
+pub use remote::{run_commands, serve_input};
+pub use rom::{check_rom, read_rom};
+pub use seed::random_seed;
+pub use watch::{RomWatcher, WATCH_INTERVAL};
@@ -0,0 +1,100 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chip8_core::{detect_variant, Variant};
+
+/// The file extensions `scan_library` takes for ROMs.
+pub const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+/// A ROM in a library directory, as a frontend's picker lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    /// The file's name without its extension.
+    pub title: String,
+    /// How big the file is, in bytes.
+    pub size: usize,
+    pub variant: Variant,
+}
+
+/// The ROMs in `dir`, not counting subdirectories, sorted by title. A file counts if it has
+/// one of the `ROM_EXTENSIONS`, in any case, and reading it doesn't fail.
+pub fn scan_library(dir: &Path) -> io::Result<Vec<RomEntry>> {
+    let mut entries = Vec::new();
+    for file in fs::read_dir(dir)? {
+        let path = file?.path();
+        let is_rom = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                ROM_EXTENSIONS
+                    .iter()
+                    .any(|rom| rom.eq_ignore_ascii_case(extension))
+            });
+        if !is_rom || !path.is_file() {
+            continue;
+        }
+        let Ok(rom) = fs::read(&path) else {
+            continue;
+        };
+
+        entries.push(RomEntry {
+            title: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: rom.len(),
+            variant: detect_variant(&rom),
+            path,
+        });
+    }
+    entries.sort_by_cached_key(|entry| entry.title.to_lowercase());
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn scan_library_lists_the_roms_in_order() {
+        let dir = env::temp_dir().join(format!("chip8-library-{}", std::process::id()));
+        fs::create_dir_all(dir.join("more.ch8")).unwrap();
+        fs::write(dir.join("more.ch8").join("nested.ch8"), [0x12, 0x00]).unwrap();
+        fs::write(dir.join("gamma.sc8"), [0x00, 0xFF, 0x12, 0x02]).unwrap();
+        fs::write(dir.join("alpha.CH8"), [0x12, 0x00]).unwrap();
+        fs::write(dir.join("Delta.xo8"), [0xF0, 0x02, 0x12, 0x02, 0x00]).unwrap();
+        fs::write(dir.join("Beta.c8"), [0x00, 0xE0, 0x12, 0x02]).unwrap();
+        fs::write(dir.join("notes.txt"), "not a ROM").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.join("missing"), dir.join("broken.ch8")).unwrap();
+
+        let entries = scan_library(&dir).unwrap();
+        let listed: Vec<_> = entries
+            .iter()
+            .map(|entry| (entry.title.as_str(), entry.size, entry.variant))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("alpha", 2, Variant::Chip8),
+                ("Beta", 4, Variant::Chip8),
+                ("Delta", 5, Variant::XoChip),
+                ("gamma", 4, Variant::SuperChip),
+            ]
+        );
+        assert_eq!(entries[0].path, dir.join("alpha.CH8"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scan_library_fails_on_a_missing_directory() {
+        let dir = env::temp_dir().join(format!("chip8-no-library-{}", std::process::id()));
+        assert!(scan_library(&dir).is_err());
+    }
+}
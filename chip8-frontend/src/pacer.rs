@@ -0,0 +1,68 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Holds a loop to 60 frames per second.
+///
+/// Use `wait` in a loop you own, or `poll` and `deadline` from an event loop that sleeps by
+/// itself, like winit's. Each frame is due a whole number of 60ths of a second after the
+/// first, rather than a rounded-off 60th after the one before, so a long session doesn't
+/// drift away from the wall clock.
+pub struct FramePacer {
+    /// When counting started.
+    start: Instant,
+    /// Frames since `start`, including the one that's due next.
+    frames: u64,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        FramePacer {
+            start: Instant::now(),
+            frames: 0,
+        }
+    }
+
+    /// Sleep until the next frame is due.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        let deadline = self.deadline();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+
+        self.advance(Instant::now());
+    }
+
+    /// Whether a frame is due, in which case the caller should run it now.
+    pub fn poll(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.deadline() {
+            return false;
+        }
+
+        self.advance(now);
+        true
+    }
+
+    /// When the next frame is due.
+    pub fn deadline(&self) -> Instant {
+        self.start + Duration::from_nanos(self.frames * 1_000_000_000 / 60)
+    }
+
+    /// If we've fallen behind, e.g. because the window was being dragged, start counting again
+    /// from now rather than racing to catch up.
+    fn advance(&mut self, now: Instant) {
+        self.frames += 1;
+
+        if self.deadline() < now {
+            self.start = now;
+            self.frames = 1;
+        }
+    }
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
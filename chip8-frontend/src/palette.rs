@@ -0,0 +1,97 @@
+/// The colours of lit and unlit pixels, as RGB.
+///
+/// Programs that only draw to the first plane, which is all of them before XO-CHIP, only ever
+/// show the foreground and background. XO-CHIP's second plane adds the other two colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Pixels lit in the first plane only.
+    pub foreground: [u8; 3],
+    pub background: [u8; 3],
+    /// Pixels lit in the second plane only.
+    pub plane2: [u8; 3],
+    /// Pixels lit in both planes.
+    pub both_planes: [u8; 3],
+}
+
+impl Palette {
+    /// White on black.
+    pub const MONOCHROME: Palette = Palette {
+        foreground: [0xFF, 0xFF, 0xFF],
+        background: [0x00, 0x00, 0x00],
+        plane2: [0xAA, 0xAA, 0xAA],
+        both_planes: [0x55, 0x55, 0x55],
+    };
+    /// A P1 green phosphor tube.
+    pub const GREEN: Palette = Palette {
+        foreground: [0x33, 0xFF, 0x66],
+        background: [0x0A, 0x1A, 0x0F],
+        plane2: [0x1F, 0x99, 0x3D],
+        both_planes: [0xA0, 0xFF, 0xB8],
+    };
+    /// A P3 amber phosphor tube.
+    pub const AMBER: Palette = Palette {
+        foreground: [0xFF, 0xB0, 0x00],
+        background: [0x1A, 0x10, 0x00],
+        plane2: [0x99, 0x6A, 0x00],
+        both_planes: [0xFF, 0xD8, 0x80],
+    };
+    /// The greenish-grey of an unlit handheld LCD, and its three darker shades.
+    pub const LCD: Palette = Palette {
+        foreground: [0x0F, 0x38, 0x0F],
+        background: [0x9B, 0xBC, 0x0F],
+        plane2: [0x8B, 0xAC, 0x0F],
+        both_planes: [0x30, 0x62, 0x30],
+    };
+    /// Black on white, for the most legible screenshots.
+    pub const HIGH_CONTRAST: Palette = Palette {
+        foreground: [0x00, 0x00, 0x00],
+        background: [0xFF, 0xFF, 0xFF],
+        plane2: [0xCC, 0x00, 0x00],
+        both_planes: [0x00, 0x00, 0xCC],
+    };
+
+    /// The presets `named` accepts.
+    pub const NAMES: [&'static str; 5] = ["mono", "green", "amber", "lcd", "high-contrast"];
+
+    /// A preset by name, as given to a frontend's `--palette`.
+    pub fn named(name: &str) -> Option<Palette> {
+        let palette = match name {
+            "mono" => Palette::MONOCHROME,
+            "green" => Palette::GREEN,
+            "amber" => Palette::AMBER,
+            "lcd" => Palette::LCD,
+            "high-contrast" => Palette::HIGH_CONTRAST,
+            _ => return None,
+        };
+
+        Some(palette)
+    }
+
+    /// The colour for a pixel of `Framebuffer::color` `color`: 0 for the background, 1 for the
+    /// foreground, 2 for the second plane and 3 for both.
+    pub fn color(&self, color: u8) -> [u8; 3] {
+        match color {
+            0 => self.background,
+            1 => self.foreground,
+            2 => self.plane2,
+            _ => self.both_planes,
+        }
+    }
+
+    /// The colour `level / 255` of the way from the background to colour `color`.
+    pub fn blend(&self, color: u8, level: u8) -> [u8; 3] {
+        let mut blended = self.background;
+        for (c, fg) in blended.iter_mut().zip(self.color(color)) {
+            let (bg, fg, level) = (*c as i32, fg as i32, level as i32);
+            *c = (bg + (fg - bg) * level / 255) as u8;
+        }
+
+        blended
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::MONOCHROME
+    }
+}
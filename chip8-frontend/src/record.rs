@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use chip8_core::{Cpu, InputLogWriter, Rng, RunEnd, RunSetup};
+
+/// An input log being written by `record_input` as a game is played.
+pub struct Recording {
+    log: Arc<Mutex<InputLogWriter<File>>>,
+}
+
+impl Recording {
+    /// End the log with where the run stopped, or return the first error writing it hit.
+    pub fn finish(self, end: RunEnd) -> io::Result<()> {
+        self.log
+            .lock()
+            .map_err(|_| io::Error::other("the input log was poisoned"))?
+            .finish(end)
+    }
+}
+
+/// Write every keypad press and release to `path` while the game is played on `cpu`, which was
+/// built from `setup`. The log needs finishing with where the run ended once it's over.
+pub fn record_input<R: Rng, const MEM: usize, const STACK: usize>(
+    cpu: &mut Cpu<R, MEM, STACK>,
+    path: &Path,
+    setup: &RunSetup,
+) -> io::Result<Recording> {
+    let log = Arc::new(Mutex::new(InputLogWriter::new(File::create(path)?, setup)?));
+    let recorder = Arc::clone(&log);
+    cpu.on_input(move |event| {
+        if let Ok(mut log) = recorder.lock() {
+            log.write(event);
+        }
+    });
+
+    Ok(Recording { log })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use chip8_core::{InputEvent, InputLog};
+
+    use super::*;
+
+    #[test]
+    fn records_the_input_the_game_gets() {
+        let path = std::env::temp_dir().join(format!("chip8-record-{}.c8log", std::process::id()));
+        let rom = [0x12, 0x00]; // JP 0x200
+        let setup = RunSetup::for_rom(&rom);
+        let mut cpu = setup.builder(&rom).build().unwrap();
+
+        let recording = record_input(&mut cpu, &path, &setup).unwrap();
+        cpu.press_key(0x5);
+        cpu.run_frame().unwrap();
+        cpu.release_key(0x5);
+        cpu.run_frame().unwrap();
+        recording.finish(RunEnd::of(&cpu)).unwrap();
+
+        let log = InputLog::decode(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(*log.setup(), setup);
+        assert_eq!(
+            log.events(),
+            [InputEvent::press(0x5, 0), InputEvent::release(0x5, 1)]
+        );
+        assert_eq!(log.end(), Some(RunEnd::of(&cpu)));
+    }
+}
@@ -0,0 +1,282 @@
+use std::fmt;
+#[cfg(unix)]
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::thread;
+
+use chip8_core::{InputEvent, InputSender};
+
+/// Accept keypad commands at `address` on a background thread, so that scripts and bots can
+/// play. `address` is `HOST:PORT` for TCP or, on Unix, `unix:PATH` for a Unix socket. A socket
+/// left over from an earlier run at `PATH` is replaced, but any other file there is an
+/// `AlreadyExists` error rather than being deleted, in case it's, say, the ROM.
+///
+/// Any number of clients can connect at once, each sending one command per line:
+///
+/// ```text
+/// press KEY [FRAME]
+/// release KEY [FRAME]
+/// tap KEY [FRAME]
+/// ```
+///
+/// where `KEY` is a hex digit and `FRAME` the `Cpu::frame` to apply it at, by default the next
+/// frame boundary. `tap` presses and releases the key in the same frame, which the program
+/// still sees. Each command is answered with `ok` or `error: ` and why; blank lines and lines
+/// starting with `#` are ignored.
+///
+/// The address is bound before this returns, so a port that's taken is an error here rather
+/// than on the thread.
+pub fn serve_input(address: &str, sender: InputSender) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(path) = address.strip_prefix("unix:") {
+        remove_stale_socket(Path::new(path))?;
+        let listener = UnixListener::bind(path)?;
+        thread::spawn(move || accept(listener.incoming(), sender, UnixStream::try_clone));
+        return Ok(());
+    }
+
+    let listener = TcpListener::bind(address)?;
+    thread::spawn(move || accept(listener.incoming(), sender, TcpStream::try_clone));
+
+    Ok(())
+}
+
+/// Delete the socket at `path`, if there is one, so that it can be bound again.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path),
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists and isn't a socket", path.display()),
+        )),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Serve each connection from `streams` on a thread of its own. `try_clone` gives a second
+/// handle on a connection to read from while answering on the first.
+fn accept<S: Read + Write + Send + 'static>(
+    streams: impl Iterator<Item = io::Result<S>>,
+    sender: InputSender,
+    try_clone: fn(&S) -> io::Result<S>,
+) {
+    for stream in streams.flatten() {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            if let Ok(reader) = try_clone(&stream) {
+                let _ = run_commands(BufReader::new(reader), stream, &sender);
+            }
+        });
+    }
+}
+
+/// Carry out the commands `serve_input` takes, one per line of `reader`, answering each on
+/// `writer`, until `reader` ends or the machine is dropped. Useful on its own for a pipe or
+/// standard input.
+pub fn run_commands(
+    reader: impl BufRead,
+    mut writer: impl Write,
+    sender: &InputSender,
+) -> io::Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match parse_command(line) {
+            Ok(events) => {
+                for event in events.into_iter().flatten() {
+                    if !sender.send(event) {
+                        return Ok(());
+                    }
+                }
+                writeln!(writer, "ok")?;
+            }
+            Err(err) => writeln!(writer, "error: {}", err)?,
+        }
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+/// The events a command queues: a press or a release, or both for `tap`.
+fn parse_command(line: &str) -> Result<[Option<InputEvent>; 2], CommandError> {
+    let mut words = line.split_whitespace();
+    let command = words.next().unwrap_or_default();
+    let key = words.next().ok_or(CommandError::MissingKey)?;
+    let frame = match words.next() {
+        Some(frame) => frame.parse().map_err(|_| CommandError::BadFrame)?,
+        None => 0,
+    };
+    if words.next().is_some() {
+        return Err(CommandError::TooManyWords);
+    }
+
+    let key = match key.as_bytes() {
+        [_] => u8::from_str_radix(key, 16).map_err(|_| CommandError::BadKey)?,
+        _ => return Err(CommandError::BadKey),
+    };
+
+    match command {
+        "press" => Ok([Some(InputEvent::press(key, frame)), None]),
+        "release" => Ok([Some(InputEvent::release(key, frame)), None]),
+        "tap" => Ok([
+            Some(InputEvent::press(key, frame)),
+            Some(InputEvent::release(key, frame)),
+        ]),
+        _ => Err(CommandError::UnknownCommand),
+    }
+}
+
+/// Why a remote command couldn't be carried out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CommandError {
+    UnknownCommand,
+    MissingKey,
+    BadKey,
+    BadFrame,
+    TooManyWords,
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand => write!(f, "the commands are press, release and tap"),
+            CommandError::MissingKey => write!(f, "a command needs a key"),
+            CommandError::BadKey => write!(f, "keys are a single hex digit, 0 to F"),
+            CommandError::BadFrame => write!(f, "a frame is a number"),
+            CommandError::TooManyWords => write!(f, "a command is COMMAND KEY [FRAME]"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Shutdown;
+    use std::sync::{Arc, Mutex};
+
+    use chip8_core::Chip8;
+
+    use super::*;
+
+    /// A machine that records every input event it's given, and a sender to give it some.
+    fn machine() -> (Chip8, InputSender, Arc<Mutex<Vec<InputEvent>>>) {
+        let mut cpu = Chip8::builder().rom(&[0x12, 0x00]).build().unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&events);
+        cpu.on_input(move |event| recorder.lock().unwrap().push(event));
+        let sender = cpu.input_sender();
+
+        (cpu, sender, events)
+    }
+
+    #[test]
+    fn parse_command_reads_each_command() {
+        assert_eq!(
+            parse_command("press a"),
+            Ok([Some(InputEvent::press(0xA, 0)), None])
+        );
+        assert_eq!(
+            parse_command("release F 12"),
+            Ok([Some(InputEvent::release(0xF, 12)), None])
+        );
+        assert_eq!(
+            parse_command("tap 0 3"),
+            Ok([
+                Some(InputEvent::press(0, 3)),
+                Some(InputEvent::release(0, 3))
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_command_rejects_bad_commands() {
+        assert_eq!(parse_command("hold 1"), Err(CommandError::UnknownCommand));
+        assert_eq!(parse_command("press"), Err(CommandError::MissingKey));
+        assert_eq!(parse_command("press g"), Err(CommandError::BadKey));
+        assert_eq!(parse_command("press 10"), Err(CommandError::BadKey));
+        assert_eq!(parse_command("press é"), Err(CommandError::BadKey));
+        assert_eq!(parse_command("press 1 soon"), Err(CommandError::BadFrame));
+        assert_eq!(
+            parse_command("press 1 2 3"),
+            Err(CommandError::TooManyWords)
+        );
+    }
+
+    #[test]
+    fn run_commands_answers_each_command() {
+        let (_cpu, sender, _) = machine();
+        let script = "press 1\n\n# a comment\nwiggle 2\npress x\n  tap 2 5  \n";
+        let mut answers = Vec::new();
+        run_commands(script.as_bytes(), &mut answers, &sender).unwrap();
+
+        let answers = String::from_utf8(answers).unwrap();
+        let answers: Vec<_> = answers.lines().collect();
+        assert_eq!(answers.len(), 4);
+        assert_eq!(answers[0], "ok");
+        assert!(answers[1].starts_with("error: "));
+        assert!(answers[2].starts_with("error: "));
+        assert_eq!(answers[3], "ok");
+    }
+
+    #[test]
+    fn serves_a_script_over_tcp() {
+        let (mut cpu, sender, events) = machine();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || accept(listener.incoming(), sender, TcpStream::try_clone));
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"press 5\ntap c 2\nbogus\n").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut answers = String::new();
+        client.read_to_string(&mut answers).unwrap();
+        assert_eq!(answers.lines().filter(|&answer| answer == "ok").count(), 2);
+
+        for _ in 0..3 {
+            cpu.run_frame().unwrap();
+        }
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                InputEvent::press(5, 0),
+                InputEvent::press(0xC, 2),
+                InputEvent::release(0xC, 2)
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_sockets_only_replace_old_sockets() {
+        let dir = std::env::temp_dir().join(format!("chip8-remote-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (_cpu, sender, _) = machine();
+
+        let rom = dir.join("game.ch8");
+        fs::write(&rom, [0x12, 0x00]).unwrap();
+        let address = format!("unix:{}", rom.display());
+        let err = serve_input(&address, sender.clone()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read(&rom).unwrap(), [0x12, 0x00]);
+
+        let socket = dir.join("input.sock");
+        drop(UnixListener::bind(&socket).unwrap());
+        serve_input(&format!("unix:{}", socket.display()), sender).unwrap();
+        UnixStream::connect(&socket).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,57 @@
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use chip8_core::{validate_rom, DEFAULT_MEMORY_SIZE, PROGRAM_START};
+
+/// Read the ROM at `path`, checking it will fit and warning about anything off about it.
+pub fn read_rom(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let rom = fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+
+    check_rom(path.display(), rom)
+}
+
+/// Check that `rom`, read from `name`, will fit in a machine from `Chip8::builder()`, and warn
+/// on stderr about anything off about it, for ROMs that come from somewhere other than a file,
+/// e.g. standard input.
+pub fn check_rom(name: impl Display, rom: Vec<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let warnings = validate_rom(&rom, DEFAULT_MEMORY_SIZE - PROGRAM_START)
+        .map_err(|err| format!("{}: {}", name, err))?;
+    for warning in warnings {
+        eprintln!("warning: {}: {}", name, warning);
+    }
+
+    Ok(rom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_roms_that_fit() {
+        let path = std::env::temp_dir().join(format!("chip8-read-rom-{}.ch8", std::process::id()));
+        fs::write(&path, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+        let rom = read_rom(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rom.unwrap(), [0x00, 0xE0, 0x12, 0x00]);
+
+        let err = read_rom(&path).unwrap_err().to_string();
+        assert!(err.starts_with(&format!("{}: ", path.display())), "{}", err);
+    }
+
+    #[test]
+    fn rejects_roms_that_dont_fit() {
+        let max = DEFAULT_MEMORY_SIZE - PROGRAM_START;
+        assert_eq!(check_rom("game", vec![0x12; max]).unwrap().len(), max);
+        // Warnings don't stop it loading.
+        assert!(check_rom("odd", vec![0x00, 0xE0, 0x12]).is_ok());
+
+        let err = check_rom("big", vec![0x12; max + 1])
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("big: "), "{}", err);
+        assert!(check_rom("empty", Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A seed for the random number generator that's different every run, so that `CXKK` games
+/// don't play out the same way each time. An input log records whichever seed is used, so
+/// replays still match.
+pub fn random_seed() -> u32 {
+    // `RandomState` is keyed from the OS's entropy, and the clock varies it within a process.
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+
+    hasher.finish() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_differ_between_calls() {
+        let seeds: Vec<u32> = (0..8).map(|_| random_seed()).collect();
+        assert!(seeds.iter().any(|&seed| seed != seeds[0]), "{:?}", seeds);
+    }
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often `RomWatcher::changed` looks at the file.
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A file's modification time and size, or `None` if it can't be read.
+type Stamp = Option<(SystemTime, u64)>;
+
+/// Notices a ROM file being rewritten, e.g. by an assembler, so a frontend can reload it.
+///
+/// Call `changed` every frame. It checks the file's modification time and size every
+/// `WATCH_INTERVAL`, which works everywhere without a file notification API, and only reports
+/// a change once the file has looked the same for a whole interval, so that a ROM still being
+/// written out isn't loaded halfway.
+pub struct RomWatcher {
+    path: PathBuf,
+    /// What the file looked like when a change was last reported, or watching started.
+    seen: Stamp,
+    /// What it looked like at the last check, if that was a change that hasn't settled yet.
+    pending: Option<Stamp>,
+    next_check: Instant,
+}
+
+impl RomWatcher {
+    /// Watch `path`, the ROM as it is now counting as unchanged.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        RomWatcher {
+            seen: stamp(&path),
+            path,
+            pending: None,
+            next_check: Instant::now() + WATCH_INTERVAL,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file has been rewritten since the last time this returned true. A file
+    /// that's been deleted counts as changed once it's back.
+    pub fn changed(&mut self) -> bool {
+        let now = Instant::now();
+        if now < self.next_check {
+            return false;
+        }
+        self.next_check = now + WATCH_INTERVAL;
+
+        self.check()
+    }
+
+    /// Look at the file now, returning whether a change has settled since the last look.
+    fn check(&mut self) -> bool {
+        let current = stamp(&self.path);
+        if current == self.seen {
+            self.pending = None;
+            return false;
+        }
+        if self.pending != Some(current) {
+            self.pending = Some(current);
+            return false;
+        }
+
+        self.seen = current;
+        self.pending = None;
+        current.is_some()
+    }
+}
+
+fn stamp(path: &Path) -> Stamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::File;
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    /// A file of its own for each test, in a fresh directory.
+    fn rom(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("chip8-watch-{}-{}", std::process::id(), name));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.ch8");
+        write(&path, b"\x12\x00", 1);
+        path
+    }
+
+    /// Replace `path` with `bytes`, modified `secs` seconds into 1970.
+    fn write(path: &Path, bytes: &[u8], secs: u64) {
+        fs::write(path, bytes).unwrap();
+        File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn reports_a_rewrite_once_it_settles() {
+        let path = rom("settles");
+        let mut watcher = RomWatcher::new(&path);
+        assert!(!watcher.check());
+
+        write(&path, b"\x12\x00\x00", 2);
+        assert!(!watcher.check());
+        write(&path, b"\x12\x00\x00\xE0", 3);
+        assert!(!watcher.check());
+        assert!(watcher.check());
+        assert!(!watcher.check());
+        assert!(!watcher.check());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn writing_the_same_file_back_isnt_a_change() {
+        let path = rom("same");
+        let mut watcher = RomWatcher::new(&path);
+
+        write(&path, b"\x13\x00", 2);
+        assert!(!watcher.check());
+        write(&path, b"\x12\x00", 1);
+        assert!(!watcher.check());
+        assert!(!watcher.check());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn survives_the_file_going_missing_during_a_save() {
+        let path = rom("rename");
+        let mut watcher = RomWatcher::new(&path);
+
+        // An editor saving by writing a new file and renaming it over the old one.
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.check());
+        let new = path.with_extension("tmp");
+        write(&new, b"\x12\x02\x00\xE0", 2);
+        fs::rename(&new, &path).unwrap();
+        assert!(!watcher.check());
+        assert!(watcher.check());
+
+        // Gone for long enough to settle, which isn't reported, then back.
+        fs::remove_file(&path).unwrap();
+        assert!(!watcher.check());
+        assert!(!watcher.check());
+        assert!(!watcher.check());
+        write(&path, b"\x12\x00", 3);
+        assert!(!watcher.check());
+        assert!(watcher.check());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
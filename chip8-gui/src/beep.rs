@@ -0,0 +1,485 @@
+//! The buzzer's tone, or an XO-CHIP program's sample pattern, and playing it for the backends
+//! that have no audio of their own: straight through cpal with the `cpal` feature, or through
+//! rodio with the `audio` feature, or in a browser through `webaudio` with the `webaudio`
+//! feature. SDL plays the same `Voice` itself.
+
+use std::cell::Cell;
+use std::error::Error;
+use std::f32::consts::TAU;
+use std::fmt;
+use std::ops::RangeInclusive;
+#[cfg(feature = "cpal")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(any(feature = "cpal", feature = "audio"))]
+use std::sync::mpsc::{self, Receiver, Sender};
+#[cfg(feature = "cpal")]
+use std::sync::Arc;
+#[cfg(all(feature = "audio", not(feature = "cpal")))]
+use std::time::Duration;
+
+use chip8_core::{AudioPattern, PatternPlayer};
+#[cfg(feature = "cpal")]
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+#[cfg(feature = "cpal")]
+use cpal::{FromSample, SizedSample};
+#[cfg(all(feature = "audio", not(feature = "cpal")))]
+use rodio::{ChannelCount, DeviceSinkBuilder, MixerDeviceSink, Player, SampleRate, Source};
+
+use crate::volume::Gain;
+#[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+use crate::webaudio::WebAudio;
+
+/// The tone's frequency when `--tone` isn't given.
+pub const DEFAULT_TONE_HZ: f32 = 440.0;
+/// How loud the buzzer is at full volume, out of 1.
+const VOLUME: f32 = 0.1;
+
+/// The shape of the buzzer's tone.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Waveform {
+    /// Harsh, like the COSMAC VIP's.
+    #[default]
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /// The names `Waveform::named` knows, for usage messages.
+    pub const NAMES: [&'static str; 3] = ["square", "sine", "triangle"];
+
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            _ => None,
+        }
+    }
+}
+
+/// What the buzzer sounds like, from `--waveform` and `--tone`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tone {
+    pub waveform: Waveform,
+    /// In Hz.
+    pub frequency: f32,
+}
+
+impl Tone {
+    /// Parse a `--tone` frequency, in Hz, as something between 20Hz and 20kHz.
+    pub fn parse_frequency(text: &str) -> Result<f32, ToneError> {
+        match text.parse() {
+            Ok(hz) if (20.0..=20_000.0).contains(&hz) => Ok(hz),
+            _ => Err(ToneError(text.to_string())),
+        }
+    }
+}
+
+impl Default for Tone {
+    fn default() -> Self {
+        Tone {
+            waveform: Waveform::default(),
+            frequency: DEFAULT_TONE_HZ,
+        }
+    }
+}
+
+/// How the buzzer's output stream is set up, from `--sample-rate` and `--audio-buffer`. The
+/// device's own defaults are used for anything left as `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AudioConfig {
+    /// Samples a second.
+    pub sample_rate: Option<u32>,
+    /// Frames in each buffer the device asks for: fewer for less latency, more if the sound
+    /// crackles.
+    pub buffer_size: Option<u32>,
+}
+
+impl AudioConfig {
+    /// The sample rates `--sample-rate` accepts.
+    pub const SAMPLE_RATES: RangeInclusive<u32> = 8_000..=192_000;
+    /// The buffer sizes `--audio-buffer` accepts.
+    pub const BUFFER_SIZES: RangeInclusive<u32> = 16..=16_384;
+}
+
+/// A `--tone` that isn't a frequency people can hear.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToneError(String);
+
+impl fmt::Display for ToneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} isn't a frequency from 20 to 20000Hz", self.0)
+    }
+}
+
+impl Error for ToneError {}
+
+/// Makes a `Tone`'s samples, one at a time.
+///
+/// Square waves are averaged over each sample's span, which rounds off their edges just enough
+/// that tones that aren't a whole number of samples long don't alias into a rough buzz.
+pub struct Oscillator {
+    waveform: Waveform,
+    /// How far through the current cycle, from 0 up to 1.
+    phase: f32,
+    /// How far each sample moves `phase` on.
+    step: f32,
+}
+
+impl Oscillator {
+    pub fn new(tone: Tone, sample_rate: u32) -> Self {
+        Oscillator {
+            waveform: tone.waveform,
+            phase: 0.0,
+            step: tone.frequency / sample_rate as f32,
+        }
+    }
+
+    /// The next sample, from `-VOLUME` to `VOLUME`.
+    pub fn next_sample(&mut self) -> f32 {
+        let next_phase = (self.phase + self.step) % 1.0;
+        let value = match self.waveform {
+            Waveform::Square => (square_area(next_phase) - square_area(self.phase)) / self.step,
+            Waveform::Sine => (self.phase * TAU).sin(),
+            Waveform::Triangle => 4.0 * (self.phase - 0.5).abs() - 1.0,
+        };
+        self.phase = next_phase;
+
+        value * VOLUME
+    }
+}
+
+/// The area under a square wave's cycle from its start up to `phase`. Whole cycles add nothing,
+/// so the area between any two phases is the difference.
+fn square_area(phase: f32) -> f32 {
+    if phase < 0.5 {
+        phase
+    } else {
+        1.0 - phase
+    }
+}
+
+/// Makes the buzzer's samples: the `Tone`'s, until an XO-CHIP program loads a pattern to play
+/// instead, either way at the volume `gain` says.
+pub struct Voice {
+    oscillator: Oscillator,
+    pattern: Option<PatternPlayer>,
+    sample_rate: u32,
+    gain: Gain,
+}
+
+impl Voice {
+    pub fn new(tone: Tone, sample_rate: u32, gain: Gain) -> Self {
+        Voice {
+            oscillator: Oscillator::new(tone, sample_rate),
+            pattern: None,
+            sample_rate,
+            gain,
+        }
+    }
+
+    /// Play `pattern`, as `AudioSink::set_pattern` asks, or go back to the tone for `None`.
+    pub fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        let sample_rate = self.sample_rate;
+        match (&mut self.pattern, pattern) {
+            (Some(player), Some(pattern)) => player.set_pattern(pattern),
+            (player, pattern) => {
+                *player = pattern.map(|pattern| PatternPlayer::new(pattern, sample_rate));
+            }
+        }
+    }
+
+    /// The next sample, from `-VOLUME` to `VOLUME` at full volume.
+    pub fn next_sample(&mut self) -> f32 {
+        let sample = match &mut self.pattern {
+            Some(player) => player.next_sample() * VOLUME,
+            None => self.oscillator.next_sample(),
+        };
+
+        sample * self.gain.get()
+    }
+}
+
+/// The tone on the default output device, started and stopped with the sound timer.
+///
+/// Without the `cpal`, `audio` or `webaudio` feature, or if there's no output device, it stays
+/// silent, and `flashing` says when to show the beep on screen instead. So does a browser that
+/// hasn't let the page play sound yet.
+pub struct Beeper {
+    sounding: Cell<bool>,
+    /// Whether `flashing` follows the buzzer: with `--visual-beep`, or when it can't be heard.
+    visual: bool,
+    /// The stream, which plays silence unless the flag is set.
+    #[cfg(feature = "cpal")]
+    stream: Option<(cpal::Stream, Arc<AtomicBool>)>,
+    /// The voice, paused while the buzzer is off. Dropped before `sink`, which it plays through.
+    #[cfg(all(feature = "audio", not(feature = "cpal")))]
+    player: Option<Player>,
+    #[cfg(all(feature = "audio", not(feature = "cpal")))]
+    _sink: Option<MixerDeviceSink>,
+    #[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+    web: Option<WebAudio>,
+    /// New patterns for the audio thread's `Voice`.
+    #[cfg(any(feature = "cpal", feature = "audio"))]
+    patterns: Sender<Option<AudioPattern>>,
+}
+
+impl Beeper {
+    /// A buzzer playing `tone`, at `gain`, that also flashes the screen if `visual` is set.
+    pub fn new(tone: Tone, config: AudioConfig, gain: Gain, visual: bool) -> Self {
+        #[cfg(feature = "cpal")]
+        {
+            let (patterns, receiver) = mpsc::channel();
+            let stream = open_cpal(tone, config, gain, receiver)
+                .map_err(|err| eprintln!("warning: audio is unavailable: {}", err))
+                .ok();
+
+            Beeper {
+                sounding: Cell::new(false),
+                visual: visual || stream.is_none(),
+                stream,
+                patterns,
+            }
+        }
+
+        #[cfg(all(feature = "audio", not(feature = "cpal")))]
+        {
+            let sink = DeviceSinkBuilder::from_default_device()
+                .and_then(|mut builder| {
+                    if let Some(rate) = config.sample_rate.and_then(SampleRate::new) {
+                        builder = builder.with_sample_rate(rate);
+                    }
+                    if let Some(frames) = config.buffer_size {
+                        builder = builder.with_buffer_size(rodio::cpal::BufferSize::Fixed(frames));
+                    }
+                    builder.open_sink_or_fallback()
+                })
+                .map_err(|err| eprintln!("warning: audio is unavailable: {}", err))
+                .ok();
+            let (patterns, receiver) = mpsc::channel();
+            let player = sink.as_ref().map(|sink| {
+                let sample_rate = sink.config().sample_rate();
+
+                let player = Player::connect_new(sink.mixer());
+                player.pause();
+                player.append(VoiceSource {
+                    voice: Voice::new(tone, sample_rate.get(), gain),
+                    patterns: receiver,
+                    sample_rate,
+                });
+                player
+            });
+
+            Beeper {
+                sounding: Cell::new(false),
+                visual: visual || player.is_none(),
+                player,
+                _sink: sink,
+                patterns,
+            }
+        }
+
+        #[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+        {
+            let web = WebAudio::open(tone, config, gain)
+                .map_err(|err| {
+                    let warning = format!("warning: audio is unavailable: {:?}", err);
+                    web_sys::console::warn_1(&warning.into());
+                })
+                .ok();
+
+            Beeper {
+                sounding: Cell::new(false),
+                visual: visual || web.is_none(),
+                web,
+            }
+        }
+
+        #[cfg(not(any(feature = "cpal", feature = "audio", feature = "webaudio")))]
+        {
+            let _ = (tone, config, gain, visual);
+            Beeper {
+                sounding: Cell::new(false),
+                visual: true,
+            }
+        }
+    }
+
+    /// Sound the buzzer, as `AudioSink::start_beep` asks.
+    pub fn start(&self) {
+        self.sounding.set(true);
+
+        #[cfg(feature = "cpal")]
+        if let Some((_, on)) = &self.stream {
+            on.store(true, Ordering::Relaxed);
+        }
+
+        #[cfg(all(feature = "audio", not(feature = "cpal")))]
+        if let Some(player) = &self.player {
+            player.play();
+        }
+
+        #[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+        if let Some(web) = &self.web {
+            web.set_on(true);
+        }
+    }
+
+    /// Silence the buzzer, as `AudioSink::stop_beep` asks.
+    pub fn stop(&self) {
+        self.sounding.set(false);
+
+        #[cfg(feature = "cpal")]
+        if let Some((_, on)) = &self.stream {
+            on.store(false, Ordering::Relaxed);
+        }
+
+        #[cfg(all(feature = "audio", not(feature = "cpal")))]
+        if let Some(player) = &self.player {
+            player.pause();
+        }
+
+        #[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+        if let Some(web) = &self.web {
+            web.set_on(false);
+        }
+    }
+
+    /// Whether the screen should show the beep, with `Screen::set_inverted`: while the buzzer
+    /// sounds, if it's visual.
+    pub fn flashing(&self) -> bool {
+        #[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+        let visual = self.visual || self.web.as_ref().is_some_and(|web| !web.is_running());
+        #[cfg(not(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio")))))]
+        let visual = self.visual;
+
+        visual && self.sounding.get()
+    }
+
+    /// Play `pattern` from now on, or the tone for `None`, as `AudioSink::set_pattern` asks.
+    pub fn set_pattern(&self, pattern: Option<AudioPattern>) {
+        #[cfg(any(feature = "cpal", feature = "audio"))]
+        let _ = self.patterns.send(pattern);
+
+        #[cfg(all(feature = "webaudio", not(any(feature = "cpal", feature = "audio"))))]
+        if let Some(web) = &self.web {
+            web.set_pattern(pattern);
+        }
+
+        #[cfg(not(any(feature = "cpal", feature = "audio", feature = "webaudio")))]
+        let _ = pattern;
+    }
+
+    /// Start or stop to match `sounding`, after something `run_frame_with` doesn't hear about,
+    /// such as pausing, a reset or loading a state.
+    pub fn follow(&self, sounding: bool) {
+        if sounding {
+            self.start();
+        } else {
+            self.stop();
+        }
+    }
+}
+
+/// Play `tone`, or the patterns from `patterns`, on the default output device whenever the flag
+/// that comes back is set, and silence otherwise.
+#[cfg(feature = "cpal")]
+fn open_cpal(
+    tone: Tone,
+    audio: AudioConfig,
+    gain: Gain,
+    patterns: Receiver<Option<AudioPattern>>,
+) -> Result<(cpal::Stream, Arc<AtomicBool>), Box<dyn Error>> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or("there's no output device")?;
+    let supported = device.default_output_config()?;
+    let mut config = supported.config();
+    if let Some(rate) = audio.sample_rate {
+        config.sample_rate = rate;
+    }
+    if let Some(frames) = audio.buffer_size {
+        config.buffer_size = cpal::BufferSize::Fixed(frames);
+    }
+    let on = Arc::new(AtomicBool::new(false));
+
+    let voice = Voice::new(tone, config.sample_rate, gain);
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_cpal::<f32>(&device, &config, voice, patterns, &on)?,
+        cpal::SampleFormat::I16 => build_cpal::<i16>(&device, &config, voice, patterns, &on)?,
+        cpal::SampleFormat::U16 => build_cpal::<u16>(&device, &config, voice, patterns, &on)?,
+        format => return Err(format!("the output device wants {} samples", format).into()),
+    };
+    stream.play()?;
+
+    Ok((stream, on))
+}
+
+#[cfg(feature = "cpal")]
+fn build_cpal<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut voice: Voice,
+    patterns: Receiver<Option<AudioPattern>>,
+    on: &Arc<AtomicBool>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let on = Arc::clone(on);
+
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            for pattern in patterns.try_iter() {
+                voice.set_pattern(pattern);
+            }
+            let on = on.load(Ordering::Relaxed);
+            for frame in data.chunks_mut(channels) {
+                let sample = if on { voice.next_sample() } else { 0.0 };
+                frame.fill(T::from_sample(sample));
+            }
+        },
+        |err| eprintln!("warning: audio stopped: {}", err),
+        None,
+    )
+}
+
+/// A `Voice` for rodio, taking new patterns from `patterns` as it goes.
+#[cfg(all(feature = "audio", not(feature = "cpal")))]
+struct VoiceSource {
+    voice: Voice,
+    patterns: Receiver<Option<AudioPattern>>,
+    sample_rate: SampleRate,
+}
+
+#[cfg(all(feature = "audio", not(feature = "cpal")))]
+impl Iterator for VoiceSource {
+    type Item = rodio::Sample;
+
+    fn next(&mut self) -> Option<rodio::Sample> {
+        for pattern in self.patterns.try_iter() {
+            self.voice.set_pattern(pattern);
+        }
+
+        Some(self.voice.next_sample())
+    }
+}
+
+#[cfg(all(feature = "audio", not(feature = "cpal")))]
+impl Source for VoiceSource {
+    fn current_span_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> ChannelCount {
+        ChannelCount::MIN
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,930 @@
+//! The egui frontend, for ROM authors: the game screen plus optional register, disassembly,
+//! memory and keypad panels, and controls to pause, single-step and reset. On touch screens a
+//! tappable keypad is drawn over the game.
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+use chip8_core::{decode, AudioPattern, AudioSink, Chip8, DisplaySink, KeyInput, Keypad, RunEnd};
+
+use chip8_frontend::{
+    scan_library, Controls, FramePacer, Hotkey, HotkeyAction, Hotkeys, Keymap, RomEntry,
+    RomWatcher, ROM_EXTENSIONS,
+};
+use eframe::egui::emath::GuiRounding;
+use eframe::egui::{
+    self, CentralPanel, Color32, ColorImage, Panel, Pos2, Rect, TextureHandle, TextureOptions,
+    TouchDeviceId, TouchId, TouchPhase,
+};
+
+use crate::beep::Beeper;
+use crate::gamepad::Gamepads;
+use crate::options::Options;
+use crate::recording::Recorder;
+use crate::render::Screen;
+use crate::rom;
+use crate::screenshot::Screenshots;
+use crate::video::VideoExport;
+use crate::volume::Volume;
+
+/// How many instructions the disassembly panel shows before and after the program counter.
+const DISASSEMBLY_CONTEXT: usize = 12;
+/// The hex keypad as it's laid out on the COSMAC VIP.
+const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+/// How many frames a key stays outlined in the keypad panel after the game last tested it.
+const POLL_HIGHLIGHT_FRAMES: u64 = 60;
+/// The touch keypad's largest size, in points, so it doesn't cover the game on a big screen.
+const TOUCH_KEYPAD_MAX_SIZE: f32 = 320.0;
+
+/// The ROMs in `Options::library`, listed in a window to pick one from.
+struct Library {
+    dir: PathBuf,
+    entries: Vec<RomEntry>,
+    /// Why the directory couldn't be listed, if it couldn't.
+    error: Option<String>,
+    open: bool,
+}
+
+impl Library {
+    fn new(dir: PathBuf, open: bool) -> Self {
+        let mut library = Library {
+            dir,
+            entries: Vec::new(),
+            error: None,
+            open: false,
+        };
+        if open {
+            library.open();
+        }
+
+        library
+    }
+
+    /// List the directory again, so that ROMs added since show up, and show the window.
+    fn open(&mut self) {
+        match scan_library(&self.dir) {
+            Ok(entries) => {
+                self.entries = entries;
+                self.error = None;
+            }
+            Err(err) => {
+                self.entries.clear();
+                self.error = Some(format!("{}: {}", self.dir.display(), err));
+            }
+        }
+        self.open = true;
+    }
+
+    fn toggle(&mut self) {
+        if self.open {
+            self.open = false;
+        } else {
+            self.open();
+        }
+    }
+
+    /// Show the window, if it's open, returning the ROM picked from it. Picking one closes it.
+    fn ui(&mut self, ctx: &egui::Context) -> Option<PathBuf> {
+        let mut open = self.open;
+        let mut picked = None;
+        let mut rescan = false;
+        egui::Window::new("Library")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(self.dir.display().to_string());
+                    rescan = ui.button("Rescan").clicked();
+                });
+                if let Some(error) = &self.error {
+                    ui.colored_label(Color32::LIGHT_RED, error);
+                } else if self.entries.is_empty() {
+                    ui.label(format!("No .{} files", ROM_EXTENSIONS.join(", .")));
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("library").striped(true).show(ui, |ui| {
+                        for entry in &self.entries {
+                            if ui.button(&entry.title).clicked() {
+                                picked = Some(entry.path.clone());
+                            }
+                            ui.monospace(format!("{:>5} bytes", entry.size));
+                            ui.label(entry.variant.name());
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        self.open = open && picked.is_none();
+        if rescan {
+            self.open();
+        }
+
+        picked
+    }
+}
+
+struct Io<'a> {
+    keys: [bool; 16],
+    beeper: &'a Beeper,
+}
+
+// `App` takes the display from the `Screen` after every frame instead, so that phosphor fading
+// keeps going between draws.
+impl DisplaySink for Io<'_> {}
+
+impl KeyInput for Io<'_> {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys[key as usize]
+    }
+}
+
+impl AudioSink for Io<'_> {
+    fn start_beep(&mut self) {
+        self.beeper.start();
+    }
+
+    fn stop_beep(&mut self) {
+        self.beeper.stop();
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.beeper.set_pattern(pattern);
+    }
+}
+
+struct App {
+    cpu: Chip8,
+    screen: Screen,
+    screenshots: Screenshots,
+    recorder: Recorder,
+    video: Option<VideoExport>,
+    texture: TextureHandle,
+    crt: bool,
+    keymap: Keymap,
+    /// The keypad keys held on the keyboard, going by `keymap`.
+    held: [bool; 16],
+    /// The keys held with the mouse in the keypad panel.
+    clicked: [bool; 16],
+    /// The frame each key was last tested by `EX9E` or `EXA1`, if it has been.
+    polled_at: [Option<u64>; 16],
+    gamepads: Gamepads,
+    beeper: Beeper,
+    volume: Volume,
+    hotkeys: Hotkeys,
+    controls: Controls,
+    /// Whether a ROM file dropped on the window is started.
+    drop_roms: bool,
+    watcher: Option<RomWatcher>,
+    library: Option<Library>,
+    /// The scale the texture was drawn at: 1, or the screen's scale with the CRT filter on so
+    /// that its scanlines are whole window pixels.
+    texture_scale: usize,
+    /// Scratch space for the image scaled up by the CRT filter.
+    scaled: Vec<u8>,
+    pacer: FramePacer,
+    error: Option<String>,
+    show_registers: bool,
+    show_disassembly: bool,
+    show_memory: bool,
+    show_keypad: bool,
+    show_touch_keypad: bool,
+    touch_keypad: TouchKeypad,
+    /// Where `run` finds out where the machine stopped, since eframe drops the app once the
+    /// window is closed.
+    end: Rc<Cell<Option<RunEnd>>>,
+}
+
+impl App {
+    /// The keypad keys held on the keyboard. egui only keeps track of which logical keys are
+    /// down, so this follows the key events itself to be able to go by position.
+    fn keys(&mut self, ctx: &egui::Context) -> [bool; 16] {
+        ctx.input(|input| {
+            if !input.focused {
+                self.held = [false; 16];
+                return;
+            }
+
+            for event in &input.events {
+                if let egui::Event::Key {
+                    key,
+                    physical_key,
+                    pressed,
+                    ..
+                } = *event
+                {
+                    if let Some(hex) = key_to_hex(&self.keymap, key, physical_key) {
+                        self.held[hex as usize] = pressed;
+                    }
+                }
+            }
+        });
+
+        self.held
+    }
+
+    /// Run one frame, or a single instruction when `step` is set, updating the screen texture if
+    /// the display changed.
+    fn advance(&mut self, keys: [bool; 16], step: bool) {
+        let mut io = Io {
+            keys,
+            beeper: &self.beeper,
+        };
+
+        let result = if step {
+            self.cpu.step().map(|_| ())
+        } else {
+            self.cpu.run_frame_with(&mut io).map(|_| ())
+        };
+
+        let polled = self.cpu.polled_keys();
+        for (key, at) in self.polled_at.iter_mut().enumerate() {
+            if polled & 1 << key != 0 {
+                *at = Some(self.cpu.frame());
+            }
+        }
+
+        if let Err(err) = result {
+            self.error = Some(err.to_string());
+            self.controls.set_paused(true);
+            self.beeper.stop();
+        }
+
+        self.refresh();
+        self.recorder.capture(&self.screen);
+        if let Some(video) = &mut self.video {
+            video.capture(&self.screen, self.cpu.timers().sound > 0);
+        }
+    }
+
+    /// Update the screen texture if the display looks any different, or starts or stops
+    /// flashing for the buzzer.
+    fn refresh(&mut self) {
+        let changed = self.screen.update(self.cpu.display());
+        if self.screen.set_inverted(self.beeper.flashing()) || changed {
+            self.upload();
+        }
+    }
+
+    /// The hotkeys pressed this frame, in order.
+    fn hotkeys(&self, ctx: &egui::Context) -> Vec<HotkeyAction> {
+        ctx.input(|input| {
+            input
+                .events
+                .iter()
+                .filter_map(|event| match *event {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        modifiers,
+                        ..
+                    } => key_to_hotkey(key, modifiers),
+                    _ => None,
+                })
+                .filter_map(|hotkey| self.hotkeys.action(hotkey))
+                .collect()
+        })
+    }
+
+    /// Carry out a hotkey's `action`, or the same thing from its button or menu item.
+    fn hotkey(&mut self, ctx: &egui::Context, action: HotkeyAction) {
+        match action {
+            HotkeyAction::Quit => ctx.send_viewport_cmd(egui::ViewportCommand::Close),
+            HotkeyAction::Crt => {
+                self.crt = !self.crt;
+                self.upload();
+            }
+            HotkeyAction::RecordGif => self.recorder.toggle(&self.screen),
+            HotkeyAction::Fullscreen => set_fullscreen(ctx, !is_fullscreen(ctx)),
+            HotkeyAction::Screenshot => self.screenshots.take(&self.screen),
+            HotkeyAction::Mute | HotkeyAction::VolumeDown | HotkeyAction::VolumeUp => {
+                self.volume.apply(action)
+            }
+            HotkeyAction::Library => {
+                if let Some(library) = &mut self.library {
+                    library.toggle();
+                }
+            }
+            // Resuming would only run straight back into the error.
+            HotkeyAction::Pause if self.error.is_some() => {}
+            action => {
+                self.controls.apply(action, &mut self.cpu);
+                self.beeper
+                    .follow(!self.controls.is_paused() && self.cpu.timers().sound > 0);
+                // Going back to the start or a saved state gets past whatever stopped execution.
+                if matches!(action, HotkeyAction::Reset | HotkeyAction::LoadState) {
+                    self.error = None;
+                }
+                self.refresh();
+            }
+        }
+    }
+
+    /// Start the ROM file dropped on the window this frame, the last of them if there were
+    /// several.
+    fn load_dropped(&mut self, ctx: &egui::Context) {
+        let Some(path) = ctx.input(|input| {
+            let file = input.raw.dropped_files.last()?;
+            Some(file.path().to_path_buf())
+        }) else {
+            return;
+        };
+
+        if rom::load(&path, &mut self.cpu, &mut self.controls) {
+            self.restarted();
+        }
+    }
+
+    /// Show the library, if it's open, and start the ROM picked from it.
+    fn library_ui(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.library.as_mut().and_then(|library| library.ui(ctx)) else {
+            return;
+        };
+
+        if rom::load(&path, &mut self.cpu, &mut self.controls) {
+            self.restarted();
+        }
+    }
+
+    /// Catch up with a new ROM, or the same one rewritten, having started.
+    fn restarted(&mut self) {
+        self.error = None;
+        self.polled_at = [None; 16];
+        self.beeper
+            .follow(!self.controls.is_paused() && self.cpu.timers().sound > 0);
+        self.refresh();
+    }
+
+    /// `text`, followed by the hotkey for `action` if it has one, for a button or menu item.
+    fn label(&self, text: &str, action: HotkeyAction) -> String {
+        match self.hotkeys.key(action) {
+            Some(hotkey) => format!("{} ({})", text, hotkey),
+            None => text.to_string(),
+        }
+    }
+
+    fn upload(&mut self) {
+        let scale = self.texture_scale;
+        let size = [self.screen.width() * scale, self.screen.height() * scale];
+
+        let image = if self.crt {
+            self.screen.scaled(scale, true, &mut self.scaled);
+            ColorImage::from_rgba_unmultiplied(size, &self.scaled)
+        } else {
+            ColorImage::from_rgba_unmultiplied(size, self.screen.rgba())
+        };
+        self.texture.set(image, TextureOptions::NEAREST);
+    }
+
+    fn menu_ui(&mut self, ui: &mut egui::Ui, keys: [bool; 16]) {
+        egui::MenuBar::new().ui(ui, |ui| {
+            ui.menu_button("View", |ui| {
+                ui.checkbox(&mut self.show_registers, "Registers");
+                ui.checkbox(&mut self.show_disassembly, "Disassembly");
+                ui.checkbox(&mut self.show_memory, "Memory");
+                ui.checkbox(&mut self.show_keypad, "Keypad");
+                ui.checkbox(&mut self.show_touch_keypad, "Touch keypad");
+                ui.separator();
+                let label = self.label("CRT filter", HotkeyAction::Crt);
+                if ui.checkbox(&mut self.crt, label).changed() {
+                    self.upload();
+                }
+                let mut fullscreen = is_fullscreen(ui.ctx());
+                let label = self.label("Fullscreen", HotkeyAction::Fullscreen);
+                if ui.checkbox(&mut fullscreen, label).changed() {
+                    set_fullscreen(ui.ctx(), fullscreen);
+                }
+                let mut turbo = self.controls.is_turbo();
+                let label = self.label("Turbo", HotkeyAction::Turbo);
+                if ui.checkbox(&mut turbo, label).changed() {
+                    self.hotkey(ui.ctx(), HotkeyAction::Turbo);
+                }
+            });
+
+            ui.separator();
+
+            if self.library.is_some()
+                && ui
+                    .button(self.label("Library", HotkeyAction::Library))
+                    .clicked()
+            {
+                self.hotkey(ui.ctx(), HotkeyAction::Library);
+            }
+            let pause = if self.controls.is_paused() {
+                "Resume"
+            } else {
+                "Pause"
+            };
+            if ui.button(self.label(pause, HotkeyAction::Pause)).clicked() {
+                self.hotkey(ui.ctx(), HotkeyAction::Pause);
+            }
+            if ui
+                .add_enabled(
+                    self.controls.is_paused() && self.error.is_none(),
+                    egui::Button::new("Step"),
+                )
+                .clicked()
+            {
+                self.advance(keys, true);
+            }
+            if ui
+                .button(self.label("Reset", HotkeyAction::Reset))
+                .clicked()
+            {
+                self.hotkey(ui.ctx(), HotkeyAction::Reset);
+            }
+            let slot = self.controls.slots().current();
+            let label = self.label(&format!("Save slot {}", slot), HotkeyAction::SaveState);
+            if ui.button(label).clicked() {
+                self.hotkey(ui.ctx(), HotkeyAction::SaveState);
+            }
+            let label = self.label(&format!("Load slot {}", slot), HotkeyAction::LoadState);
+            let saved = self.controls.slots().get().is_some();
+            if ui.add_enabled(saved, egui::Button::new(label)).clicked() {
+                self.hotkey(ui.ctx(), HotkeyAction::LoadState);
+            }
+            if ui
+                .button(self.label("Screenshot", HotkeyAction::Screenshot))
+                .clicked()
+            {
+                self.screenshots.take(&self.screen);
+            }
+            let record = if self.recorder.is_recording() {
+                "Stop recording"
+            } else {
+                "Record GIF"
+            };
+            if ui
+                .button(self.label(record, HotkeyAction::RecordGif))
+                .clicked()
+            {
+                self.recorder.toggle(&self.screen);
+            }
+            let mute = if self.volume.is_muted() {
+                "Unmute"
+            } else {
+                "Mute"
+            };
+            if ui.button(self.label(mute, HotkeyAction::Mute)).clicked() {
+                self.hotkey(ui.ctx(), HotkeyAction::Mute);
+            }
+            ui.label(format!("Volume {}%", self.volume.percent()));
+
+            if let Some(error) = &self.error {
+                ui.colored_label(Color32::LIGHT_RED, error);
+            }
+        });
+    }
+
+    fn registers_ui(&self, ui: &mut egui::Ui) {
+        ui.heading("Registers");
+
+        egui::Grid::new("registers").striped(true).show(ui, |ui| {
+            for (i, value) in self.cpu.registers().iter().enumerate() {
+                ui.monospace(format!("V{:X}", i));
+                ui.monospace(format!("{:02X}", value));
+                if i % 2 == 1 {
+                    ui.end_row();
+                }
+            }
+
+            ui.monospace("I");
+            ui.monospace(format!("{:03X}", self.cpu.i()));
+            ui.monospace("PC");
+            ui.monospace(format!("{:03X}", self.cpu.pc()));
+            ui.end_row();
+
+            ui.monospace("DT");
+            ui.monospace(format!("{:02X}", self.cpu.timers().delay));
+            ui.monospace("ST");
+            ui.monospace(format!("{:02X}", self.cpu.timers().sound));
+            ui.end_row();
+        });
+
+        ui.separator();
+        ui.label("Stack");
+        for frame in self.cpu.stack_frames() {
+            ui.monospace(format!("{:03X}", frame));
+        }
+    }
+
+    fn disassembly_ui(&self, ui: &mut egui::Ui) {
+        ui.heading("Disassembly");
+
+        let memory = self.cpu.memory().as_slice();
+        let pc = self.cpu.pc();
+        // Stay aligned with the program counter, which is what's actually executing.
+        let start = pc.saturating_sub(DISASSEMBLY_CONTEXT * 2);
+
+        for addr in (start..pc + DISASSEMBLY_CONTEXT * 2).step_by(2) {
+            let (Some(&hi), Some(&lo)) = (memory.get(addr), memory.get(addr + 1)) else {
+                break;
+            };
+            let opcode = u16::from_be_bytes([hi, lo]);
+            let line = format!("{:03X}  {:04X}  {}", addr, opcode, decode(opcode));
+
+            if addr == pc {
+                ui.colored_label(Color32::YELLOW, egui::RichText::new(line).monospace());
+            } else {
+                ui.monospace(line);
+            }
+        }
+    }
+
+    fn memory_ui(&self, ui: &mut egui::Ui) {
+        ui.heading("Memory");
+
+        let memory = self.cpu.memory().as_slice();
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+
+        egui::ScrollArea::vertical().show_rows(ui, row_height, memory.len() / 16, |ui, rows| {
+            for row in rows {
+                let bytes = &memory[row * 16..row * 16 + 16];
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+
+                ui.monospace(format!("{:03X}  {}", row * 16, hex.join(" ")));
+            }
+        });
+    }
+
+    /// The keypad, with the keys held down selected and the ones the game has tested recently
+    /// outlined. Keys can be held with the mouse too.
+    fn keypad_ui(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Keypad");
+
+        let frame = self.cpu.frame();
+        let outline = egui::Stroke::new(2.0, ui.visuals().selection.stroke.color);
+        egui::Grid::new("keypad").show(ui, |ui| {
+            for row in KEYPAD_LAYOUT {
+                for key in row {
+                    let polled = self.polled_at[key as usize]
+                        .and_then(|at| frame.checked_sub(at))
+                        .is_some_and(|age| age < POLL_HIGHLIGHT_FRAMES);
+                    let text = egui::RichText::new(format!("{:X}", key)).monospace();
+                    let mut button = egui::Button::new(text)
+                        .selected(self.cpu.keypad().is_down(key))
+                        .sense(egui::Sense::click_and_drag());
+                    if polled {
+                        button = button.stroke(outline);
+                    }
+
+                    let keyboard = self.keymap.key_char(key).to_uppercase();
+                    let response = ui
+                        .add(button)
+                        .on_hover_text(format!("{} on the keyboard", keyboard));
+                    self.clicked[key as usize] = response.is_pointer_button_down_on();
+                }
+                ui.end_row();
+            }
+        });
+
+        if self.cpu.waiting_for_key().is_some() {
+            ui.label("Waiting for any key");
+        } else if self.polled_at.iter().all(Option::is_none) {
+            ui.label("No keys read yet");
+        }
+    }
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        self.end.set(Some(RunEnd::of(&self.cpu)));
+    }
+}
+
+impl eframe::App for App {
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        let ctx = ui.ctx().clone();
+        let mut keys = self.keys(&ctx);
+        self.gamepads.poll();
+        if ctx.input(|input| input.any_touches()) {
+            self.show_touch_keypad = true;
+        }
+        let touched = self.touch_keypad.keys(&ctx);
+        for (key, down) in keys.iter_mut().enumerate() {
+            *down |= self.gamepads.is_down(key as u8) || touched[key] || self.clicked[key];
+        }
+
+        if self.pacer.poll() {
+            for _ in 0..self.controls.frames() {
+                // An error pauses partway through a turbo frame.
+                if self.controls.is_paused() {
+                    break;
+                }
+                self.advance(keys, false);
+            }
+        }
+        for action in self.hotkeys(&ctx) {
+            self.hotkey(&ctx, action);
+        }
+        if self.drop_roms {
+            self.load_dropped(&ctx);
+        }
+        if rom::reload(self.watcher.as_mut(), &mut self.cpu, &mut self.controls) {
+            self.restarted();
+        }
+        self.library_ui(&ctx);
+
+        Panel::top("menu").show(ui, |ui| self.menu_ui(ui, keys));
+
+        if self.show_registers || self.show_keypad {
+            Panel::right("state").show(ui, |ui| {
+                if self.show_registers {
+                    self.registers_ui(ui);
+                }
+                if self.show_keypad {
+                    ui.separator();
+                    self.keypad_ui(ui);
+                }
+            });
+        }
+        if !self.show_keypad {
+            self.clicked = [false; 16];
+        }
+        if self.show_disassembly {
+            Panel::left("disassembly").show(ui, |ui| self.disassembly_ui(ui));
+        }
+        if self.show_memory {
+            Panel::bottom("memory")
+                .resizable(true)
+                .show(ui, |ui| self.memory_ui(ui));
+        }
+
+        CentralPanel::default().show(ui, |ui| {
+            // The largest whole multiple of the display that fits, so pixels stay square. It's
+            // worked out in physical pixels rather than points, so that it stays a whole number
+            // of them whatever the scale factor and nearest-neighbour sampling stays sharp.
+            let ppp = ui.ctx().pixels_per_point();
+            let available = ui.available_rect_before_wrap();
+            let display = egui::vec2(self.screen.width() as f32, self.screen.height() as f32);
+            let physical = available.size() * ppp;
+            let scale = (physical.x / display.x)
+                .min(physical.y / display.y)
+                .floor()
+                .max(1.0);
+            let size = display * scale / ppp;
+
+            let texture_scale = if self.crt { scale as usize } else { 1 };
+            if texture_scale != self.texture_scale {
+                self.texture_scale = texture_scale;
+                self.upload();
+            }
+
+            // Centred, and on a pixel boundary for the same reason.
+            let min = (available.center() - size / 2.0).round_to_pixels(ppp);
+            let rect = egui::Rect::from_min_size(min, size);
+            ui.put(rect, egui::Image::new((self.texture.id(), size)));
+
+            if self.show_touch_keypad {
+                self.touch_keypad.ui(ui.ctx(), available, self.cpu.keypad());
+            } else {
+                self.touch_keypad.hide();
+            }
+        });
+
+        let wait = self
+            .pacer
+            .deadline()
+            .saturating_duration_since(std::time::Instant::now());
+        ctx.request_repaint_after(wait);
+    }
+}
+
+/// The tappable keypad drawn over the game for touch screens, in the COSMAC VIP's layout.
+///
+/// Every finger on the screen holds down the key under it, so several keys can be held at once,
+/// and sliding a finger from one key to another moves the press with it. The mouse works too,
+/// one key at a time.
+struct TouchKeypad {
+    /// Where each finger on the screen is.
+    touches: BTreeMap<(TouchDeviceId, TouchId), Pos2>,
+    /// Each key's button as it was last drawn, or `Rect::NOTHING` while hidden.
+    buttons: [Rect; 16],
+}
+
+impl TouchKeypad {
+    fn new() -> Self {
+        TouchKeypad {
+            touches: BTreeMap::new(),
+            buttons: [Rect::NOTHING; 16],
+        }
+    }
+
+    /// Follow the touches since the last frame, and return which keys they're holding down.
+    fn keys(&mut self, ctx: &egui::Context) -> [bool; 16] {
+        let mouse = ctx.input(|input| {
+            for event in &input.events {
+                if let egui::Event::Touch {
+                    device_id,
+                    id,
+                    phase,
+                    pos,
+                    ..
+                } = *event
+                {
+                    match phase {
+                        TouchPhase::Start | TouchPhase::Move => {
+                            self.touches.insert((device_id, id), pos);
+                        }
+                        TouchPhase::End | TouchPhase::Cancel => {
+                            self.touches.remove(&(device_id, id));
+                        }
+                    }
+                }
+            }
+
+            let pointer = &input.pointer;
+            pointer.interact_pos().filter(|_| pointer.primary_down())
+        });
+
+        let mut keys = [false; 16];
+        for pos in self.touches.values().copied().chain(mouse) {
+            if let Some(key) = self.buttons.iter().position(|button| button.contains(pos)) {
+                keys[key] = true;
+            }
+        }
+
+        keys
+    }
+
+    /// Draw the keypad at the bottom of `area`, lighting up the keys held on `keypad`.
+    fn ui(&mut self, ctx: &egui::Context, area: Rect, keypad: &Keypad) {
+        let size = (area.width().min(area.height()) * 0.6).min(TOUCH_KEYPAD_MAX_SIZE);
+        let cell = size / 4.0;
+        let rect = Rect::from_min_size(
+            egui::pos2(
+                area.center().x - size / 2.0,
+                area.bottom() - size - cell / 4.0,
+            ),
+            egui::vec2(size, size),
+        );
+        let painter = ctx.layer_painter(egui::LayerId::new(
+            egui::Order::Foreground,
+            egui::Id::new("touch keypad"),
+        ));
+
+        for (row, keys) in KEYPAD_LAYOUT.iter().enumerate() {
+            for (column, &key) in keys.iter().enumerate() {
+                let min = rect.min + egui::vec2(column as f32, row as f32) * cell;
+                let button = Rect::from_min_size(min, egui::vec2(cell, cell)).shrink(cell / 16.0);
+                self.buttons[key as usize] = button;
+
+                let alpha = if keypad.is_down(key) { 160 } else { 48 };
+                painter.rect_filled(button, cell / 6.0, Color32::from_white_alpha(alpha));
+                painter.text(
+                    button.center(),
+                    egui::Align2::CENTER_CENTER,
+                    format!("{:X}", key),
+                    egui::FontId::monospace(cell * 0.4),
+                    Color32::WHITE,
+                );
+            }
+        }
+    }
+
+    /// Stop the keys responding to touches while the keypad isn't shown.
+    fn hide(&mut self) {
+        self.buttons = [Rect::NOTHING; 16];
+    }
+}
+
+/// The hex key for an egui key event: by where the key is, if the keymap goes by position, and
+/// otherwise by the character it types.
+fn key_to_hex(keymap: &Keymap, key: egui::Key, physical_key: Option<egui::Key>) -> Option<u8> {
+    let position = physical_key.and_then(key_position);
+    if let Some(hex) = position.and_then(|(row, column)| keymap.position_key(row, column)) {
+        return Some(hex);
+    }
+
+    let mut chars = key.symbol_or_name().chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => keymap.hex_key(c),
+        _ => None,
+    }
+}
+
+/// Where a physical key is in the block at the left of the keyboard, as
+/// `Keymap::position_key` takes it.
+fn key_position(key: egui::Key) -> Option<(u8, u8)> {
+    use egui::Key;
+
+    let rows = [
+        [Key::Num1, Key::Num2, Key::Num3, Key::Num4],
+        [Key::Q, Key::W, Key::E, Key::R],
+        [Key::A, Key::S, Key::D, Key::F],
+        [Key::Z, Key::X, Key::C, Key::V],
+    ];
+
+    rows.iter().enumerate().find_map(|(row, keys)| {
+        let column = keys.iter().position(|&k| k == key)?;
+        Some((row as u8, column as u8))
+    })
+}
+
+/// The hotkey for an egui key with `modifiers` held. egui's names for keys are the ones
+/// `Hotkey` parses, and its symbols are the characters they type.
+fn key_to_hotkey(key: egui::Key, modifiers: egui::Modifiers) -> Option<Hotkey> {
+    let hotkey: Hotkey = key.symbol_or_name().parse().ok()?;
+
+    Some(hotkey.with_ctrl(modifiers.ctrl).with_shift(modifiers.shift))
+}
+
+fn is_fullscreen(ctx: &egui::Context) -> bool {
+    ctx.input(|input| input.viewport().fullscreen.unwrap_or(false))
+}
+
+/// Go fullscreen or back to a window. The panels stay, and the screen is letterboxed in what's
+/// left over.
+fn set_fullscreen(ctx: &egui::Context, fullscreen: bool) {
+    ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(fullscreen));
+}
+
+pub fn run(cpu: Chip8, options: &Options) -> Result<RunEnd, Box<dyn Error>> {
+    // Room for the screen at the requested scale, plus the panels around it.
+    let (width, height) = options.window_size();
+    let mut screen = options.screen();
+    screen.update(cpu.display());
+    let crt = options.crt;
+    let keymap = options.keymap;
+    let hotkeys = options.hotkeys;
+    let gamepads = options.gamepads();
+    let screenshots = options.screenshots();
+    let recorder = options.recorder();
+    let video = options.video()?;
+    let end = Rc::new(Cell::new(None));
+    let native = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([width as f32 + 400.0, height as f32 + 300.0])
+            .with_fullscreen(options.fullscreen),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "CHIP-8",
+        native,
+        Box::new(|cc| {
+            let size = [screen.width(), screen.height()];
+            let image = ColorImage::from_rgba_unmultiplied(size, screen.rgba());
+            let texture = cc
+                .egui_ctx
+                .load_texture("screen", image, TextureOptions::NEAREST);
+
+            let volume = options.volume();
+            let mut app = App {
+                cpu,
+                screen,
+                screenshots,
+                recorder,
+                video,
+                texture,
+                crt,
+                keymap,
+                held: [false; 16],
+                clicked: [false; 16],
+                polled_at: [None; 16],
+                gamepads,
+                beeper: options.beeper(&volume),
+                volume,
+                hotkeys,
+                controls: options.controls(),
+                drop_roms: options.drop_roms,
+                watcher: options.watcher(),
+                library: options
+                    .library
+                    .clone()
+                    .map(|dir| Library::new(dir, options.show_library)),
+                texture_scale: 1,
+                scaled: Vec::new(),
+                pacer: FramePacer::new(),
+                error: None,
+                show_registers: true,
+                show_disassembly: true,
+                show_memory: false,
+                show_keypad: true,
+                show_touch_keypad: options.touch_keypad,
+                touch_keypad: TouchKeypad::new(),
+                end: Rc::clone(&end),
+            };
+            app.upload();
+            // There's nothing to run until a game is picked.
+            if options.show_library {
+                app.controls.set_paused(true);
+            }
+
+            Ok(Box::new(app))
+        }),
+    )?;
+
+    Ok(end
+        .get()
+        .ok_or("the window closed before the game started")?)
+}
@@ -0,0 +1,181 @@
+//! Game controllers, read through gilrs when the `gamepad` feature is on.
+//!
+//! Which keypad key each button presses comes from a `PadMap`. It starts out as `PadMap::default`
+//! and can be changed with `--pad BUTTON=KEY` or, for one game, a `.pad` file next to its ROM.
+
+use std::fmt;
+
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, Button, Gilrs};
+
+/// How far a stick has to be pushed before it counts as the d-pad direction.
+#[cfg(feature = "gamepad")]
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// The buttons a `PadMap` can bind, named the way gilrs does: `south`, `east`, `west` and `north`
+/// are the four face buttons wherever a particular controller's labels put them.
+pub const BUTTONS: [&str; 14] = [
+    "up", "down", "left", "right", "south", "east", "west", "north", "l1", "r1", "l2", "r2",
+    "select", "start",
+];
+
+/// Which hex key, if any, each controller button presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadMap {
+    /// Indexed like `BUTTONS`.
+    keys: [Option<u8>; BUTTONS.len()],
+}
+
+impl PadMap {
+    /// A map with no buttons bound.
+    pub const EMPTY: PadMap = PadMap {
+        keys: [None; BUTTONS.len()],
+    };
+
+    /// The key `button`, one of `BUTTONS`, presses.
+    pub fn key(&self, button: &str) -> Option<u8> {
+        self.keys[button_index(button)?]
+    }
+
+    /// Parse one binding, `BUTTON=KEY`, where `KEY` is a hex digit or `none`, and apply it.
+    /// `a`, `b`, `x` and `y` are accepted for the face buttons in their Xbox positions.
+    pub fn bind(&mut self, binding: &str) -> Result<(), PadMapError> {
+        let err = || PadMapError(binding.to_string());
+        let (button, key) = binding.split_once('=').ok_or_else(err)?;
+
+        let index = button_index(button.trim()).ok_or_else(err)?;
+        self.keys[index] = match key.trim() {
+            "none" => None,
+            key if key.len() == 1 => Some(u8::from_str_radix(key, 16).map_err(|_| err())?),
+            _ => return Err(err()),
+        };
+
+        Ok(())
+    }
+
+    /// Apply the bindings in a `.pad` file: one or more per line, separated by whitespace, with
+    /// `#` starting a comment. Buttons the file doesn't mention keep their keys.
+    pub fn apply(&mut self, text: &str) -> Result<(), PadMapError> {
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            for binding in line.split_whitespace() {
+                self.bind(binding)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The d-pad and stick on `2`, `4`, `6` and `8`, the directions in most CHIP-8 games, and the
+/// bottom face button on `5`, the usual fire button. The other face buttons and start are on the
+/// keypad's bottom row, `0`, `A`, `B` and `F`.
+impl Default for PadMap {
+    fn default() -> Self {
+        let mut map = PadMap::EMPTY;
+        map.apply("up=2 down=8 left=4 right=6 south=5 east=0 west=a north=b start=f")
+            .expect("the default pad map is valid");
+        map
+    }
+}
+
+/// The index in `BUTTONS` of `name`, or of the button an Xbox-style letter stands for.
+fn button_index(name: &str) -> Option<usize> {
+    let name = name.to_ascii_lowercase();
+    let name = match name.as_str() {
+        "a" => "south",
+        "b" => "east",
+        "x" => "west",
+        "y" => "north",
+        name => name,
+    };
+
+    BUTTONS.iter().position(|&button| button == name)
+}
+
+/// A binding that isn't `BUTTON=KEY` with a known button and a hex digit or `none`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PadMapError(String);
+
+impl fmt::Display for PadMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} isn't a binding like south=5; buttons are {}",
+            self.0,
+            BUTTONS.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for PadMapError {}
+
+/// Every connected controller, as one set of held keypad keys.
+///
+/// Without the `gamepad` feature, or if the platform's controller API can't be opened, no keys
+/// are ever held.
+pub struct Gamepads {
+    map: PadMap,
+    /// Which hex keys are held, one bit per key.
+    held: u16,
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<Gilrs>,
+}
+
+impl Gamepads {
+    pub fn new(map: PadMap) -> Self {
+        Gamepads {
+            map,
+            held: 0,
+            #[cfg(feature = "gamepad")]
+            gilrs: Gilrs::new()
+                .map_err(|err| eprintln!("warning: gamepads are unavailable: {}", err))
+                .ok(),
+        }
+    }
+
+    pub fn map(&self) -> &PadMap {
+        &self.map
+    }
+
+    /// Catch up with the controllers. Call once per frame, before running it.
+    pub fn poll(&mut self) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gilrs) = &mut self.gilrs {
+            while gilrs.next_event().is_some() {}
+
+            self.held = 0;
+            for (_, pad) in gilrs.gamepads() {
+                let x = pad.value(Axis::LeftStickX);
+                let y = pad.value(Axis::LeftStickY);
+                let buttons = [
+                    pad.is_pressed(Button::DPadUp) || y > STICK_THRESHOLD,
+                    pad.is_pressed(Button::DPadDown) || y < -STICK_THRESHOLD,
+                    pad.is_pressed(Button::DPadLeft) || x < -STICK_THRESHOLD,
+                    pad.is_pressed(Button::DPadRight) || x > STICK_THRESHOLD,
+                    pad.is_pressed(Button::South),
+                    pad.is_pressed(Button::East),
+                    pad.is_pressed(Button::West),
+                    pad.is_pressed(Button::North),
+                    pad.is_pressed(Button::LeftTrigger),
+                    pad.is_pressed(Button::RightTrigger),
+                    pad.is_pressed(Button::LeftTrigger2),
+                    pad.is_pressed(Button::RightTrigger2),
+                    pad.is_pressed(Button::Select),
+                    pad.is_pressed(Button::Start),
+                ];
+
+                for (pressed, key) in buttons.into_iter().zip(self.map.keys) {
+                    if let (true, Some(key)) = (pressed, key) {
+                        self.held |= 1 << key;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether hex `key` is held on any controller, as of the last `poll`.
+    pub fn is_down(&self, key: u8) -> bool {
+        self.held & (1 << (key & 0xF)) != 0
+    }
+}
@@ -0,0 +1,661 @@
+//! A wgpu renderer that uploads the 64x32 framebuffer as a texture and draws it through a chain
+//! of WGSL shader passes, so that scaling and effects happen on the GPU.
+//!
+//! Each pass is a fragment shader that defines `fs_main`. It's compiled after a prelude that
+//! declares what it can use:
+//!
+//! ```wgsl
+//! struct VertexOutput {
+//!     @builtin(position) position: vec4<f32>,
+//!     @location(0) uv: vec2<f32>,
+//! }
+//!
+//! struct Params {
+//!     source_size: vec2<f32>,  // the size of `source` in pixels
+//!     output_size: vec2<f32>,  // the size of the output in pixels
+//!     display_size: vec2<f32>, // the size of the CHIP-8 display in pixels
+//!     frame: u32,              // frames rendered so far
+//! }
+//!
+//! @group(0) @binding(0) var source: texture_2d<f32>;
+//! @group(0) @binding(1) var source_sampler: sampler;
+//! @group(0) @binding(2) var<uniform> params: Params;
+//! ```
+//!
+//! The first pass samples the display itself, and each later pass samples the output of the one
+//! before. The last pass's output is then copied to the window, or run through the built-in CRT
+//! filter if that's switched on. A pass that just copies its source looks like:
+//!
+//! ```wgsl
+//! @fragment
+//! fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+//!     return textureSample(source, source_sampler, in.uv);
+//! }
+//! ```
+
+use std::error::Error;
+use std::path::Path;
+use std::{fmt, fs, io};
+
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+use chip8_frontend::Palette;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CurrentSurfaceTexture, Device, Instance, Queue,
+    RenderPipeline, Sampler, Surface, SurfaceConfiguration, Texture, TextureFormat, TextureView,
+};
+
+use crate::render::{self, Viewport};
+
+/// Declarations every pass is compiled with.
+const PRELUDE: &str = r#"
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+struct Params {
+    source_size: vec2<f32>,
+    output_size: vec2<f32>,
+    display_size: vec2<f32>,
+    frame: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> params: Params;
+
+// A triangle that covers the whole target, with `uv` running from (0, 0) at the top left to
+// (1, 1) at the bottom right.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+"#;
+
+/// The last pass when the CRT filter is off: nearest-neighbour scaling and nothing else.
+const COPY: &str = r#"
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, in.uv);
+}
+"#;
+
+/// The last pass when the CRT filter is on: a curved, vignetted tube with scanlines, a shadow
+/// mask and a little bloom around lit pixels.
+const CRT: &str = r#"
+const CURVATURE: f32 = 0.06;
+const BLOOM: f32 = 0.4;
+
+// Bulge `uv` outwards from the centre, like the glass of a tube.
+fn curve(uv: vec2<f32>) -> vec2<f32> {
+    let centred = uv * 2.0 - 1.0;
+    let bent = centred * (1.0 + centred.yx * centred.yx * CURVATURE);
+    return bent * 0.5 + 0.5;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let uv = curve(in.uv);
+    if any(uv < vec2<f32>(0.0)) || any(uv > vec2<f32>(1.0)) {
+        return vec4<f32>(0.0, 0.0, 0.0, 1.0);
+    }
+
+    var color = textureSampleLevel(source, source_sampler, uv, 0.0).rgb;
+
+    // Light bleeding in from the neighbouring display pixels.
+    let texel = 1.0 / params.display_size;
+    var glow = vec3<f32>(0.0);
+    for (var dy = -1; dy <= 1; dy++) {
+        for (var dx = -1; dx <= 1; dx++) {
+            let offset = vec2<f32>(f32(dx), f32(dy)) * texel;
+            glow += textureSampleLevel(source, source_sampler, uv + offset, 0.0).rgb;
+        }
+    }
+    color += glow / 9.0 * BLOOM;
+
+    // Dark gaps between the display's rows, and fainter ones between its columns.
+    let cell = fract(uv * params.display_size);
+    color *= mix(0.55, 1.0, sin(cell.y * 3.14159));
+    color *= mix(0.8, 1.0, smoothstep(0.0, 0.2, cell.x) * smoothstep(1.0, 0.8, cell.x));
+
+    // Darker towards the corners.
+    let edge = uv * (1.0 - uv.yx);
+    color *= pow(edge.x * edge.y * 16.0, 0.2);
+
+    return vec4<f32>(color, 1.0);
+}
+"#;
+
+/// Bytes in the `Params` uniform.
+const PARAMS_LEN: u64 = 32;
+/// The format of the display texture and of the targets between passes.
+const TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// One post-processing pass: a name for error messages, and its WGSL source.
+#[derive(Debug, Clone)]
+pub struct ShaderPass {
+    pub label: String,
+    pub source: String,
+}
+
+/// The passes the display is drawn through, in order. An empty chain just scales the display up.
+#[derive(Debug, Clone, Default)]
+pub struct ShaderChain {
+    passes: Vec<ShaderPass>,
+}
+
+impl ShaderChain {
+    pub fn new() -> Self {
+        ShaderChain::default()
+    }
+
+    /// Add a pass to the end of the chain.
+    pub fn pass(mut self, label: impl Into<String>, source: impl Into<String>) -> Self {
+        self.passes.push(ShaderPass {
+            label: label.into(),
+            source: source.into(),
+        });
+        self
+    }
+
+    /// A chain of the shaders in `paths`, in order, labelled with their file names.
+    pub fn load<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let mut chain = ShaderChain::new();
+
+        for path in paths {
+            let path = path.as_ref();
+            chain = chain.pass(path.display().to_string(), fs::read_to_string(path)?);
+        }
+
+        Ok(chain)
+    }
+
+    pub fn passes(&self) -> &[ShaderPass] {
+        &self.passes
+    }
+}
+
+/// Why a shader in the chain couldn't be used.
+#[derive(Debug)]
+pub struct ShaderError {
+    pub label: String,
+    pub message: String,
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "shader {}: {}", self.label, self.message)
+    }
+}
+
+impl Error for ShaderError {}
+
+struct Pass {
+    pipeline: RenderPipeline,
+    params: Buffer,
+}
+
+/// Draws the display to a surface through a `ShaderChain`.
+///
+/// The chain's passes draw into textures the size of the viewport, and then `copy` or `crt`
+/// puts the result on the surface.
+pub struct GpuRenderer {
+    device: Device,
+    queue: Queue,
+    surface: Surface<'static>,
+    config: SurfaceConfiguration,
+    display: Texture,
+    display_view: TextureView,
+    sampler: Sampler,
+    layout: BindGroupLayout,
+    passes: Vec<Pass>,
+    copy: Pass,
+    crt: Pass,
+    crt_enabled: bool,
+    /// What each pass in the chain draws to.
+    targets: Vec<TextureView>,
+    /// What each pass reads from: one for each pass in the chain, then one each for `copy` and
+    /// `crt`. Rebuilt along with the targets.
+    bind_groups: Vec<BindGroup>,
+    viewport: Viewport,
+    frame: u32,
+    /// The colour around the display, as RGB.
+    border: [u8; 3],
+}
+
+impl GpuRenderer {
+    /// Set up rendering to `surface`, which is `width` by `height` pixels, and compile `chain`.
+    pub async fn new(
+        instance: &Instance,
+        surface: Surface<'static>,
+        width: u32,
+        height: u32,
+        chain: &ShaderChain,
+    ) -> Result<Self, Box<dyn Error>> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                compatible_surface: Some(&surface),
+                ..Default::default()
+            })
+            .await?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("chip8"),
+                ..Default::default()
+            })
+            .await?;
+
+        let config = surface
+            .get_default_config(&adapter, width.max(1), height.max(1))
+            .ok_or("the GPU can't draw to this window")?;
+        surface.configure(&device, &config);
+
+        let display = create_display(&device, DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32);
+        let display_view = display.create_view(&Default::default());
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("nearest"),
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("pass"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let mut passes = Vec::new();
+        for pass in &chain.passes {
+            passes.push(compile(&device, &layout, pass, TEXTURE_FORMAT).await?);
+        }
+        let builtin = |label: &str, source: &str| ShaderPass {
+            label: label.to_string(),
+            source: source.to_string(),
+        };
+        let copy = compile(&device, &layout, &builtin("copy", COPY), config.format).await?;
+        let crt = compile(&device, &layout, &builtin("crt", CRT), config.format).await?;
+
+        let mut renderer = GpuRenderer {
+            device,
+            queue,
+            surface,
+            config,
+            display,
+            display_view,
+            sampler,
+            layout,
+            passes,
+            copy,
+            crt,
+            crt_enabled: false,
+            targets: Vec::new(),
+            bind_groups: Vec::new(),
+            viewport: Viewport::fit(width, height, DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            frame: 0,
+            border: Palette::default().background,
+        };
+        renderer.rebuild_targets();
+
+        Ok(renderer)
+    }
+
+    /// Whether the last pass is the CRT filter.
+    pub fn crt(&self) -> bool {
+        self.crt_enabled
+    }
+
+    pub fn set_crt(&mut self, crt: bool) {
+        self.crt_enabled = crt;
+    }
+
+    /// Fill the space around the display with `color`, usually the palette's background.
+    pub fn set_border(&mut self, color: [u8; 3]) {
+        self.border = color;
+    }
+
+    /// Copy a `width` by `height` RGBA image of the display, like `Screen::rgba`, to the GPU to
+    /// be drawn by the next `render`. The size changes when the display switches resolution.
+    pub fn upload(&mut self, rgba: &[u8], width: usize, height: usize) {
+        let (width, height) = (width as u32, height as u32);
+        if (self.display.width(), self.display.height()) != (width, height) {
+            self.display = create_display(&self.device, width, height);
+            self.display_view = self.display.create_view(&Default::default());
+            self.viewport = self.fit(self.config.width, self.config.height);
+            self.rebuild_targets();
+        }
+
+        self.queue.write_texture(
+            self.display.as_image_copy(),
+            rgba,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: None,
+            },
+            self.display.size(),
+        );
+    }
+
+    /// Follow the window to a new size.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            // Minimised. Keep the old size until the window comes back.
+            return;
+        }
+
+        self.config.width = width;
+        self.config.height = height;
+        self.surface.configure(&self.device, &self.config);
+
+        self.viewport = self.fit(width, height);
+        self.rebuild_targets();
+    }
+
+    /// Where the display goes in a surface `width` by `height`.
+    fn fit(&self, width: u32, height: u32) -> Viewport {
+        let display = self.display.size();
+        Viewport::fit(
+            width,
+            height,
+            display.width as usize,
+            display.height as usize,
+        )
+    }
+
+    /// Draw the last uploaded frame through the chain and present it.
+    pub fn render(&mut self) -> Result<(), Box<dyn Error>> {
+        let frame = match self.surface.get_current_texture() {
+            CurrentSurfaceTexture::Success(frame) => frame,
+            CurrentSurfaceTexture::Suboptimal(frame) => {
+                // Still usable; reconfigure for the next one.
+                self.surface.configure(&self.device, &self.config);
+                frame
+            }
+            CurrentSurfaceTexture::Timeout | CurrentSurfaceTexture::Occluded => return Ok(()),
+            CurrentSurfaceTexture::Outdated => {
+                self.surface.configure(&self.device, &self.config);
+                return Ok(());
+            }
+            CurrentSurfaceTexture::Lost => return Err("the window's GPU surface was lost".into()),
+            CurrentSurfaceTexture::Validation => {
+                return Err("the window's GPU surface is invalid".into())
+            }
+        };
+        let output = frame.texture.create_view(&Default::default());
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+
+        let [r, g, b] = if self.config.format.is_srgb() {
+            render::srgb_to_linear(self.border)
+        } else {
+            self.border.map(|c| c as f64 / 255.0)
+        };
+        let border = wgpu::Color { r, g, b, a: 1.0 };
+
+        let (last, last_bind_group) = if self.crt_enabled {
+            (&self.crt, self.passes.len() + 1)
+        } else {
+            (&self.copy, self.passes.len())
+        };
+        let passes = self
+            .passes
+            .iter()
+            .enumerate()
+            .chain([(last_bind_group, last)]);
+
+        for (i, pass) in passes {
+            let is_last = i >= self.passes.len();
+            let reads_display = if is_last {
+                self.passes.is_empty()
+            } else {
+                i == 0
+            };
+            let display_size = [self.display.width() as f32, self.display.height() as f32];
+            let source_size = if reads_display {
+                display_size
+            } else {
+                [self.viewport.width as f32, self.viewport.height as f32]
+            };
+            self.queue.write_buffer(
+                &pass.params,
+                0,
+                &params(source_size, self.viewport, display_size, self.frame),
+            );
+
+            let target = if is_last { &output } else { &self.targets[i] };
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(border),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            // The targets between passes are exactly the viewport's size; only the surface
+            // has borders around it.
+            if is_last {
+                let Viewport {
+                    x,
+                    y,
+                    width,
+                    height,
+                    ..
+                } = self.viewport;
+                let width = width.min(self.config.width - x);
+                let height = height.min(self.config.height - y);
+                rpass.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            }
+
+            rpass.set_pipeline(&pass.pipeline);
+            rpass.set_bind_group(0, &self.bind_groups[i], &[]);
+            rpass.draw(0..3, 0..1);
+        }
+
+        self.queue.submit([encoder.finish()]);
+        self.queue.present(frame);
+        self.frame = self.frame.wrapping_add(1);
+
+        Ok(())
+    }
+
+    fn rebuild_targets(&mut self) {
+        self.targets = (0..self.passes.len())
+            .map(|_| {
+                self.device
+                    .create_texture(&wgpu::TextureDescriptor {
+                        label: Some("pass target"),
+                        size: wgpu::Extent3d {
+                            width: self.viewport.width,
+                            height: self.viewport.height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: TEXTURE_FORMAT,
+                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                        view_formats: &[],
+                    })
+                    .create_view(&Default::default())
+            })
+            .collect();
+
+        let passes = self.passes.iter().chain([&self.copy, &self.crt]);
+
+        self.bind_groups = passes
+            .enumerate()
+            .map(|(i, pass)| {
+                // `copy` and `crt` both read from the end of the chain.
+                let source = match i.min(self.passes.len()) {
+                    0 => &self.display_view,
+                    i => &self.targets[i - 1],
+                };
+
+                self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("pass"),
+                    layout: &self.layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: pass.params.as_entire_binding(),
+                        },
+                    ],
+                })
+            })
+            .collect();
+    }
+}
+
+/// Build the pipeline for one pass, reporting WGSL errors rather than panicking on them.
+async fn compile(
+    device: &Device,
+    layout: &BindGroupLayout,
+    pass: &ShaderPass,
+    format: TextureFormat,
+) -> Result<Pass, ShaderError> {
+    let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&pass.label),
+        source: wgpu::ShaderSource::Wgsl(format!("{}\n{}", PRELUDE, pass.source).into()),
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some(&pass.label),
+        bind_group_layouts: &[Some(layout)],
+        immediate_size: 0,
+    });
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some(&pass.label),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &module,
+            entry_point: Some("vs_main"),
+            compilation_options: Default::default(),
+            buffers: &[],
+        },
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        fragment: Some(wgpu::FragmentState {
+            module: &module,
+            entry_point: Some("fs_main"),
+            compilation_options: Default::default(),
+            targets: &[Some(format.into())],
+        }),
+        multiview_mask: None,
+        cache: None,
+    });
+
+    if let Some(err) = scope.pop().await {
+        return Err(ShaderError {
+            label: pass.label.clone(),
+            message: err.to_string(),
+        });
+    }
+
+    let params = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(&pass.label),
+        size: PARAMS_LEN,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    Ok(Pass { pipeline, params })
+}
+
+/// The texture the display is uploaded to.
+fn create_display(device: &Device, width: u32, height: u32) -> Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("display"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: TEXTURE_FORMAT,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+/// The `Params` uniform, laid out as WGSL expects.
+fn params(
+    source_size: [f32; 2],
+    viewport: Viewport,
+    display_size: [f32; 2],
+    frame: u32,
+) -> [u8; PARAMS_LEN as usize] {
+    let words = [
+        source_size[0].to_bits(),
+        source_size[1].to_bits(),
+        (viewport.width as f32).to_bits(),
+        (viewport.height as f32).to_bits(),
+        display_size[0].to_bits(),
+        display_size[1].to_bits(),
+        frame,
+        0,
+    ];
+
+    let mut bytes = [0; PARAMS_LEN as usize];
+    for (chunk, word) in bytes.chunks_exact_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+
+    bytes
+}
@@ -0,0 +1,28 @@
+//! Windowed frontends for the CHIP-8 interpreter, and the plumbing they share.
+//!
+//! Each windowing backend is an optional cargo feature, so that heavyweight dependencies are only
+//! built by people who want them.
+
+pub mod beep;
+#[cfg(feature = "egui")]
+pub mod egui;
+pub mod gamepad;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+#[cfg(feature = "minifb")]
+pub mod minifb;
+pub mod options;
+pub mod recording;
+pub mod render;
+pub mod rom;
+pub mod screenshot;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+pub mod video;
+pub mod volume;
+#[cfg(feature = "webaudio")]
+pub mod webaudio;
+#[cfg(feature = "pixels")]
+pub mod winit_pixels;
+#[cfg(feature = "wgpu")]
+pub mod winit_wgpu;
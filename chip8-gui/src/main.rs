@@ -0,0 +1,351 @@
+//! Runs a ROM in a window. With several backends compiled in, `--backend NAME` picks one, and
+//! SDL is preferred otherwise.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use chip8_core::{Chip8, Quirks, RomSettings, RunEnd, RunSetup};
+
+use chip8_frontend::{random_seed, read_rom, record_input, serve_input, HotkeyAction, Palette};
+use chip8_gui::beep::{AudioConfig, Tone, Waveform};
+#[cfg(feature = "egui")]
+use chip8_gui::egui;
+#[cfg(feature = "minifb")]
+use chip8_gui::minifb;
+use chip8_gui::options::Options;
+use chip8_gui::render;
+#[cfg(feature = "sdl")]
+use chip8_gui::sdl;
+#[cfg(feature = "pixels")]
+use chip8_gui::winit_pixels;
+#[cfg(feature = "wgpu")]
+use chip8_gui::winit_wgpu;
+
+/// Runs the machine until the window is closed or the quit hotkey is pressed, and says where
+/// it stopped, to end an input log with.
+type Backend = fn(Chip8, &Options) -> Result<RunEnd, Box<dyn Error>>;
+
+/// The compiled-in backends, most preferred first.
+#[allow(unused_mut, clippy::vec_init_then_push)]
+fn backends() -> Vec<(&'static str, Backend)> {
+    let mut backends: Vec<(&'static str, Backend)> = Vec::new();
+
+    #[cfg(feature = "sdl")]
+    backends.push(("sdl", sdl::run));
+    #[cfg(feature = "pixels")]
+    backends.push(("pixels", winit_pixels::run));
+    #[cfg(feature = "wgpu")]
+    backends.push(("wgpu", winit_wgpu::run));
+    #[cfg(feature = "minifb")]
+    backends.push(("minifb", minifb::run));
+    #[cfg(feature = "egui")]
+    backends.push(("egui", egui::run));
+
+    backends
+}
+
+fn usage() -> ! {
+    eprintln!("usage: chip8-gui [--backend NAME] [--ips N] [--scale N] [--palette NAME]");
+    eprintln!("                 [--quirks PROFILE] [--rom-db FILE] [--seed N]");
+    eprintln!("                 [--foreground RRGGBB] [--background RRGGBB]");
+    eprintln!("                 [--plane2 RRGGBB] [--both-planes RRGGBB]");
+    eprintln!("                 [--waveform square|sine|triangle] [--tone HZ]");
+    eprintln!("                 [--volume PERCENT] [--sample-rate HZ] [--audio-buffer FRAMES]");
+    eprintln!("                 [--visual-beep] [--phosphor] [--crt] [--fullscreen]");
+    eprintln!("                 [--keymap LAYOUT] [--pad BUTTON=KEY]... [--hotkey ACTION=KEY]...");
+    eprintln!("                 [--shader FILE]... [--screenshot-dir DIR] [--ffmpeg FILE]");
+    eprintln!("                 [--record-input FILE] [--remote ADDRESS] [--touch-keypad]");
+    eprintln!("                 [--watch] [--library DIR] ROM");
+    eprintln!();
+    eprintln!("While running, Esc quits, F1 opens the library, F2 toggles the CRT filter, F3");
+    eprintln!("pauses, F4 resets, F5 saves a state to the current slot and F8 loads it, F6 and");
+    eprintln!("F7 change slot, Tab toggles turbo, F9 starts and stops recording a GIF, F10");
+    eprintln!("mutes, - and = turn the volume down and up, F11 toggles fullscreen (except with");
+    eprintln!("minifb) and F12 saves a screenshot. --hotkey rebinds them: ACTION is quit,");
+    eprintln!("library, crt, pause, reset, save-state, load-state, prev-slot, next-slot, turbo,");
+    eprintln!("record-gif, mute, volume-down, volume-up, fullscreen or screenshot, and KEY is");
+    eprintln!("e.g. F5, Ctrl+R or none.");
+    eprintln!("Dropping a ROM file on the window starts it in place of the one running, except");
+    eprintln!("with minifb.");
+    eprintln!();
+    eprintln!("LAYOUT is physical (the default), qwerty, azerty, qwertz, colemak, or the 16");
+    eprintln!("keys for 123C 456D 789E A0BF, e.g. 1234qwerasdfzxcv. physical uses the keys");
+    eprintln!("where 1234 QWER ASDF ZXCV are on a US keyboard, whatever the layout, except");
+    eprintln!("with minifb, which only has the characters keys type.");
+    eprintln!("With the gamepad feature, --pad binds a controller button to a keypad key,");
+    eprintln!("e.g. south=5, and a ROM.pad file next to ROM holds bindings for that game.");
+    eprintln!("--record-input logs every keypad press and release, with its frame, to FILE,");
+    eprintln!("which `chip8 replay ROM FILE` plays back, so reset, load-state and dropping");
+    eprintln!("ROMs are off.");
+    eprintln!("--remote takes keypad commands from scripts at ADDRESS, HOST:PORT or unix:PATH,");
+    eprintln!("one per line: press KEY, release KEY or tap KEY, each optionally with a frame.");
+    eprintln!("--waveform and --tone set the buzzer's sound, a 440Hz square wave by default,");
+    eprintln!("heard with SDL, or with the other backends through the audio or cpal feature.");
+    eprintln!("--volume starts at PERCENT, 0 to 100, and unmuted, instead of the volume saved");
+    eprintln!("when it was last changed with the hotkeys.");
+    eprintln!("--sample-rate and --audio-buffer override the sound device's defaults: a");
+    eprintln!("smaller buffer for less delay, a larger one if the sound crackles.");
+    eprintln!("--visual-beep inverts the screen's colours while the buzzer sounds, as happens");
+    eprintln!("anyway when there's no sound device or the build has no sound.");
+    eprintln!("--touch-keypad shows egui's on-screen keypad from the start, which otherwise");
+    eprintln!("appears when the screen is first touched.");
+    eprintln!("--watch starts ROM again from the top whenever the file is rewritten, e.g. by an");
+    eprintln!("assembler, for an edit-assemble-run loop. It can't be used with --record-input.");
+    eprintln!("--library lists the ROMs in DIR, with their sizes and the CHIP-8 variant they");
+    eprintln!("seem to be for, to start any of them from. It opens from the start if ROM is");
+    eprintln!("left out, and needs the egui backend, which it picks by default.");
+    eprintln!("--quirks is the interpreter to behave like: modern, vip, schip or xo-chip.");
+    eprintln!("Without it or --ips, well-known ROMs get the profile and speed they need from a");
+    eprintln!("built-in database, and the others modern at 600. --rom-db FILE is looked in");
+    eprintln!("first, with a line per ROM: SHA1 PROFILE IPS TITLE.");
+    eprintln!("--seed N seeds the random number generator, so that games play the same way");
+    eprintln!("every time. By default, it's different each run.");
+    process::exit(2);
+}
+
+/// Parse a colour argument, or exit explaining what's wrong with it.
+fn color(arg: Option<String>) -> [u8; 3] {
+    let arg = arg.unwrap_or_else(|| usage());
+
+    render::parse_color(&arg).unwrap_or_else(|err| {
+        eprintln!("error: {}", err);
+        process::exit(2);
+    })
+}
+
+fn main() {
+    let mut backend = None;
+    let mut clock_hz = None;
+    let mut seed = None;
+    let mut quirks = None;
+    let mut rom_db: Option<PathBuf> = None;
+    let mut options = Options::default();
+    let mut input_log: Option<PathBuf> = None;
+    let mut remote = None;
+    let mut watch = false;
+    let mut rom = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--backend" => backend = Some(args.next().unwrap_or_else(|| usage())),
+            "--ips" => {
+                clock_hz = match args.next().map(|n| n.parse()) {
+                    Some(Ok(hz)) => Some(hz),
+                    _ => usage(),
+                }
+            }
+            "--seed" => {
+                seed = match args.next().map(|n| n.parse()) {
+                    Some(Ok(n)) => Some(n),
+                    _ => usage(),
+                }
+            }
+            "--quirks" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                quirks = Some(Quirks::named(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "error: unknown quirks profile {:?}, expected one of {}",
+                        name,
+                        Quirks::NAMES.join(", ")
+                    );
+                    process::exit(2);
+                }));
+            }
+            "--rom-db" => rom_db = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--scale" => {
+                options.scale = match args.next().map(|n| n.parse()) {
+                    Some(Ok(scale)) if scale > 0 => scale,
+                    _ => usage(),
+                }
+            }
+            "--palette" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                options.palette = Palette::named(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "error: unknown palette {:?}, expected one of {}",
+                        name,
+                        Palette::NAMES.join(", ")
+                    );
+                    process::exit(2);
+                });
+            }
+            "--foreground" => options.palette.foreground = color(args.next()),
+            "--background" => options.palette.background = color(args.next()),
+            "--plane2" => options.palette.plane2 = color(args.next()),
+            "--both-planes" => options.palette.both_planes = color(args.next()),
+            "--waveform" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                options.tone.waveform = Waveform::named(&name).unwrap_or_else(|| {
+                    eprintln!(
+                        "error: unknown waveform {:?}, expected one of {}",
+                        name,
+                        Waveform::NAMES.join(", ")
+                    );
+                    process::exit(2);
+                });
+            }
+            "--tone" => {
+                let hz = args.next().unwrap_or_else(|| usage());
+                options.tone.frequency = Tone::parse_frequency(&hz).unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    process::exit(2);
+                });
+            }
+            "--volume" => {
+                options.volume = match args.next().map(|n| n.parse()) {
+                    Some(Ok(percent)) if percent <= 100 => Some(percent),
+                    _ => usage(),
+                }
+            }
+            "--sample-rate" => {
+                options.audio.sample_rate = match args.next().map(|n| n.parse()) {
+                    Some(Ok(hz)) if AudioConfig::SAMPLE_RATES.contains(&hz) => Some(hz),
+                    _ => usage(),
+                }
+            }
+            "--audio-buffer" => {
+                options.audio.buffer_size = match args.next().map(|n| n.parse()) {
+                    Some(Ok(frames)) if AudioConfig::BUFFER_SIZES.contains(&frames) => Some(frames),
+                    _ => usage(),
+                }
+            }
+            "--visual-beep" => options.visual_beep = true,
+            "--phosphor" => options.phosphor = true,
+            "--crt" => options.crt = true,
+            "--fullscreen" => options.fullscreen = true,
+            "--touch-keypad" => options.touch_keypad = true,
+            "--watch" => watch = true,
+            "--library" => options.library = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--keymap" => {
+                let layout = args.next().unwrap_or_else(|| usage());
+                options.keymap = layout.parse().unwrap_or_else(|err| {
+                    eprintln!("error: {}", err);
+                    process::exit(2);
+                });
+            }
+            "--pad" => {
+                let binding = args.next().unwrap_or_else(|| usage());
+                if let Err(err) = options.pad_map.bind(&binding) {
+                    eprintln!("error: {}", err);
+                    process::exit(2);
+                }
+            }
+            "--hotkey" => {
+                let binding = args.next().unwrap_or_else(|| usage());
+                if let Err(err) = options.hotkeys.bind(&binding) {
+                    eprintln!("error: {}", err);
+                    process::exit(2);
+                }
+            }
+            "--shader" => options
+                .shaders
+                .push(args.next().unwrap_or_else(|| usage()).into()),
+            "--ffmpeg" => options.video = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--record-input" => input_log = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--remote" => remote = Some(args.next().unwrap_or_else(|| usage())),
+            "--screenshot-dir" => {
+                options.screenshot_dir = args.next().unwrap_or_else(|| usage()).into()
+            }
+            _ if rom.is_none() => rom = Some(arg),
+            _ => usage(),
+        }
+    }
+    if rom.is_none() && options.library.is_none() {
+        usage();
+    }
+    if input_log.is_some() {
+        // A replay only has the input to go on, so it couldn't follow a jump back, or into
+        // another game.
+        options.hotkeys.set(HotkeyAction::Reset, None);
+        options.hotkeys.set(HotkeyAction::LoadState, None);
+        options.drop_roms = false;
+        for (flag, given) in [("--watch", watch), ("--library", options.library.is_some())] {
+            if given {
+                eprintln!("error: {} can't be used with --record-input", flag);
+                process::exit(2);
+            }
+        }
+    }
+    if watch {
+        let Some(rom) = &rom else {
+            eprintln!("error: --watch needs a ROM");
+            process::exit(2);
+        };
+        options.watch = Some(rom.into());
+    }
+    options.show_library = rom.is_none();
+
+    // Bindings for this game in particular win over the command line's.
+    if let Some(rom) = &rom {
+        let pad_file = Path::new(rom).with_extension("pad");
+        if let Ok(text) = fs::read_to_string(&pad_file) {
+            if let Err(err) = options.pad_map.apply(&text) {
+                eprintln!("error: {}: {}", pad_file.display(), err);
+                process::exit(2);
+            }
+        }
+    }
+
+    options.rom_settings = RomSettings::new(quirks, clock_hz);
+    if let Some(path) = &rom_db {
+        let read = |settings: RomSettings| -> Result<RomSettings, Box<dyn Error>> {
+            Ok(settings.with_known_roms(fs::read_to_string(path)?)?)
+        };
+        options.rom_settings = read(options.rom_settings).unwrap_or_else(|err| {
+            eprintln!("error: {}: {}", path.display(), err);
+            process::exit(2);
+        });
+    }
+
+    // egui is the only backend that can show the library.
+    let backend = backend.or_else(|| options.library.is_some().then(|| "egui".to_string()));
+    let backends = backends();
+    let run = match &backend {
+        Some(name) => backends.iter().find(|(n, _)| n == name),
+        None => backends.first(),
+    };
+    let Some(&(name, run)) = run else {
+        match backend {
+            Some(name) => eprintln!("error: chip8-gui was built without the {} backend", name),
+            None => eprintln!("error: chip8-gui was built without a windowing backend"),
+        }
+        process::exit(1);
+    };
+    if options.library.is_some() && name != "egui" {
+        eprintln!("error: --library needs the egui backend");
+        process::exit(2);
+    }
+
+    // Without a ROM, the machine waits with an empty memory until one is picked.
+    let read = |path: &String| read_rom(Path::new(path));
+    let result = rom.as_ref().map_or(Ok(Vec::new()), read).and_then(|rom| {
+        let (quirks, clock_hz) = options.rom_settings.for_rom(&rom);
+        let setup = RunSetup {
+            seed: seed.unwrap_or_else(random_seed),
+            clock_hz,
+            quirks,
+            ..RunSetup::for_rom(&rom)
+        };
+        let mut cpu = setup.builder(&rom).build()?;
+        let log = input_log
+            .as_ref()
+            .map(|path| record_input(&mut cpu, path, &setup))
+            .transpose()?;
+        if let Some(address) = &remote {
+            serve_input(address, cpu.input_sender())?;
+        }
+        let end = run(cpu, &options)?;
+
+        if let Some(log) = log {
+            log.finish(end)?;
+        }
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,302 @@
+//! The minifb frontend: a window showing the framebuffer at an integer scale, and the
+//! keyboard. No sound. It's the smallest complete example of plugging a frontend into the core.
+
+use std::error::Error;
+
+use chip8_core::{AudioPattern, AudioSink, Chip8, DisplaySink, KeyInput, RunEnd};
+
+use chip8_frontend::{
+    Controls, FramePacer, Hotkey, HotkeyAction, HotkeyKey, Hotkeys, Keymap, RomWatcher,
+};
+use minifb::{Key, KeyRepeat, Window, WindowOptions};
+
+use crate::beep::Beeper;
+use crate::gamepad::Gamepads;
+use crate::options::Options;
+use crate::recording::Recorder;
+use crate::render::{self, Screen};
+use crate::rom;
+use crate::screenshot::Screenshots;
+use crate::video::VideoExport;
+use crate::volume::Volume;
+
+struct Minifb {
+    window: Window,
+    /// The window's contents, `width` by `height`, with the display letterboxed in it.
+    buffer: Vec<u32>,
+    screen: Screen,
+    screenshots: Screenshots,
+    recorder: Recorder,
+    video: Option<VideoExport>,
+    crt: bool,
+    keymap: Keymap,
+    gamepads: Gamepads,
+    beeper: Beeper,
+    volume: Volume,
+    hotkeys: Hotkeys,
+    controls: Controls,
+    watcher: Option<RomWatcher>,
+    width: usize,
+    height: usize,
+}
+
+impl Minifb {
+    /// Catch up with a reset, a loaded state or the ROM rewritten, even while paused. Returns
+    /// whether the screen changed.
+    fn jumped(&mut self, cpu: &Chip8) -> bool {
+        self.beeper
+            .follow(!self.controls.is_paused() && cpu.timers().sound > 0);
+        let changed = self.screen.update(cpu.display());
+        self.screen.set_inverted(self.beeper.flashing()) || changed
+    }
+
+    /// Match the buffer to the window after a resize. Returns whether it changed.
+    fn fit_window(&mut self) -> bool {
+        let (width, height) = self.window.get_size();
+        if (width, height) == (self.width, self.height) || width == 0 || height == 0 {
+            return false;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.buffer = vec![0; width * height];
+        true
+    }
+
+    /// The action of the hotkey made by pressing `key` with whatever modifiers are held.
+    fn hotkey_action(&self, key: Key) -> Option<HotkeyAction> {
+        let down = |keys: [Key; 2]| keys.iter().any(|&key| self.window.is_key_down(key));
+        let hotkey = Hotkey::new(from_minifb(key)?)
+            .with_ctrl(down([Key::LeftCtrl, Key::RightCtrl]))
+            .with_shift(down([Key::LeftShift, Key::RightShift]));
+
+        self.hotkeys.action(hotkey)
+    }
+
+    /// Scale the screen's image into the buffer.
+    fn redraw(&mut self) {
+        let rgb = |[r, g, b]: [u8; 3]| u32::from_be_bytes([0, r, g, b]);
+        let border = rgb(self.screen.palette().background);
+        let image = self.screen.rgba();
+        let viewport = self.screen.viewport(self.width as u32, self.height as u32);
+        let (width, height) = (self.screen.width(), self.screen.height());
+        let (left, top) = (viewport.x as usize, viewport.y as usize);
+        let scale = viewport.scale as usize;
+
+        for (i, pixel) in self.buffer.iter_mut().enumerate() {
+            let (x, y) = (i % self.width, i / self.width);
+            *pixel = match (x.checked_sub(left), y.checked_sub(top)) {
+                (Some(x), Some(y)) if x / scale < width && y / scale < height => {
+                    let i = (y / scale * width + x / scale) * 4;
+                    let shade = if self.crt {
+                        render::crt_shade(x % scale, y % scale, scale)
+                    } else {
+                        256
+                    };
+
+                    rgb([0, 1, 2].map(|c| (image[i + c] as u16 * shade / 256) as u8))
+                }
+                _ => border,
+            };
+        }
+    }
+}
+
+// The screen is updated once per frame instead, so that phosphor fading keeps going.
+impl DisplaySink for Minifb {}
+
+impl KeyInput for Minifb {
+    fn is_down(&mut self, key: u8) -> bool {
+        to_minifb(self.keymap.key_char(key)).is_some_and(|key| self.window.is_key_down(key))
+            || self.gamepads.is_down(key)
+    }
+}
+
+impl AudioSink for Minifb {
+    fn start_beep(&mut self) {
+        self.beeper.start();
+    }
+
+    fn stop_beep(&mut self) {
+        self.beeper.stop();
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.beeper.set_pattern(pattern);
+    }
+}
+
+fn to_minifb(c: char) -> Option<Key> {
+    let key = match c {
+        '0' => Key::Key0,
+        '1' => Key::Key1,
+        '2' => Key::Key2,
+        '3' => Key::Key3,
+        '4' => Key::Key4,
+        '5' => Key::Key5,
+        '6' => Key::Key6,
+        '7' => Key::Key7,
+        '8' => Key::Key8,
+        '9' => Key::Key9,
+        'a' => Key::A,
+        'b' => Key::B,
+        'c' => Key::C,
+        'd' => Key::D,
+        'e' => Key::E,
+        'f' => Key::F,
+        'g' => Key::G,
+        'h' => Key::H,
+        'i' => Key::I,
+        'j' => Key::J,
+        'k' => Key::K,
+        'l' => Key::L,
+        'm' => Key::M,
+        'n' => Key::N,
+        'o' => Key::O,
+        'p' => Key::P,
+        'q' => Key::Q,
+        'r' => Key::R,
+        's' => Key::S,
+        't' => Key::T,
+        'u' => Key::U,
+        'v' => Key::V,
+        'w' => Key::W,
+        'x' => Key::X,
+        'y' => Key::Y,
+        'z' => Key::Z,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// The hotkey key for a minifb key: the ones `to_minifb` knows, plus the named keys.
+fn from_minifb(key: Key) -> Option<HotkeyKey> {
+    const F_KEYS: [Key; 15] = [
+        Key::F1,
+        Key::F2,
+        Key::F3,
+        Key::F4,
+        Key::F5,
+        Key::F6,
+        Key::F7,
+        Key::F8,
+        Key::F9,
+        Key::F10,
+        Key::F11,
+        Key::F12,
+        Key::F13,
+        Key::F14,
+        Key::F15,
+    ];
+
+    let key = match key {
+        Key::Escape => HotkeyKey::Escape,
+        Key::Tab => HotkeyKey::Tab,
+        Key::Space => HotkeyKey::Space,
+        Key::Enter => HotkeyKey::Enter,
+        Key::Backspace => HotkeyKey::Backspace,
+        Key::Minus => HotkeyKey::Char('-'),
+        Key::Equal => HotkeyKey::Char('='),
+        _ => match F_KEYS.iter().position(|&f| f == key) {
+            Some(n) => HotkeyKey::F(n as u8 + 1),
+            None => HotkeyKey::Char(
+                ('0'..='9')
+                    .chain('a'..='z')
+                    .find(|&c| to_minifb(c) == Some(key))?,
+            ),
+        },
+    };
+
+    Some(key)
+}
+
+pub fn run(mut cpu: Chip8, options: &Options) -> Result<RunEnd, Box<dyn Error>> {
+    let (width, height) = options.window_size();
+    let (width, height) = (width as usize, height as usize);
+    let window = Window::new(
+        "CHIP-8",
+        width,
+        height,
+        WindowOptions {
+            resize: true,
+            ..WindowOptions::default()
+        },
+    )?;
+    let volume = options.volume();
+    let mut frontend = Minifb {
+        window,
+        buffer: vec![0; width * height],
+        screen: options.screen(),
+        screenshots: options.screenshots(),
+        recorder: options.recorder(),
+        video: options.video()?,
+        crt: options.crt,
+        keymap: options.keymap,
+        gamepads: options.gamepads(),
+        beeper: options.beeper(&volume),
+        volume,
+        hotkeys: options.hotkeys,
+        controls: options.controls(),
+        watcher: options.watcher(),
+        width,
+        height,
+    };
+    frontend.screen.update(cpu.display());
+    frontend.redraw();
+
+    let mut pacer = FramePacer::new();
+
+    while frontend.window.is_open() {
+        frontend.gamepads.poll();
+        let frames = frontend.controls.frames();
+        for _ in 0..frames {
+            cpu.run_frame_with(&mut frontend)?;
+        }
+        let mut changed = frontend.screen.update(cpu.display());
+        changed |= frontend.screen.set_inverted(frontend.beeper.flashing());
+        if frames > 0 {
+            frontend.recorder.capture(&frontend.screen);
+            if let Some(video) = &mut frontend.video {
+                video.capture(&frontend.screen, cpu.timers().sound > 0);
+            }
+        }
+
+        for key in frontend.window.get_keys_pressed(KeyRepeat::No) {
+            match frontend.hotkey_action(key) {
+                Some(HotkeyAction::Quit) => return Ok(RunEnd::of(&cpu)),
+                Some(HotkeyAction::Crt) => {
+                    frontend.crt = !frontend.crt;
+                    changed = true;
+                }
+                Some(HotkeyAction::RecordGif) => frontend.recorder.toggle(&frontend.screen),
+                Some(HotkeyAction::Screenshot) => frontend.screenshots.take(&frontend.screen),
+                Some(
+                    action @ (HotkeyAction::Mute
+                    | HotkeyAction::VolumeDown
+                    | HotkeyAction::VolumeUp),
+                ) => frontend.volume.apply(action),
+                // minifb can't go fullscreen.
+                Some(HotkeyAction::Fullscreen) | None => {}
+                Some(action) => {
+                    frontend.controls.apply(action, &mut cpu);
+                    changed |= frontend.jumped(&cpu);
+                }
+            }
+        }
+        if rom::reload(frontend.watcher.as_mut(), &mut cpu, &mut frontend.controls) {
+            changed |= frontend.jumped(&cpu);
+        }
+        if frontend.fit_window() || changed {
+            frontend.redraw();
+        }
+
+        // Also pumps the window's events, so it has to happen every frame, not just after draws.
+        frontend
+            .window
+            .update_with_buffer(&frontend.buffer, frontend.width, frontend.height)?;
+        pacer.wait();
+    }
+
+    Ok(RunEnd::of(&cpu))
+}
@@ -0,0 +1,161 @@
+use std::io;
+use std::path::PathBuf;
+
+use chip8_core::{RomSettings, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+use chip8_frontend::{Controls, Hotkeys, Keymap, Palette, RomWatcher};
+
+use crate::beep::{AudioConfig, Beeper, Tone};
+use crate::gamepad::{Gamepads, PadMap};
+use crate::recording::Recorder;
+use crate::render::Screen;
+use crate::screenshot::Screenshots;
+use crate::video::VideoExport;
+use crate::volume::Volume;
+
+/// The window scale when `--scale` isn't given.
+pub const DEFAULT_SCALE: u32 = 10;
+
+/// Settings from the command line, passed to whichever backend runs. Backends ignore the ones
+/// that don't apply to them.
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// How many window pixels wide each display pixel starts out. Windows can be resized
+    /// afterwards, and the display stays the largest whole multiple that fits.
+    pub scale: u32,
+    pub palette: Palette,
+    /// Fade pixels out over a few frames instead of turning them off at once.
+    pub phosphor: bool,
+    /// Start with the CRT filter on. Its hotkey toggles it while running.
+    pub crt: bool,
+    /// Start out fullscreen. Its hotkey toggles it while running, except with minifb, which
+    /// can't.
+    pub fullscreen: bool,
+    /// Start with egui's on-screen keypad shown. It also appears as soon as the screen is
+    /// touched.
+    pub touch_keypad: bool,
+    /// Which keyboard keys stand for the hex keypad. By default they go by position, except
+    /// with minifb, which only reports the characters keys type.
+    pub keymap: Keymap,
+    /// Which keypad keys controller buttons press.
+    pub pad_map: PadMap,
+    /// Start ROM files dropped on the window, in place of the one running. Not with minifb,
+    /// which doesn't report drops.
+    pub drop_roms: bool,
+    /// Start the ROM at this path again whenever it's rewritten, e.g. by an assembler.
+    pub watch: Option<PathBuf>,
+    /// The directory of ROMs egui's library lists, to start any of them from.
+    pub library: Option<PathBuf>,
+    /// Start with the library open, and the machine paused until a ROM is picked from it.
+    pub show_library: bool,
+    /// The quirks and clock speed to play ROMs that are dropped, picked from the library or
+    /// reloaded with, as the first one was.
+    pub rom_settings: RomSettings,
+    /// Which keys pause, reset, save states, take screenshots and so on.
+    pub hotkeys: Hotkeys,
+    /// What the buzzer sounds like.
+    pub tone: Tone,
+    /// The buzzer's sample rate and buffer size.
+    pub audio: AudioConfig,
+    /// The volume to start at, in percent and unmuted, instead of the one saved last time.
+    pub volume: Option<u8>,
+    /// Invert the screen's colours while the buzzer sounds. This happens anyway when there's
+    /// no sound device to hear it on.
+    pub visual_beep: bool,
+    /// WGSL post-processing passes for the wgpu backend, applied in order.
+    pub shaders: Vec<PathBuf>,
+    /// Where screenshots and GIF recordings are saved.
+    pub screenshot_dir: PathBuf,
+    /// Stream the whole session to `ffmpeg`, which encodes it to this file.
+    pub video: Option<PathBuf>,
+}
+
+impl Options {
+    /// A `Screen` with these colours and effects.
+    pub fn screen(&self) -> Screen {
+        Screen::new(self.palette, self.phosphor)
+    }
+
+    /// Screenshots at the starting scale, so they come out the same size however the window has
+    /// been resized.
+    pub fn screenshots(&self) -> Screenshots {
+        Screenshots::new(&self.screenshot_dir, self.scale as usize)
+    }
+
+    /// A recorder making GIFs the same size as screenshots, in the same place.
+    pub fn recorder(&self) -> Recorder {
+        Recorder::new(&self.screenshot_dir, self.scale as usize)
+    }
+
+    /// The connected controllers, mapped by `pad_map`.
+    pub fn gamepads(&self) -> Gamepads {
+        Gamepads::new(self.pad_map)
+    }
+
+    /// The volume saved last time, or `volume`, unmuted, if it's set.
+    pub fn volume(&self) -> Volume {
+        match self.volume {
+            Some(percent) => Volume::new(percent, false),
+            None => Volume::load(),
+        }
+    }
+
+    /// The buzzer, playing `tone` as `audio` sets it up, at `volume`.
+    pub fn beeper(&self, volume: &Volume) -> Beeper {
+        Beeper::new(self.tone, self.audio, volume.gain(), self.visual_beep)
+    }
+
+    /// A watcher for the ROM, if `watch` asks for one.
+    pub fn watcher(&self) -> Option<RomWatcher> {
+        self.watch.as_ref().map(RomWatcher::new)
+    }
+
+    /// The hotkeys' controls, loading ROMs with `rom_settings`.
+    pub fn controls(&self) -> Controls {
+        Controls::with_rom_settings(self.rom_settings.clone())
+    }
+
+    /// Start `ffmpeg` if `video` asks for it.
+    pub fn video(&self) -> io::Result<Option<VideoExport>> {
+        self.video
+            .as_ref()
+            .map(|path| VideoExport::start(path, self.scale as usize))
+            .transpose()
+    }
+
+    /// The initial window size, in logical pixels.
+    pub fn window_size(&self) -> (u32, u32) {
+        (
+            DISPLAY_WIDTH as u32 * self.scale,
+            DISPLAY_HEIGHT as u32 * self.scale,
+        )
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            scale: DEFAULT_SCALE,
+            palette: Palette::default(),
+            phosphor: false,
+            crt: false,
+            fullscreen: false,
+            touch_keypad: false,
+            keymap: Keymap::default(),
+            pad_map: PadMap::default(),
+            drop_roms: true,
+            watch: None,
+            library: None,
+            show_library: false,
+            rom_settings: RomSettings::default(),
+            hotkeys: Hotkeys::default(),
+            tone: Tone::default(),
+            audio: AudioConfig::default(),
+            volume: None,
+            visual_beep: false,
+            shaders: Vec::new(),
+            screenshot_dir: PathBuf::from("screenshots"),
+            video: None,
+        }
+    }
+}
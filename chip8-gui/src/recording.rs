@@ -0,0 +1,195 @@
+//! Animated GIF recordings of the display, captured a frame at a time at 60Hz.
+
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use gif::{Encoder, EncodingError, Frame, Repeat};
+
+use crate::render::Screen;
+use crate::screenshot::{timestamp, unused_path};
+
+/// Frames captured per second: one per emulated frame.
+const FPS: u64 = 60;
+/// The low bits of a GIF palette index are how lit a pixel is, and the rest its colour.
+const LEVEL_BITS: u8 = 6;
+const LEVEL_MASK: u8 = (1 << LEVEL_BITS) - 1;
+
+/// Records the screen to GIFs, started and stopped with the F9 hotkey.
+///
+/// The GIF's palette has 64 levels of fading for each of the four colours, so frames are stored
+/// without dithering, and exactly unless phosphor fading is on.
+/// Frames that look the same as the one before are merged into it, so a mostly still game makes
+/// a small file.
+pub struct Recorder {
+    dir: PathBuf,
+    scale: usize,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    path: PathBuf,
+    encoder: Encoder<BufWriter<File>>,
+    width: usize,
+    height: usize,
+    /// The image waiting to be written, as palette indices at the GIF's size, and the frame it
+    /// first appeared in. It's written when a different one replaces it, once its delay is
+    /// known.
+    pending: Vec<u8>,
+    /// Scratch space for the latest frame, to compare with `pending`.
+    latest: Vec<u8>,
+    pending_since: u64,
+    /// Frames captured so far.
+    frames: u64,
+}
+
+impl Recorder {
+    pub fn new(dir: impl Into<PathBuf>, scale: usize) -> Self {
+        Recorder {
+            dir: dir.into(),
+            scale,
+            recording: None,
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Start recording to a new file in the directory, named after the time, and return its
+    /// path. The directory is created if it doesn't exist yet.
+    pub fn start(&mut self, screen: &Screen) -> Result<PathBuf, EncodingError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = unused_path(&self.dir, &timestamp(SystemTime::now()), "gif");
+
+        let palette: Vec<u8> = (0..=255u8)
+            .flat_map(|index| {
+                let level = (index & LEVEL_MASK) as u32 * 255 / LEVEL_MASK as u32;
+                screen.palette().blend(index >> LEVEL_BITS, level as u8)
+            })
+            .collect();
+        let (width, height) = self.size();
+        let mut encoder = Encoder::new(
+            BufWriter::new(File::create_new(&path)?),
+            width as u16,
+            height as u16,
+            &palette,
+        )?;
+        encoder.set_repeat(Repeat::Infinite)?;
+
+        self.recording = Some(Recording {
+            path: path.clone(),
+            encoder,
+            width,
+            height,
+            pending: indices(screen, width, height).collect(),
+            latest: Vec::new(),
+            pending_since: 0,
+            frames: 0,
+        });
+
+        Ok(path)
+    }
+
+    /// Finish the recording, if there is one, and return where it was saved.
+    pub fn stop(&mut self) -> Result<Option<PathBuf>, EncodingError> {
+        let Some(mut recording) = self.recording.take() else {
+            return Ok(None);
+        };
+
+        recording.flush()?;
+        recording.encoder.into_inner()?;
+
+        Ok(Some(recording.path))
+    }
+
+    /// Add the screen as it looks this frame. Call this once a frame while recording, whether or
+    /// not the screen changed, so that the GIF keeps time. Does nothing when not recording.
+    pub fn capture(&mut self, screen: &Screen) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        recording.latest.clear();
+        recording
+            .latest
+            .extend(indices(screen, recording.width, recording.height));
+        if recording.latest != recording.pending {
+            if let Err(err) = recording.flush() {
+                eprintln!("error: stopped recording: {}", err);
+                self.recording = None;
+                return;
+            }
+            std::mem::swap(&mut recording.pending, &mut recording.latest);
+            recording.pending_since = recording.frames;
+        }
+        recording.frames += 1;
+    }
+
+    /// The GIF's size: a lores display scaled up, which a hires one is squeezed into.
+    fn size(&self) -> (usize, usize) {
+        (DISPLAY_WIDTH * self.scale, DISPLAY_HEIGHT * self.scale)
+    }
+
+    /// Start recording if not already, or stop if so, and say what happened on stderr. For
+    /// hotkeys, which have nowhere better to report to.
+    pub fn toggle(&mut self, screen: &Screen) {
+        if self.is_recording() {
+            match self.stop() {
+                Ok(Some(path)) => eprintln!("saved recording to {}", path.display()),
+                Ok(None) => {}
+                Err(err) => eprintln!("error: couldn't save recording: {}", err),
+            }
+        } else {
+            match self.start(screen) {
+                Ok(path) => eprintln!("recording to {}", path.display()),
+                Err(err) => eprintln!("error: couldn't start recording: {}", err),
+            }
+        }
+    }
+}
+
+impl Drop for Recorder {
+    /// Finish the file rather than leave it truncated when the window closes mid-recording.
+    fn drop(&mut self) {
+        if let Ok(Some(path)) = self.stop() {
+            eprintln!("saved recording to {}", path.display());
+        }
+    }
+}
+
+impl Recording {
+    /// Write the pending image, shown from `pending_since` up to now.
+    fn flush(&mut self) -> Result<(), EncodingError> {
+        // GIF delays are in hundredths of a second, which 60Hz frames don't divide into evenly.
+        // Rounding the start and end times instead of each delay keeps the total right.
+        let centis = |frame: u64| frame * 100 / FPS;
+        let delay = centis(self.frames) - centis(self.pending_since);
+        if delay == 0 {
+            return Ok(());
+        }
+
+        self.encoder.write_frame(&Frame {
+            width: self.width as u16,
+            height: self.height as u16,
+            delay: delay.min(u16::MAX as u64) as u16,
+            buffer: Cow::Borrowed(&self.pending),
+            ..Frame::default()
+        })
+    }
+}
+
+/// The screen as GIF palette indices, stretched to `width` by `height`.
+fn indices(screen: &Screen, width: usize, height: usize) -> impl Iterator<Item = u8> + '_ {
+    let (levels, colors) = (screen.levels(), screen.colors());
+
+    (0..height).flat_map(move |y| {
+        (0..width).map(move |x| {
+            let i = screen.nearest(x, y, width, height);
+            colors[i] << LEVEL_BITS | levels[i] >> (8 - LEVEL_BITS)
+        })
+    })
+}
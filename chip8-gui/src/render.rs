@@ -0,0 +1,292 @@
+use std::fmt;
+
+use chip8_core::{Framebuffer, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+use chip8_frontend::Palette;
+
+/// Convert an sRGB colour to linear light, e.g. for clearing an sRGB surface on the GPU.
+pub fn srgb_to_linear(color: [u8; 3]) -> [f64; 3] {
+    color.map(|c| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    })
+}
+
+/// A colour that isn't six hex digits, optionally after a `#`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} isn't a colour like #33FF66", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Parse an RGB colour written as `RRGGBB` or `#RRGGBB`.
+pub fn parse_color(s: &str) -> Result<[u8; 3], ParseColorError> {
+    let err = || ParseColorError(s.to_string());
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(err());
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| err());
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// How much of its brightness an unlit pixel keeps each frame with phosphor persistence on, as
+/// a fraction of 256.
+const PHOSPHOR_DECAY: u16 = 150;
+/// Pixels dimmer than this go straight to the background colour, so fading ends after about
+/// half a dozen frames instead of trailing off forever.
+const PHOSPHOR_CUTOFF: u8 = 16;
+
+/// `color` turned negative if `inverted` is set.
+fn invert(color: [u8; 3], inverted: bool) -> [u8; 3] {
+    if inverted {
+        color.map(|channel| !channel)
+    } else {
+        color
+    }
+}
+
+/// Turns the framebuffer into the RGBA image a frontend shows, in its palette's colours: 64x32,
+/// or 128x64 while a SUPER-CHIP game is in hires.
+///
+/// With phosphor persistence on, pixels that go dark fade out over a few frames instead of
+/// vanishing, like on a CRT. Games that erase and redraw their sprites every frame then stop
+/// flickering. Fading happens per frame, so `update` has to be called every frame, whether or not
+/// anything was drawn.
+pub struct Screen {
+    palette: Palette,
+    phosphor: bool,
+    width: usize,
+    height: usize,
+    /// How lit each pixel looks, from 0 for the background to 255 for its colour.
+    levels: Vec<u8>,
+    /// The colour each pixel is lit in, or was before it went dark and started fading.
+    colors: Vec<u8>,
+    /// Whether `rgba` is in inverted colours, to show the buzzer without sound.
+    inverted: bool,
+    rgba: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(palette: Palette, phosphor: bool) -> Self {
+        let [r, g, b] = palette.background;
+
+        Screen {
+            palette,
+            phosphor,
+            width: DISPLAY_WIDTH,
+            height: DISPLAY_HEIGHT,
+            levels: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            colors: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            inverted: false,
+            rgba: [r, g, b, 0xFF].repeat(DISPLAY_WIDTH * DISPLAY_HEIGHT),
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// The image's width in pixels, which is the display's in its current mode.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Catch up with `framebuffer` at the end of a frame. Returns whether the image changed,
+    /// including its size.
+    pub fn update(&mut self, framebuffer: &Framebuffer) -> bool {
+        let mut changed = false;
+
+        // A new resolution starts over from the background, fading and all.
+        if (framebuffer.width(), framebuffer.height()) != (self.width, self.height) {
+            let [r, g, b] = invert(self.palette.background, self.inverted);
+            self.width = framebuffer.width();
+            self.height = framebuffer.height();
+            self.levels = vec![0; self.width * self.height];
+            self.colors = vec![0; self.width * self.height];
+            self.rgba = [r, g, b, 0xFF].repeat(self.width * self.height);
+            changed = true;
+        }
+
+        let width = self.width;
+        let inverted = self.inverted;
+        for (i, (level, color)) in self.levels.iter_mut().zip(&mut self.colors).enumerate() {
+            let lit = framebuffer.color(i % width, i / width);
+            let new = if lit != 0 {
+                0xFF
+            } else if self.phosphor {
+                let faded = (*level as u16 * PHOSPHOR_DECAY / 256) as u8;
+                if faded < PHOSPHOR_CUTOFF {
+                    0
+                } else {
+                    faded
+                }
+            } else {
+                0
+            };
+
+            // A fading pixel keeps its colour.
+            let new_color = if lit != 0 { lit } else { *color };
+            if new != *level || new_color != *color {
+                *level = new;
+                *color = new_color;
+                let [r, g, b] = invert(self.palette.blend(new_color, new), inverted);
+                self.rgba[i * 4..i * 4 + 4].copy_from_slice(&[r, g, b, 0xFF]);
+                changed = true;
+            }
+        }
+
+        changed
+    }
+
+    /// Show the image in inverted colours, e.g. while the buzzer sounds on a machine that can't
+    /// play it, or go back to normal. Returns whether the image changed.
+    pub fn set_inverted(&mut self, inverted: bool) -> bool {
+        if inverted == self.inverted {
+            return false;
+        }
+
+        self.inverted = inverted;
+        for pixel in self.rgba.chunks_exact_mut(4) {
+            for channel in &mut pixel[..3] {
+                *channel = !*channel;
+            }
+        }
+
+        true
+    }
+
+    /// How lit each pixel looks, row by row, from 0 for the background to 255 for its colour in
+    /// `colors`. `palette().blend` turns the two into what's shown.
+    pub fn levels(&self) -> &[u8] {
+        &self.levels
+    }
+
+    /// The colour each pixel is lit in, row by row, as in `Framebuffer::color`. Pixels still
+    /// fading out keep the colour they had.
+    pub fn colors(&self) -> &[u8] {
+        &self.colors
+    }
+
+    /// Where the image goes in a window `width` by `height`.
+    pub fn viewport(&self, width: u32, height: u32) -> Viewport {
+        Viewport::fit(width, height, self.width, self.height)
+    }
+
+    /// The image, row by row, for upload to a texture.
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// The image blown up `scale` times into `out`, with software CRT effects if `crt` is set.
+    /// For frontends that can't scale and filter on the GPU.
+    pub fn scaled(&self, scale: usize, crt: bool, out: &mut Vec<u8>) {
+        let width = self.width * scale;
+        out.clear();
+        out.reserve(self.rgba.len() * scale * scale);
+
+        for y in 0..self.height * scale {
+            for x in 0..width {
+                let i = (y / scale * self.width + x / scale) * 4;
+                let shade = if crt {
+                    crt_shade(x % scale, y % scale, scale)
+                } else {
+                    256
+                };
+
+                for c in &self.rgba[i..i + 3] {
+                    out.push((*c as u16 * shade / 256) as u8);
+                }
+                out.push(0xFF);
+            }
+        }
+    }
+
+    /// The image resized to exactly `width` by `height` into `out`, picking the nearest pixel.
+    /// For captures that stay the same size when the display changes resolution.
+    pub fn resized(&self, width: usize, height: usize, out: &mut Vec<u8>) {
+        out.clear();
+        out.reserve(width * height * 4);
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = self.nearest(x, y, width, height) * 4;
+                out.extend_from_slice(&self.rgba[i..i + 4]);
+            }
+        }
+    }
+
+    /// The index of the pixel nearest `(x, y)` in an image `width` by `height` stretched over this
+    /// one.
+    pub fn nearest(&self, x: usize, y: usize, width: usize, height: usize) -> usize {
+        y * self.height / height * self.width + x * self.width / width
+    }
+}
+
+/// How much of a scanline's brightness survives, as a fraction of 256.
+const SCANLINE: u16 = 140;
+/// How much of the pixel grid's brightness survives, as a fraction of 256.
+const GRID: u16 = 200;
+
+/// The software CRT filter: how bright a window pixel is, as a fraction of 256, when it's at
+/// `(x, y)` within a display pixel that's been scaled up `scale` times.
+///
+/// The bottom row of every display pixel is a dark scanline, and its right-hand column is a
+/// fainter line of the shadow mask's grid. Neither is drawn at scales too small to leave the
+/// pixel itself visible.
+pub fn crt_shade(x: usize, y: usize, scale: usize) -> u16 {
+    let mut shade = 256;
+
+    if scale >= 2 && y == scale - 1 {
+        shade = shade * SCANLINE / 256;
+    }
+    if scale >= 3 && x == scale - 1 {
+        shade = shade * GRID / 256;
+    }
+
+    shade
+}
+
+/// Where the display goes in a window: the largest whole multiple of it that fits, centred, so
+/// that nearest-neighbour scaling keeps every pixel the same size. `Screen::viewport` fits its
+/// current image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Window pixels per display pixel. At least 1, even if the window is smaller than that.
+    pub scale: u32,
+}
+
+impl Viewport {
+    /// Fit an image `image_width` by `image_height` into a window `width` by `height`.
+    pub fn fit(width: u32, height: u32, image_width: usize, image_height: usize) -> Self {
+        let (image_width, image_height) = (image_width as u32, image_height as u32);
+        let scale = (width / image_width).min(height / image_height).max(1);
+        let (w, h) = (image_width * scale, image_height * scale);
+
+        Viewport {
+            x: width.saturating_sub(w) / 2,
+            y: height.saturating_sub(h) / 2,
+            width: w,
+            height: h,
+            scale,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! Loading ROM files in place of the running one, whether dropped on the window or rewritten
+//! while they're watched.
+
+use std::path::Path;
+
+use chip8_core::Chip8;
+
+use chip8_frontend::{read_rom, Controls, RomWatcher};
+
+/// Start the ROM at `path` on `cpu` from the top, in place of the one running, as
+/// `Controls::load_rom` does. Returns whether it did: a file that isn't a ROM that fits is
+/// reported on stderr, and the game that was running carries on.
+pub fn load(path: &Path, cpu: &mut Chip8, controls: &mut Controls) -> bool {
+    let loaded = read_rom(path).and_then(|rom| {
+        controls
+            .load_rom(cpu, &rom)
+            .map_err(|err| format!("{}: {}", path.display(), err).into())
+    });
+
+    match loaded {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            false
+        }
+    }
+}
+
+/// Start the ROM again if `watcher` has seen it rewritten, returning whether it did.
+pub fn reload(watcher: Option<&mut RomWatcher>, cpu: &mut Chip8, controls: &mut Controls) -> bool {
+    let Some(watcher) = watcher else {
+        return false;
+    };
+
+    watcher.changed() && load(watcher.path(), cpu, controls)
+}
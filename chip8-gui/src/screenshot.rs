@@ -0,0 +1,107 @@
+//! PNG screenshots of the display, in the palette it's shown in and scaled up like the window.
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use png::{BitDepth, ColorType, Encoder, EncodingError};
+
+use crate::render::Screen;
+
+/// Encode the screen's image as a PNG `64 * scale` by `32 * scale` pixels, the size of a lores
+/// display scaled `scale` times, whatever resolution it's in.
+pub fn write_png(screen: &Screen, scale: usize, out: impl Write) -> Result<(), EncodingError> {
+    let (width, height) = (DISPLAY_WIDTH * scale, DISPLAY_HEIGHT * scale);
+    let mut image = Vec::new();
+    screen.resized(width, height, &mut image);
+
+    let mut encoder = Encoder::new(out, width as u32, height as u32);
+    encoder.set_color(ColorType::Rgba);
+    encoder.set_depth(BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image)?;
+    writer.finish()
+}
+
+/// Where the F12 hotkey saves screenshots, and how big they are.
+#[derive(Debug, Clone)]
+pub struct Screenshots {
+    dir: PathBuf,
+    scale: usize,
+}
+
+impl Screenshots {
+    pub fn new(dir: impl Into<PathBuf>, scale: usize) -> Self {
+        Screenshots {
+            dir: dir.into(),
+            scale,
+        }
+    }
+
+    /// Save the screen to a new file in the directory, named after the time, and return its path.
+    /// The directory is created if it doesn't exist yet.
+    pub fn save(&self, screen: &Screen) -> Result<PathBuf, EncodingError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = unused_path(&self.dir, &timestamp(SystemTime::now()), "png");
+
+        let mut out = BufWriter::new(File::create_new(&path)?);
+        write_png(screen, self.scale, &mut out)?;
+        out.flush()?;
+
+        Ok(path)
+    }
+
+    /// Save the screen and say where it went, or why it couldn't be saved, on stderr. For
+    /// hotkeys, which have nowhere better to report to.
+    pub fn take(&self, screen: &Screen) {
+        match self.save(screen) {
+            Ok(path) => eprintln!("saved screenshot to {}", path.display()),
+            Err(err) => eprintln!("error: couldn't save screenshot: {}", err),
+        }
+    }
+}
+
+/// `chip8-STAMP.EXT` in `dir`, or `chip8-STAMP-2.EXT` and so on if that's been taken already,
+/// e.g. by a screenshot earlier in the same second.
+pub(crate) fn unused_path(dir: &Path, stamp: &str, ext: &str) -> PathBuf {
+    let mut path = dir.join(format!("chip8-{}.{}", stamp, ext));
+    let mut n = 1;
+    while path.exists() {
+        n += 1;
+        path = dir.join(format!("chip8-{}-{}.{}", stamp, n, ext));
+    }
+
+    path
+}
+
+/// `time` as `YYYYMMDD-HHMMSS`, in UTC.
+pub(crate) fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    let (days, secs) = (secs / 86_400, secs % 86_400);
+
+    // Days since 1970 to a Gregorian date, from Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3_600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
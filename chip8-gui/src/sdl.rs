@@ -0,0 +1,423 @@
+//! The SDL2 frontend: a scaled window, keyboard input and a square-wave beep.
+
+use std::error::Error;
+use std::path::Path;
+
+use chip8_core::{AudioPattern, AudioSink, Chip8, DisplaySink, KeyInput, RunEnd};
+
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_frontend::{
+    Controls, FramePacer, Hotkey, HotkeyAction, HotkeyKey, Hotkeys, Keymap, RomWatcher,
+};
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator, TextureValueError};
+use sdl2::video::{FullscreenType, Window, WindowContext};
+
+use crate::beep::Voice;
+use crate::gamepad::Gamepads;
+use crate::options::Options;
+use crate::recording::Recorder;
+use crate::render::Screen;
+use crate::rom;
+use crate::screenshot::Screenshots;
+use crate::video::VideoExport;
+use crate::volume::Volume;
+
+/// `options.tone`, or the program's audio pattern, for SDL's audio thread.
+struct Buzzer(Voice);
+
+impl AudioCallback for Buzzer {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out {
+            *sample = self.0.next_sample();
+        }
+    }
+}
+
+struct Sdl<'t> {
+    canvas: Canvas<Window>,
+    texture_creator: &'t TextureCreator<WindowContext>,
+    /// The screen's image, at 1x or, with the CRT filter on, at the viewport's scale, so
+    /// `texture_size` changes with both that and the display's resolution.
+    texture: Texture<'t>,
+    texture_size: (u32, u32),
+    screen: Screen,
+    screenshots: Screenshots,
+    recorder: Recorder,
+    video: Option<VideoExport>,
+    crt: bool,
+    /// Scratch space for the image scaled up by the CRT filter.
+    scaled: Vec<u8>,
+    keymap: Keymap,
+    keys: [bool; 16],
+    gamepads: Gamepads,
+    hotkeys: Hotkeys,
+    controls: Controls,
+    /// Whether a ROM file dropped on the window is started.
+    drop_roms: bool,
+    watcher: Option<RomWatcher>,
+    audio: Option<AudioDevice<Buzzer>>,
+    volume: Volume,
+    /// Whether the screen flashes while the buzzer sounds, as `Beeper::flashing` does for the
+    /// other backends.
+    visual_beep: bool,
+    beeping: bool,
+}
+
+// The screen is updated once per frame instead, so that phosphor fading keeps going.
+impl DisplaySink for Sdl<'_> {}
+
+impl<'t> Sdl<'t> {
+    /// Bring the window up to date with `cpu`'s display, if it looks any different, and record
+    /// the frame.
+    fn show(&mut self, cpu: &Chip8) {
+        let mut changed = self.screen.update(cpu.display());
+        changed |= self.screen.set_inverted(self.visual_beep && self.beeping);
+        self.recorder.capture(&self.screen);
+        if let Some(video) = &mut self.video {
+            video.capture(&self.screen, cpu.timers().sound > 0);
+        }
+        if changed {
+            self.upload();
+        }
+    }
+
+    /// Copy the screen to the texture and draw it. With the CRT filter on, the texture is the
+    /// size of the viewport, so that its scanlines are whole window pixels.
+    fn upload(&mut self) {
+        let scale = if self.crt {
+            let (width, height) = self.canvas.output_size().unwrap_or((0, 0));
+            self.screen.viewport(width, height).scale
+        } else {
+            1
+        };
+        let size = (
+            self.screen.width() as u32 * scale,
+            self.screen.height() as u32 * scale,
+        );
+
+        if size != self.texture_size {
+            match create_texture(self.texture_creator, size) {
+                Ok(texture) => {
+                    self.texture = texture;
+                    self.texture_size = size;
+                }
+                // Out of video memory, probably. Keep showing the old picture.
+                Err(_) => return self.draw(),
+            }
+        }
+
+        let pitch = size.0 as usize * 4;
+        // Drawing only fails if the renderer has been lost, e.g. on some GPU resets; the next
+        // frame tries again.
+        let _ = if self.crt {
+            self.screen.scaled(scale as usize, true, &mut self.scaled);
+            self.texture.update(None, &self.scaled, pitch)
+        } else {
+            self.texture.update(None, self.screen.rgba(), pitch)
+        };
+        self.draw();
+    }
+
+    /// Carry out a hotkey's `action` on `cpu`. Returns whether it was asking to quit.
+    fn hotkey(&mut self, action: HotkeyAction, cpu: &mut Chip8) -> bool {
+        match action {
+            HotkeyAction::Quit => return true,
+            HotkeyAction::Crt => {
+                self.crt = !self.crt;
+                self.upload();
+            }
+            HotkeyAction::RecordGif => self.recorder.toggle(&self.screen),
+            HotkeyAction::Fullscreen => self.toggle_fullscreen(),
+            HotkeyAction::Screenshot => self.screenshots.take(&self.screen),
+            HotkeyAction::Mute | HotkeyAction::VolumeDown | HotkeyAction::VolumeUp => {
+                self.volume.apply(action)
+            }
+            action => {
+                self.controls.apply(action, cpu);
+                self.jumped(cpu);
+            }
+        }
+
+        false
+    }
+
+    /// Catch up with a pause, a reset, a loaded state or a new ROM straight away.
+    fn jumped(&mut self, cpu: &Chip8) {
+        // Hold a beep while paused rather than leaving it droning on, and stop one a reset, a
+        // loaded state or a new ROM cut short.
+        if !self.controls.is_paused() && cpu.timers().sound > 0 {
+            self.start_beep();
+        } else {
+            self.stop_beep();
+        }
+        self.show(cpu);
+    }
+
+    /// Start the ROM file dropped on the window at `path` on `cpu`, if that's allowed.
+    fn drop_rom(&mut self, path: &Path, cpu: &mut Chip8) {
+        if self.drop_roms && rom::load(path, cpu, &mut self.controls) {
+            self.jumped(cpu);
+        }
+    }
+
+    /// Switch between a window and fullscreen at the desktop's resolution. The resize that
+    /// follows redraws the display, letterboxed.
+    fn toggle_fullscreen(&mut self) {
+        let window = self.canvas.window_mut();
+        let state = match window.fullscreen_state() {
+            FullscreenType::Off => FullscreenType::Desktop,
+            _ => FullscreenType::Off,
+        };
+        if let Err(err) = window.set_fullscreen(state) {
+            eprintln!("error: couldn't toggle fullscreen: {}", err);
+        }
+    }
+
+    /// Show the texture in the window, letterboxed at a whole-number scale.
+    fn draw(&mut self) {
+        let (width, height) = self.canvas.output_size().unwrap_or((0, 0));
+        let viewport = self.screen.viewport(width, height);
+        let dest = Rect::new(
+            viewport.x as i32,
+            viewport.y as i32,
+            viewport.width,
+            viewport.height,
+        );
+
+        let [r, g, b] = self.screen.palette().background;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+        let _ = self.canvas.copy(&self.texture, None, dest);
+        self.canvas.present();
+    }
+}
+
+impl KeyInput for Sdl<'_> {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys[key as usize] || self.gamepads.is_down(key)
+    }
+}
+
+impl AudioSink for Sdl<'_> {
+    fn start_beep(&mut self) {
+        self.beeping = true;
+        if let Some(audio) = &self.audio {
+            audio.resume();
+        }
+    }
+
+    fn stop_beep(&mut self) {
+        self.beeping = false;
+        if let Some(audio) = &self.audio {
+            audio.pause();
+        }
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        if let Some(audio) = &mut self.audio {
+            audio.lock().0.set_pattern(pattern);
+        }
+    }
+}
+
+fn create_texture(
+    creator: &TextureCreator<WindowContext>,
+    (width, height): (u32, u32),
+) -> Result<Texture<'_>, TextureValueError> {
+    creator.create_texture_streaming(PixelFormatEnum::ABGR8888, width, height)
+}
+
+/// The hotkey for an SDL keycode with modifiers `keymod`.
+fn keycode_to_hotkey(keycode: Keycode, keymod: Mod) -> Option<Hotkey> {
+    let key = match keycode {
+        Keycode::Escape => HotkeyKey::Escape,
+        Keycode::Tab => HotkeyKey::Tab,
+        Keycode::Space => HotkeyKey::Space,
+        Keycode::Return => HotkeyKey::Enter,
+        Keycode::Backspace => HotkeyKey::Backspace,
+        _ => {
+            // F1-F12 and F13-F24 each have consecutive codes.
+            let code = keycode.into_i32();
+            let f1 = Keycode::F1.into_i32();
+            let f13 = Keycode::F13.into_i32();
+            if (f1..f1 + 12).contains(&code) {
+                HotkeyKey::F((code - f1 + 1) as u8)
+            } else if (f13..f13 + 12).contains(&code) {
+                HotkeyKey::F((code - f13 + 13) as u8)
+            } else {
+                HotkeyKey::Char(char::from_u32(u32::try_from(code).ok()?)?)
+            }
+        }
+    };
+
+    Some(
+        Hotkey::new(key)
+            .with_ctrl(keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD))
+            .with_shift(keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD)),
+    )
+}
+
+/// The hex key for an SDL key. Scancodes are physical positions, while printable keys' codes
+/// are the characters they type in the current keyboard layout.
+fn key_to_hex(keymap: &Keymap, keycode: Keycode, scancode: Option<Scancode>) -> Option<u8> {
+    let position = scancode.and_then(scancode_position);
+    if let Some(key) = position.and_then(|(row, column)| keymap.position_key(row, column)) {
+        return Some(key);
+    }
+
+    let c = char::from_u32(u32::try_from(keycode.into_i32()).ok()?)?;
+    keymap.hex_key(c)
+}
+
+/// Where a scancode's key is in the block at the left of the keyboard, as
+/// `Keymap::position_key` takes it.
+fn scancode_position(scancode: Scancode) -> Option<(u8, u8)> {
+    let rows = [
+        [
+            Scancode::Num1,
+            Scancode::Num2,
+            Scancode::Num3,
+            Scancode::Num4,
+        ],
+        [Scancode::Q, Scancode::W, Scancode::E, Scancode::R],
+        [Scancode::A, Scancode::S, Scancode::D, Scancode::F],
+        [Scancode::Z, Scancode::X, Scancode::C, Scancode::V],
+    ];
+
+    rows.iter().enumerate().find_map(|(row, codes)| {
+        let column = codes.iter().position(|&code| code == scancode)?;
+        Some((row as u8, column as u8))
+    })
+}
+
+pub fn run(mut cpu: Chip8, options: &Options) -> Result<RunEnd, Box<dyn Error>> {
+    let sdl = sdl2::init()?;
+    let video = sdl.video()?;
+
+    let (width, height) = options.window_size();
+    let window = video
+        .window("CHIP-8", width, height)
+        .position_centered()
+        .resizable()
+        // Draw at the screen's real resolution on HiDPI displays, not a blurry upscale of the
+        // window's size in points. Everything that fits the display asks the canvas for its
+        // output size in pixels.
+        .allow_highdpi()
+        .build()?;
+    let mut canvas = window.into_canvas().present_vsync().build()?;
+    if options.fullscreen {
+        canvas
+            .window_mut()
+            .set_fullscreen(FullscreenType::Desktop)?;
+    }
+    let texture_creator = canvas.texture_creator();
+    let texture_size = (DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32);
+    let texture = create_texture(&texture_creator, texture_size)?;
+
+    // Play silently rather than refusing to start on machines without a sound device.
+    let volume = options.volume();
+    let audio = sdl.audio().ok().and_then(|audio| {
+        let spec = AudioSpecDesired {
+            freq: Some(options.audio.sample_rate.unwrap_or(44_100) as i32),
+            channels: Some(1),
+            samples: options.audio.buffer_size.map(|frames| frames as u16),
+        };
+
+        audio
+            .open_playback(None, &spec, |spec| {
+                Buzzer(Voice::new(options.tone, spec.freq as u32, volume.gain()))
+            })
+            .ok()
+    });
+
+    let mut frontend = Sdl {
+        canvas,
+        texture_creator: &texture_creator,
+        texture,
+        texture_size,
+        screen: options.screen(),
+        screenshots: options.screenshots(),
+        recorder: options.recorder(),
+        video: options.video()?,
+        crt: options.crt,
+        scaled: Vec::new(),
+        keymap: options.keymap,
+        keys: [false; 16],
+        gamepads: options.gamepads(),
+        hotkeys: options.hotkeys,
+        controls: options.controls(),
+        drop_roms: options.drop_roms,
+        watcher: options.watcher(),
+        visual_beep: options.visual_beep || audio.is_none(),
+        beeping: false,
+        audio,
+        volume,
+    };
+    frontend.screen.update(cpu.display());
+    frontend.upload();
+
+    let mut events = sdl.event_pump()?;
+    let mut pacer = FramePacer::new();
+
+    loop {
+        for event in events.poll_iter() {
+            match event {
+                Event::Quit { .. } => return Ok(RunEnd::of(&cpu)),
+                Event::Window {
+                    win_event: WindowEvent::SizeChanged(..),
+                    ..
+                } => frontend.upload(),
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    scancode,
+                    keymod,
+                    repeat,
+                    ..
+                } => {
+                    let action = keycode_to_hotkey(keycode, keymod)
+                        .and_then(|hotkey| frontend.hotkeys.action(hotkey));
+                    if let Some(action) = action {
+                        if !repeat && frontend.hotkey(action, &mut cpu) {
+                            return Ok(RunEnd::of(&cpu));
+                        }
+                    } else if let Some(key) = key_to_hex(&frontend.keymap, keycode, scancode) {
+                        frontend.keys[key as usize] = true;
+                    }
+                }
+                Event::DropFile { filename, .. } => {
+                    frontend.drop_rom(Path::new(&filename), &mut cpu)
+                }
+                Event::KeyUp {
+                    keycode: Some(keycode),
+                    scancode,
+                    ..
+                } => {
+                    if let Some(key) = key_to_hex(&frontend.keymap, keycode, scancode) {
+                        frontend.keys[key as usize] = false;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if rom::reload(frontend.watcher.as_mut(), &mut cpu, &mut frontend.controls) {
+            frontend.jumped(&cpu);
+        }
+        frontend.gamepads.poll();
+        let frames = frontend.controls.frames();
+        for _ in 0..frames {
+            cpu.run_frame_with(&mut frontend)?;
+        }
+        if frames > 0 {
+            frontend.show(&cpu);
+        }
+        pacer.wait();
+    }
+}
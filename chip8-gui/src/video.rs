@@ -0,0 +1,206 @@
+//! Video of a whole session, encoded by an external `ffmpeg` fed raw frames over a pipe.
+//!
+//! Frames are streamed while the game runs, so long sessions don't pile up in memory. The beep
+//! can't go down the same pipe, so it's remembered as one on/off flag per frame and muxed in
+//! afterwards by a second, quick `ffmpeg` run that copies the video as it is.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+use crate::render::Screen;
+
+const FPS: usize = 60;
+const SAMPLE_RATE: usize = 44_100;
+const BEEP_HZ: usize = 440;
+const VOLUME: i16 = i16::MAX / 10;
+/// Frames that can wait for ffmpeg before the game has to, so an encoder hiccup doesn't stall it.
+const BACKLOG: usize = 2 * FPS;
+
+/// A session being streamed to `ffmpeg`. Dropping it finishes the file.
+pub struct VideoExport {
+    path: PathBuf,
+    /// The video without sound, until it's muxed with the beep into `path`.
+    silent_path: PathBuf,
+    scale: usize,
+    ffmpeg: Child,
+    /// Frames on their way to the thread writing to ffmpeg. `None` once finished or if ffmpeg
+    /// has gone away.
+    frames: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<io::Result<()>>>,
+    /// Whether the beep was on, frame by frame.
+    beeps: Vec<bool>,
+    finished: bool,
+}
+
+impl VideoExport {
+    /// Start `ffmpeg` encoding to `path`, in whatever format its extension says, with each
+    /// display pixel `scale` pixels wide.
+    pub fn start(path: impl Into<PathBuf>, scale: usize) -> io::Result<Self> {
+        let path = path.into();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("mkv");
+        let silent_path = path.with_extension(format!("silent.{}", extension));
+
+        let mut command = ffmpeg();
+        command
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s")
+            .arg(format!(
+                "{}x{}",
+                DISPLAY_WIDTH * scale,
+                DISPLAY_HEIGHT * scale
+            ))
+            .args(["-r", &FPS.to_string(), "-i", "pipe:0"])
+            // Most players can't show RGB video, and 4:2:0 chroma is what they all expect.
+            .args(["-pix_fmt", "yuv420p"])
+            .arg(&silent_path);
+        let mut ffmpeg = command
+            .spawn()
+            .map_err(|err| io::Error::new(err.kind(), format!("couldn't run ffmpeg: {}", err)))?;
+
+        let stdin = ffmpeg.stdin.take().expect("ffmpeg's stdin is piped");
+        let (frames, received) = mpsc::sync_channel(BACKLOG);
+        let writer = thread::spawn(move || write_frames(stdin, received));
+
+        Ok(VideoExport {
+            path,
+            silent_path,
+            scale,
+            ffmpeg,
+            frames: Some(frames),
+            writer: Some(writer),
+            beeps: Vec::new(),
+            finished: false,
+        })
+    }
+
+    /// Add the screen as it looks this frame, and whether the beep is sounding. Call this once a
+    /// frame, whether or not anything changed, so that the video keeps time.
+    pub fn capture(&mut self, screen: &Screen, beeping: bool) {
+        let Some(frames) = &self.frames else {
+            return;
+        };
+
+        let mut image = Vec::new();
+        screen.resized(
+            DISPLAY_WIDTH * self.scale,
+            DISPLAY_HEIGHT * self.scale,
+            &mut image,
+        );
+        if frames.send(image).is_err() {
+            // The writer only stops early if ffmpeg does. `finish` reports why.
+            self.frames = None;
+            return;
+        }
+        self.beeps.push(beeping);
+    }
+
+    /// Stop streaming, wait for ffmpeg, and add the beep. Returns where the video was saved.
+    pub fn finish(&mut self) -> io::Result<&Path> {
+        self.finished = true;
+        self.frames = None;
+        let written = match self.writer.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(io::Error::other("the thread writing to ffmpeg panicked")),
+            None => Ok(()),
+        };
+        let status = self.ffmpeg.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("ffmpeg failed ({})", status)));
+        }
+        written?;
+
+        self.mux()?;
+        fs::remove_file(&self.silent_path)?;
+
+        Ok(&self.path)
+    }
+
+    /// Copy the silent video into the final file along with the beep, synthesized as a square
+    /// wave and piped in as raw samples.
+    fn mux(&self) -> io::Result<()> {
+        let mut ffmpeg = ffmpeg()
+            .arg("-i")
+            .arg(&self.silent_path)
+            .args(["-f", "s16le", "-ac", "1", "-ar", &SAMPLE_RATE.to_string()])
+            .args(["-i", "pipe:0", "-map", "0:v", "-map", "1:a", "-c:v", "copy"])
+            .arg(&self.path)
+            .spawn()?;
+
+        let mut stdin = ffmpeg.stdin.take().expect("ffmpeg's stdin is piped");
+        let written = write_beeps(&mut stdin, &self.beeps);
+        drop(stdin);
+
+        let status = ffmpeg.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "ffmpeg failed adding the sound ({})",
+                status
+            )));
+        }
+
+        written
+    }
+}
+
+impl Drop for VideoExport {
+    fn drop(&mut self) {
+        if self.finished {
+            return;
+        }
+
+        match self.finish() {
+            Ok(path) => eprintln!("saved video to {}", path.display()),
+            Err(err) => eprintln!("error: couldn't save video: {}", err),
+        }
+    }
+}
+
+/// `ffmpeg`, quiet except for errors, overwriting its output, and reading from a pipe.
+fn ffmpeg() -> Command {
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-y"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null());
+
+    command
+}
+
+fn write_frames(mut stdin: ChildStdin, frames: mpsc::Receiver<Vec<u8>>) -> io::Result<()> {
+    for frame in frames {
+        stdin.write_all(&frame)?;
+    }
+
+    Ok(())
+}
+
+/// The beep as 16-bit mono samples, a frame's worth at a time.
+fn write_beeps(out: &mut impl Write, beeps: &[bool]) -> io::Result<()> {
+    let per_frame = SAMPLE_RATE / FPS;
+    let half_period = SAMPLE_RATE / BEEP_HZ / 2;
+    let mut samples = Vec::with_capacity(per_frame * 2);
+
+    for (frame, &beeping) in beeps.iter().enumerate() {
+        samples.clear();
+        for i in frame * per_frame..(frame + 1) * per_frame {
+            let sample = match (beeping, i / half_period % 2) {
+                (false, _) => 0,
+                (true, 0) => VOLUME,
+                (true, _) => -VOLUME,
+            };
+            samples.extend_from_slice(&sample.to_le_bytes());
+        }
+        out.write_all(&samples)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,138 @@
+//! The buzzer's master volume and mute, remembered from one run to the next.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+use chip8_frontend::HotkeyAction;
+
+/// The volume before anything has been saved, in percent.
+pub const DEFAULT_VOLUME: u8 = 100;
+/// How far each press of a volume hotkey turns it up or down, in percent.
+const STEP: u8 = 10;
+
+/// How loud the buzzer is, whether it's playing a tone or an XO-CHIP pattern: a percentage, and
+/// whether it's muted. Changes from the volume hotkeys are saved to `volume` in the
+/// `chip8-gui` config directory, and loaded again next time.
+#[derive(Debug, Clone)]
+pub struct Volume {
+    percent: u8,
+    muted: bool,
+    gain: Gain,
+}
+
+impl Volume {
+    pub fn new(percent: u8, muted: bool) -> Self {
+        let percent = percent.min(100);
+        let volume = Volume {
+            percent,
+            muted,
+            gain: Gain(Arc::new(AtomicU32::new(0))),
+        };
+        volume.update_gain();
+
+        volume
+    }
+
+    /// The volume saved last time, or `DEFAULT_VOLUME` if there isn't one.
+    pub fn load() -> Self {
+        let saved = path().and_then(|path| fs::read_to_string(path).ok());
+        let mut words = saved.as_deref().unwrap_or_default().split_whitespace();
+        let percent = words.next().and_then(|percent| percent.parse().ok());
+        let muted = words.next() == Some("muted");
+
+        Volume::new(percent.unwrap_or(DEFAULT_VOLUME), muted)
+    }
+
+    pub fn percent(&self) -> u8 {
+        self.percent
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// The handle the audio thread reads the volume through.
+    pub fn gain(&self) -> Gain {
+        self.gain.clone()
+    }
+
+    /// Carry out `Mute`, `VolumeDown` or `VolumeUp` and save the result. Other actions are
+    /// ignored. Turning the volume up or down also unmutes.
+    pub fn apply(&mut self, action: HotkeyAction) {
+        match action {
+            HotkeyAction::Mute => self.muted = !self.muted,
+            HotkeyAction::VolumeDown => {
+                self.percent = self.percent.saturating_sub(STEP);
+                self.muted = false;
+            }
+            HotkeyAction::VolumeUp => {
+                self.percent = (self.percent + STEP).min(100);
+                self.muted = false;
+            }
+            _ => return,
+        }
+        self.update_gain();
+        self.save();
+    }
+
+    fn update_gain(&self) {
+        let gain = if self.muted {
+            0.0
+        } else {
+            self.percent as f32 / 100.0
+        };
+        self.gain.0.store(gain.to_bits(), Ordering::Relaxed);
+    }
+
+    fn save(&self) {
+        let Some(path) = path() else { return };
+        let text = if self.muted {
+            format!("{} muted\n", self.percent)
+        } else {
+            format!("{}\n", self.percent)
+        };
+
+        let saved = match path.parent() {
+            Some(dir) => fs::create_dir_all(dir).and_then(|()| fs::write(&path, text)),
+            None => fs::write(&path, text),
+        };
+        if let Err(err) = saved {
+            eprintln!(
+                "warning: couldn't save the volume to {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+}
+
+impl Default for Volume {
+    fn default() -> Self {
+        Volume::new(DEFAULT_VOLUME, false)
+    }
+}
+
+/// The volume as a multiplier for samples, from 0 when muted up to 1, which the audio thread
+/// reads as it plays so that changes are heard straight away.
+#[derive(Debug, Clone)]
+pub struct Gain(Arc<AtomicU32>);
+
+impl Gain {
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+/// Where the volume is saved: under `$XDG_CONFIG_HOME`, `~/.config` or, on Windows,
+/// `%APPDATA%`.
+fn path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(dir.join("chip8-gui").join("volume"))
+}
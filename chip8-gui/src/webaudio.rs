@@ -0,0 +1,136 @@
+//! The buzzer through the browser's WebAudio, for builds that run on the web, with the
+//! `webaudio` feature.
+//!
+//! Browsers only let a page make sound once someone has interacted with it, so the context
+//! starts out suspended and is resumed by the first click, tap or key press. Until then
+//! `is_running` is false and `Beeper` flashes the screen instead.
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use chip8_core::AudioPattern;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{
+    AudioContext, AudioContextOptions, AudioContextState, AudioProcessingEvent, ScriptProcessorNode,
+};
+
+use crate::beep::{AudioConfig, Tone, Voice};
+use crate::volume::Gain;
+
+/// The events that count as a user gesture, letting the context play.
+const GESTURES: [&str; 4] = ["pointerdown", "touchend", "keydown", "mousedown"];
+
+/// A `Voice` played through a script processor node on the page's `AudioContext`.
+pub struct WebAudio {
+    context: AudioContext,
+    processor: ScriptProcessorNode,
+    voice: Rc<RefCell<Voice>>,
+    /// Whether the buzzer is on; the processor plays silence otherwise.
+    on: Rc<Cell<bool>>,
+    _process: Closure<dyn FnMut(AudioProcessingEvent)>,
+    /// Resumes the context, on every gesture in case the browser suspends it again.
+    unlock: Closure<dyn FnMut()>,
+}
+
+impl WebAudio {
+    /// Play `tone` as `config` asks, as far as the browser allows: the sample rate is a request,
+    /// and the buffer size is rounded up to a power of two from 256 to 16384.
+    pub fn open(tone: Tone, config: AudioConfig, gain: Gain) -> Result<Self, JsValue> {
+        let options = AudioContextOptions::new();
+        if let Some(rate) = config.sample_rate {
+            options.set_sample_rate(rate as f32);
+        }
+        let context = AudioContext::new_with_context_options(&options)?;
+        // 0 lets the browser choose.
+        let buffer_size = config
+            .buffer_size
+            .map_or(0, |frames| frames.next_power_of_two().clamp(256, 16_384));
+        let processor = context
+            .create_script_processor_with_buffer_size_and_number_of_input_channels_and_number_of_output_channels(
+                buffer_size,
+                0,
+                1,
+            )?;
+
+        let voice = Rc::new(RefCell::new(Voice::new(
+            tone,
+            context.sample_rate() as u32,
+            gain,
+        )));
+        let on = Rc::new(Cell::new(false));
+        let process = {
+            let voice = Rc::clone(&voice);
+            let on = Rc::clone(&on);
+            let mut samples = Vec::new();
+            Closure::<dyn FnMut(AudioProcessingEvent)>::new(move |event: AudioProcessingEvent| {
+                let Ok(buffer) = event.output_buffer() else {
+                    return;
+                };
+                samples.resize(buffer.length() as usize, 0.0);
+                if on.get() {
+                    let mut voice = voice.borrow_mut();
+                    samples.fill_with(|| voice.next_sample());
+                } else {
+                    samples.fill(0.0);
+                }
+                let _ = buffer.copy_to_channel(&samples, 0);
+            })
+        };
+        processor.set_onaudioprocess(Some(process.as_ref().unchecked_ref()));
+        processor.connect_with_audio_node(&context.destination())?;
+
+        let unlock = {
+            let context = context.clone();
+            Closure::<dyn FnMut()>::new(move || {
+                if context.state() != AudioContextState::Running {
+                    let _ = context.resume();
+                }
+            })
+        };
+        if let Some(window) = web_sys::window() {
+            for gesture in GESTURES {
+                window
+                    .add_event_listener_with_callback(gesture, unlock.as_ref().unchecked_ref())?;
+            }
+        }
+
+        Ok(WebAudio {
+            context,
+            processor,
+            voice,
+            on,
+            _process: process,
+            unlock,
+        })
+    }
+
+    /// Whether the browser is playing the context, rather than waiting for a gesture.
+    pub fn is_running(&self) -> bool {
+        self.context.state() == AudioContextState::Running
+    }
+
+    pub fn set_on(&self, on: bool) {
+        self.on.set(on);
+    }
+
+    pub fn set_pattern(&self, pattern: Option<AudioPattern>) {
+        self.voice.borrow_mut().set_pattern(pattern);
+    }
+}
+
+impl Drop for WebAudio {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            for gesture in GESTURES {
+                let _ = window.remove_event_listener_with_callback(
+                    gesture,
+                    self.unlock.as_ref().unchecked_ref(),
+                );
+            }
+        }
+        self.processor.set_onaudioprocess(None);
+        let _ = self.processor.disconnect();
+        let _ = self.context.close();
+    }
+}
@@ -0,0 +1,437 @@
+//! The winit + pixels frontend: a pure-Rust window, drawn on the GPU through wgpu.
+
+use std::error::Error;
+use std::path::Path;
+use std::sync::Arc;
+
+use chip8_core::{AudioPattern, AudioSink, Chip8, DisplaySink, KeyInput, RunEnd};
+
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8_frontend::{
+    Controls, FramePacer, Hotkey, HotkeyAction, HotkeyKey, Hotkeys, Keymap, RomWatcher,
+};
+use pixels::wgpu::Extent3d;
+use pixels::{Pixels, SurfaceTexture, TextureError};
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey};
+use winit::window::{Fullscreen, Window, WindowId};
+
+use crate::beep::Beeper;
+use crate::gamepad::Gamepads;
+use crate::options::Options;
+use crate::recording::Recorder;
+use crate::render::{self, Screen};
+use crate::rom;
+use crate::screenshot::Screenshots;
+use crate::video::VideoExport;
+use crate::volume::Volume;
+
+/// What `run_frame_with` talks to. Borrowed from `App` for the length of one frame.
+struct Io<'a> {
+    keys: &'a [bool; 16],
+    gamepads: &'a Gamepads,
+    beeper: &'a Beeper,
+}
+
+// `App` takes the display from the `Screen` after every frame instead, so that phosphor fading
+// keeps going between draws.
+impl DisplaySink for Io<'_> {}
+
+impl KeyInput for Io<'_> {
+    fn is_down(&mut self, key: u8) -> bool {
+        self.keys[key as usize] || self.gamepads.is_down(key)
+    }
+}
+
+impl AudioSink for Io<'_> {
+    fn start_beep(&mut self) {
+        self.beeper.start();
+    }
+
+    fn stop_beep(&mut self) {
+        self.beeper.stop();
+    }
+
+    fn set_pattern(&mut self, pattern: Option<AudioPattern>) {
+        self.beeper.set_pattern(pattern);
+    }
+}
+
+struct App {
+    cpu: Chip8,
+    screen: Screen,
+    screenshots: Screenshots,
+    recorder: Recorder,
+    video: Option<VideoExport>,
+    crt: bool,
+    /// Scratch space for the image scaled up by the CRT filter.
+    scaled: Vec<u8>,
+    /// The initial window size, in logical pixels.
+    size: (u32, u32),
+    /// Whether to start out fullscreen.
+    fullscreen: bool,
+    window: Option<Arc<Window>>,
+    pixels: Option<Pixels<'static>>,
+    /// Whether a ROM file dropped on the window is started.
+    drop_roms: bool,
+    watcher: Option<RomWatcher>,
+    keymap: Keymap,
+    keys: [bool; 16],
+    gamepads: Gamepads,
+    beeper: Beeper,
+    volume: Volume,
+    hotkeys: Hotkeys,
+    /// The modifier keys held, which winit reports separately from the keys themselves.
+    modifiers: ModifiersState,
+    controls: Controls,
+    pacer: FramePacer,
+    error: Option<Box<dyn Error>>,
+}
+
+/// Copy the screen into `pixels`, resizing its buffer to match when the display changes
+/// resolution. Normally that's at 1x and pixels scales it up, but with the CRT filter on it's
+/// drawn at the window's scale, so that the scanlines are window pixels.
+fn fill(
+    pixels: &mut Pixels<'_>,
+    window: &Window,
+    screen: &Screen,
+    crt: bool,
+    scaled: &mut Vec<u8>,
+) -> Result<(), TextureError> {
+    let scale = if crt {
+        let size = window.inner_size();
+        screen.viewport(size.width, size.height).scale
+    } else {
+        1
+    };
+    let (width, height) = (
+        screen.width() as u32 * scale,
+        screen.height() as u32 * scale,
+    );
+
+    if pixels.texture().size()
+        != (Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        })
+    {
+        pixels.resize_buffer(width, height)?;
+    }
+
+    if crt {
+        screen.scaled(scale as usize, true, scaled);
+        pixels.frame_mut().copy_from_slice(scaled);
+    } else {
+        pixels.frame_mut().copy_from_slice(screen.rgba());
+    }
+
+    Ok(())
+}
+
+impl App {
+    /// Redraw the window from the screen, e.g. after it's been resized or the CRT toggled.
+    fn refill(&mut self, event_loop: &ActiveEventLoop) {
+        let (Some(window), Some(pixels)) = (&self.window, &mut self.pixels) else {
+            return;
+        };
+
+        match fill(pixels, window, &self.screen, self.crt, &mut self.scaled) {
+            Ok(()) => window.request_redraw(),
+            Err(err) => self.fail(event_loop, err),
+        }
+    }
+
+    fn hotkey(&mut self, event_loop: &ActiveEventLoop, action: HotkeyAction) {
+        match action {
+            HotkeyAction::Quit => event_loop.exit(),
+            HotkeyAction::Crt => {
+                self.crt = !self.crt;
+                self.refill(event_loop);
+            }
+            HotkeyAction::RecordGif => self.recorder.toggle(&self.screen),
+            HotkeyAction::Fullscreen => {
+                if let Some(window) = &self.window {
+                    toggle_fullscreen(window);
+                }
+            }
+            HotkeyAction::Screenshot => self.screenshots.take(&self.screen),
+            HotkeyAction::Mute | HotkeyAction::VolumeDown | HotkeyAction::VolumeUp => {
+                self.volume.apply(action)
+            }
+            action => {
+                self.controls.apply(action, &mut self.cpu);
+                self.jumped(event_loop);
+            }
+        }
+    }
+
+    /// Show a reset, a loaded state or a new ROM straight away, even while paused.
+    fn jumped(&mut self, event_loop: &ActiveEventLoop) {
+        self.beeper
+            .follow(!self.controls.is_paused() && self.cpu.timers().sound > 0);
+        self.screen.update(self.cpu.display());
+        self.screen.set_inverted(self.beeper.flashing());
+        self.refill(event_loop);
+    }
+
+    /// Start the ROM file dropped on the window at `path`, if that's allowed.
+    fn drop_rom(&mut self, event_loop: &ActiveEventLoop, path: &Path) {
+        if self.drop_roms && rom::load(path, &mut self.cpu, &mut self.controls) {
+            self.jumped(event_loop);
+        }
+    }
+
+    fn fail(&mut self, event_loop: &ActiveEventLoop, err: impl Into<Box<dyn Error>>) {
+        self.error = Some(err.into());
+        event_loop.exit();
+    }
+}
+
+impl ApplicationHandler for App {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+
+        let attributes = Window::default_attributes()
+            .with_title("CHIP-8")
+            .with_inner_size(LogicalSize::new(self.size.0, self.size.1))
+            .with_fullscreen(self.fullscreen.then_some(Fullscreen::Borderless(None)));
+        let window = match event_loop.create_window(attributes) {
+            Ok(window) => Arc::new(window),
+            Err(err) => return self.fail(event_loop, err),
+        };
+
+        let size = window.inner_size();
+        let surface = SurfaceTexture::new(size.width, size.height, window.clone());
+        let mut pixels = match Pixels::new(DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32, surface) {
+            Ok(pixels) => pixels,
+            Err(err) => return self.fail(event_loop, err),
+        };
+        let [r, g, b] = render::srgb_to_linear(self.screen.palette().background);
+        pixels.clear_color(pixels::wgpu::Color { r, g, b, a: 1.0 });
+        self.screen.update(self.cpu.display());
+
+        self.window = Some(window);
+        self.pixels = Some(pixels);
+        self.refill(event_loop);
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::KeyboardInput { event, .. } => {
+                let down = event.state == ElementState::Pressed;
+                let action = key_to_hotkey(&event.logical_key, self.modifiers)
+                    .and_then(|hotkey| self.hotkeys.action(hotkey));
+
+                if let Some(action) = action {
+                    if down && !event.repeat {
+                        self.hotkey(event_loop, action);
+                    }
+                } else if let Some(key) = key_to_hex(&self.keymap, &event) {
+                    self.keys[key as usize] = down;
+                }
+            }
+            WindowEvent::DroppedFile(path) => self.drop_rom(event_loop, &path),
+            // Sizes are physical pixels, so moving to a monitor with a different scale factor
+            // comes through here too. pixels refits the display at a whole-number scale.
+            WindowEvent::Resized(size) => {
+                if let Some(pixels) = &mut self.pixels {
+                    if let Err(err) = pixels.resize_surface(size.width, size.height) {
+                        return self.fail(event_loop, err);
+                    }
+                }
+                if self.crt {
+                    self.refill(event_loop);
+                } else if let Some(window) = &self.window {
+                    window.request_redraw();
+                }
+            }
+            WindowEvent::RedrawRequested => {
+                if let Some(pixels) = &self.pixels {
+                    if let Err(err) = pixels.render() {
+                        self.fail(event_loop, err);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if rom::reload(self.watcher.as_mut(), &mut self.cpu, &mut self.controls) {
+            self.jumped(event_loop);
+        }
+
+        let (Some(window), Some(pixels)) = (&self.window, &mut self.pixels) else {
+            return;
+        };
+
+        if self.pacer.poll() && self.controls.frames() > 0 {
+            self.gamepads.poll();
+            let mut io = Io {
+                keys: &self.keys,
+                gamepads: &self.gamepads,
+                beeper: &self.beeper,
+            };
+
+            for _ in 0..self.controls.frames() {
+                if let Err(err) = self.cpu.run_frame_with(&mut io) {
+                    return self.fail(event_loop, err);
+                }
+            }
+            let mut changed = self.screen.update(self.cpu.display());
+            changed |= self.screen.set_inverted(self.beeper.flashing());
+            self.recorder.capture(&self.screen);
+            if let Some(video) = &mut self.video {
+                video.capture(&self.screen, self.cpu.timers().sound > 0);
+            }
+            if changed {
+                if let Err(err) = fill(pixels, window, &self.screen, self.crt, &mut self.scaled) {
+                    return self.fail(event_loop, err);
+                }
+                window.request_redraw();
+            }
+        }
+
+        event_loop.set_control_flow(ControlFlow::WaitUntil(self.pacer.deadline()));
+    }
+}
+
+/// The hex key for a winit key event: by where the key is, if the keymap goes by position, and
+/// otherwise by the character it types.
+fn key_to_hex(keymap: &Keymap, event: &KeyEvent) -> Option<u8> {
+    if let PhysicalKey::Code(code) = event.physical_key {
+        let position = code_position(code);
+        if let Some(key) = position.and_then(|(row, column)| keymap.position_key(row, column)) {
+            return Some(key);
+        }
+    }
+
+    match &event.logical_key {
+        Key::Character(s) => keymap.hex_key(s.chars().next()?),
+        _ => None,
+    }
+}
+
+/// Where a key is in the block at the left of the keyboard, as `Keymap::position_key` takes it.
+fn code_position(code: KeyCode) -> Option<(u8, u8)> {
+    let rows = [
+        [
+            KeyCode::Digit1,
+            KeyCode::Digit2,
+            KeyCode::Digit3,
+            KeyCode::Digit4,
+        ],
+        [KeyCode::KeyQ, KeyCode::KeyW, KeyCode::KeyE, KeyCode::KeyR],
+        [KeyCode::KeyA, KeyCode::KeyS, KeyCode::KeyD, KeyCode::KeyF],
+        [KeyCode::KeyZ, KeyCode::KeyX, KeyCode::KeyC, KeyCode::KeyV],
+    ];
+
+    rows.iter().enumerate().find_map(|(row, codes)| {
+        let column = codes.iter().position(|&c| c == code)?;
+        Some((row as u8, column as u8))
+    })
+}
+
+/// The hotkey for a winit key with `modifiers` held.
+fn key_to_hotkey(key: &Key, modifiers: ModifiersState) -> Option<Hotkey> {
+    let key = match key {
+        Key::Named(NamedKey::Escape) => HotkeyKey::Escape,
+        Key::Named(NamedKey::Tab) => HotkeyKey::Tab,
+        Key::Named(NamedKey::Space) => HotkeyKey::Space,
+        Key::Named(NamedKey::Enter) => HotkeyKey::Enter,
+        Key::Named(NamedKey::Backspace) => HotkeyKey::Backspace,
+        Key::Named(named) => {
+            const F_KEYS: [NamedKey; 24] = [
+                NamedKey::F1,
+                NamedKey::F2,
+                NamedKey::F3,
+                NamedKey::F4,
+                NamedKey::F5,
+                NamedKey::F6,
+                NamedKey::F7,
+                NamedKey::F8,
+                NamedKey::F9,
+                NamedKey::F10,
+                NamedKey::F11,
+                NamedKey::F12,
+                NamedKey::F13,
+                NamedKey::F14,
+                NamedKey::F15,
+                NamedKey::F16,
+                NamedKey::F17,
+                NamedKey::F18,
+                NamedKey::F19,
+                NamedKey::F20,
+                NamedKey::F21,
+                NamedKey::F22,
+                NamedKey::F23,
+                NamedKey::F24,
+            ];
+            let n = F_KEYS.iter().position(|f| f == named)?;
+            HotkeyKey::F(n as u8 + 1)
+        }
+        Key::Character(s) => HotkeyKey::Char(s.chars().next()?),
+        _ => return None,
+    };
+
+    Some(
+        Hotkey::new(key)
+            .with_ctrl(modifiers.control_key())
+            .with_shift(modifiers.shift_key()),
+    )
+}
+
+/// Go borderless fullscreen on the current monitor, or back to a window. The display is
+/// letterboxed either way.
+fn toggle_fullscreen(window: &Window) {
+    if window.fullscreen().is_some() {
+        window.set_fullscreen(None);
+    } else {
+        window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+    }
+}
+
+pub fn run(cpu: Chip8, options: &Options) -> Result<RunEnd, Box<dyn Error>> {
+    let event_loop = EventLoop::new()?;
+    let volume = options.volume();
+    let mut app = App {
+        cpu,
+        screen: options.screen(),
+        screenshots: options.screenshots(),
+        recorder: options.recorder(),
+        video: options.video()?,
+        crt: options.crt,
+        scaled: Vec::new(),
+        size: options.window_size(),
+        fullscreen: options.fullscreen,
+        window: None,
+        pixels: None,
+        drop_roms: options.drop_roms,
+        watcher: options.watcher(),
+        keymap: options.keymap,
+        keys: [false; 16],
+        gamepads: options.gamepads(),
+        beeper: options.beeper(&volume),
+        volume,
+        hotkeys: options.hotkeys,
+        modifiers: ModifiersState::empty(),
+        controls: options.controls(),
+        pacer: FramePacer::new(),
+        error: None,
+    };
+
+    event_loop.run_app(&mut app)?;
+
+    match app.error {
+        Some(err) => Err(err),
+        None => Ok(RunEnd::of(&app.cpu)),
+    }
+}